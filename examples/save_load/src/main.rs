@@ -230,9 +230,11 @@ async fn main() {
             println!("Save.");
             saved_state = Some(save_world(&world));
         }
-        if is_key_released(KeyCode::F9) && saved_state.is_some() {
+        if is_key_released(KeyCode::F9)
+            && let Some(saved_state) = &saved_state
+        {
             println!("Load.");
-            world = load_world(saved_state.as_ref().unwrap())
+            world = load_world(saved_state)
         }
 
         if is_key_released(KeyCode::Q) {
@@ -1,4 +1,7 @@
-use std::any::TypeId;
+use std::{
+    any::TypeId,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use hi_sparse_bitset::reduce;
 use rustc_hash::FxHashMap as HashMap;
@@ -6,10 +9,11 @@ use rustc_hash::FxHashSet as HashSet;
 
 use crate::{
     archetype::{Archetype, ArchetypeId, ArchetypeRow},
-    component::{Component, ComponentId},
+    component::{CleanupPolicy, Component, ComponentId},
     entity_store::{Entity, EntityId, EntityStore},
     layout_vec::LayoutVec,
     relation_vec::RelationVec,
+    traversal::{Traversal, TraversalOrder},
     util::get_mut_2,
 };
 
@@ -29,8 +33,18 @@ pub struct Bookkeeping {
     pub entities: EntityStore,
     /// maps to the Archetype which has all the components in the vec and just those
     pub exact_archetype: HashMap<Vec<ComponentId>, ArchetypeId>,
+    /// unique for every Bookkeeping, lets cached queries notice that they run on another world
+    pub world_id: u64,
+    /// incremented whenever an archetype is created
+    /// archetypes are never removed, so cached queries only need to look at the new ones
+    pub archetype_generation: u64,
+    /// true once a relation with a `CleanupPolicy::Panic` is registered,
+    /// until then `destroy` doesn't need to look for partners that make it panic
+    pub has_panic_policy: bool,
 }
 
+static NEXT_WORLD_ID: AtomicU64 = AtomicU64::new(1);
+
 const EMPTY_ARCHETYPE_ID: ArchetypeId = ArchetypeId(0);
 
 // ATTENTION: no function in bookkeeping may be generic over types
@@ -49,6 +63,9 @@ impl Bookkeeping {
             archetypes,
             entities: EntityStore::new(),
             exact_archetype,
+            world_id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+            archetype_generation: 0,
+            has_panic_policy: false,
         }
     }
 
@@ -108,6 +125,14 @@ impl Bookkeeping {
         self.component_map.get(&tid).copied()
     }
 
+    /// The entity standing for a component type, see `World::type_entity`.
+    /// Returns a dead entity if there is none, so that queries find no rows for it.
+    pub fn type_entity_or_dead(&self, tid: TypeId) -> Entity {
+        self.get_component_id(tid)
+            .and_then(|cid| self.components[cid.as_index()].type_entity)
+            .unwrap_or(Entity::DEAD)
+    }
+
     pub fn get_component_id_unchecked(&self, tid: TypeId) -> ComponentId {
         self.component_map
             .get(&tid)
@@ -218,12 +243,13 @@ impl Bookkeeping {
         let columns = a_components
             .iter()
             .map(|id| &self.components[id.as_index()])
-            .map(|c| LayoutVec::new(c.layout, c.drop_fn.clone()))
+            .map(|c| LayoutVec::new(c.layout, c.drop_fn))
             .collect::<Vec<_>>();
 
         let new_archetype = Archetype::new(a_components, columns);
         self.archetypes.push(new_archetype);
         self.exact_archetype.insert(c_ids, new_aid);
+        self.archetype_generation += 1;
         new_aid
     }
 
@@ -347,9 +373,83 @@ impl Bookkeeping {
         }
     }
 
+    /// Like `matching_archetypes`, but the archetype also needs to contain
+    /// at least one component of each `any_of` group.
+    pub fn matching_archetypes_any(
+        &self,
+        with: &[ComponentId],
+        without: &[ComponentId],
+        any_of: &[&[ComponentId]],
+    ) -> Vec<ArchetypeId> {
+        use hi_sparse_bitset::ops::Or;
+        let mut ids = if with.is_empty() && without.is_empty() {
+            (0..self.archetypes.len() as u32).map(ArchetypeId).collect()
+        } else {
+            self.matching_archetypes(with, without)
+        };
+        for group in any_of {
+            let branch_sets = group
+                .iter()
+                .copied()
+                .map(|cid| self.components[cid.as_index()].get_archetype_bitset(cid));
+            // union of the branches
+            let Some(b_any) = reduce(Or, branch_sets) else {
+                return Vec::new();
+            };
+            ids.retain(|id| b_any.contains(id.as_index()));
+        }
+        ids
+    }
+
     pub fn destroy(&mut self, e: Entity) {
         self.realize_deferred(); // need to do that so we don't break the free list
 
+        if self.entities.is_alive(e) {
+            // panics before anything is changed, also for entities reached by cascades
+            if self.has_panic_policy {
+                self.check_cleanup_panics(e, &mut HashSet::default());
+            }
+            self.destroy_checked(e);
+        }
+    }
+
+    /// Panics if destroying `e` reaches an entity that still has partners
+    /// in a relation with `CleanupPolicy::Panic`.
+    /// Follows the cascades in the same order as `destroy_checked`,
+    /// partners that are destroyed earlier are gone by then.
+    fn check_cleanup_panics(&self, e: Entity, destroyed: &mut HashSet<u32>) {
+        destroyed.insert(e.id.0);
+        let (a_id, a_row) = self.entities.get_archetype(e);
+        let a = &self.archetypes[a_id.0 as usize];
+        let mut to_destroy = Vec::new();
+        for (index, cid) in a.components.iter().enumerate() {
+            if cid.is_relation() {
+                let ptr = unsafe { a.columns[index].get(a_row.0) } as *const RelationVec;
+                let vec = unsafe { &*ptr };
+                let mut partners = vec.iter().filter(|id| !destroyed.contains(*id));
+                match cid.cleanup_policy() {
+                    CleanupPolicy::Remove => {}
+                    CleanupPolicy::Destroy => to_destroy.extend(partners),
+                    CleanupPolicy::Panic => {
+                        if partners.next().is_some() {
+                            let name = &self.components[cid.as_index()].name;
+                            let side = if cid.is_target() { "target" } else { "origin" };
+                            panic!("Can't destroy {e:?}, it is the {side} of a {name} relation.");
+                        }
+                    }
+                }
+            }
+        }
+        for other_id in to_destroy {
+            if !destroyed.contains(&other_id) {
+                let other = self.entities.get_from_id(EntityId(other_id));
+                self.check_cleanup_panics(other, destroyed);
+            }
+        }
+    }
+
+    /// `destroy` after the cleanup policies are checked
+    fn destroy_checked(&mut self, e: Entity) {
         if self.entities.is_alive(e) {
             let (a_id, a_row) = self.entities.get_archetype(e);
             let a = &self.archetypes[a_id.0 as usize];
@@ -366,12 +466,20 @@ impl Bookkeeping {
                     debug_assert!(!vec.is_empty());
                     let flipped = cid.flip_target();
                     for other_id in vec.iter() {
-                        to_delete.push((flipped, EntityId(*other_id)));
+                        // self relations are gone with the row
+                        if *other_id != e.id.0 {
+                            to_delete.push((flipped, EntityId(*other_id)));
+                        }
                     }
-                    if cid.is_cascading() {
-                        for other_id in vec.iter() {
-                            to_destroy.push(EntityId(*other_id));
+                    match cid.cleanup_policy() {
+                        CleanupPolicy::Remove => {}
+                        CleanupPolicy::Destroy => {
+                            for other_id in vec.iter() {
+                                to_destroy.push(EntityId(*other_id));
+                            }
                         }
+                        // already checked by `check_cleanup_panics`
+                        CleanupPolicy::Panic => {}
                     }
                 }
             }
@@ -403,17 +511,62 @@ impl Bookkeeping {
 
             // cascading destruction if necessary
             for other_id in to_destroy {
+                // may already be destroyed by an earlier cascade
+                if !self.entities.is_alive_id(other_id) {
+                    continue;
+                }
                 let other_e = self.entities.get_from_id(other_id);
-                self.destroy(other_e);
+                self.destroy_checked(other_e);
             }
         }
     }
 
+    /// Panics if the relation is `ACYCLIC` and the new relation would close a cycle.
+    #[track_caller]
     pub fn add_relation(&mut self, cid: ComponentId, from: Entity, to: Entity) {
+        if let Err(err) = self.try_add_relation(cid, from, to) {
+            let name = &self.components[cid.as_index()].name;
+            panic!("Can't add relation {name}: {err}");
+        }
+    }
+
+    /// Returns the relations that were unlinked because of `EXCLUSIVE` and `EXCLUSIVE_TARGET`.
+    pub fn try_add_relation(
+        &mut self,
+        cid: ComponentId,
+        from: Entity,
+        to: Entity,
+    ) -> Result<Displaced, AddRelationError> {
         debug_assert!(cid.is_relation());
         debug_assert!(!cid.is_target());
+        if cid.is_acyclic() && self.closes_cycle(cid, from, to) {
+            return Err(AddRelationError::Cycle { from, to });
+        }
+        let mut displaced = Displaced::default();
+        if cid.is_exclusive() && self.has_component(from, cid) {
+            // the old target must forget about the origin
+            let ptr = self.get_component(from, cid) as *const RelationVec;
+            let old_target = unsafe { &*ptr }[0];
+            if old_target != to.id.0 {
+                let old = self.entities.get_from_id(EntityId(old_target));
+                self.remove_relation(cid, from, old);
+                displaced.old_target = Some(old);
+            }
+        }
+        let target_cid = cid.flip_target();
+        if cid.is_exclusive_target() && self.has_component(to, target_cid) {
+            // the old origin must forget about the target,
+            // for exclusive symmetric relations this breaks up the old pair of the target
+            let ptr = self.get_component(to, target_cid) as *const RelationVec;
+            let old_origin = unsafe { &*ptr }[0];
+            if old_origin != from.id.0 {
+                let old = self.entities.get_from_id(EntityId(old_origin));
+                self.remove_relation(cid, old, to);
+                displaced.old_origin = Some(old);
+            }
+        }
         inner(self, cid, from, to);
-        inner(self, cid.flip_target(), to, from);
+        inner(self, target_cid, to, from);
         // inner function because removing the relationship component
         // from Origin and Target works the same, just gotta swap arguments
         fn inner(this: &mut Bookkeeping, cid: ComponentId, e: Entity, other: Entity) {
@@ -437,6 +590,15 @@ impl Bookkeeping {
                 }
             }
         }
+        Ok(displaced)
+    }
+
+    /// true if `to` already reaches `from`, so that `from -> to` would close a cycle
+    fn closes_cycle(&self, origin_cid: ComponentId, from: Entity, to: Entity) -> bool {
+        from.id == to.id
+            || Traversal::new(self, origin_cid, TraversalOrder::DepthFirst, None)
+                .from_origin(to.id)
+                .any(|(id, _)| id == from.id)
     }
 
     pub fn remove_relation(&mut self, cid: ComponentId, from: Entity, to: Entity) {
@@ -472,26 +634,9 @@ impl Bookkeeping {
             }
             if origin_cid.is_transitive() {
                 // now we need to follow the transitive relationship
-                let mut visited = HashSet::default();
-                let mut work = Vec::new();
-                work.extend_from_slice(rel_vec);
-                visited.extend(work.iter().copied());
-                while !work.is_empty() {
-                    let current = EntityId(work.pop().unwrap());
-                    let comp_opt = self.get_component_opt_unchecked(current, origin_cid);
-                    if let Some(ptr) = comp_opt {
-                        let rel_vec = unsafe { &*(ptr as *const RelationVec) };
-                        if rel_vec.contains(&to.id.0) {
-                            return true;
-                        }
-                        for id in &rel_vec[..] {
-                            if !visited.contains(id) {
-                                work.push(*id);
-                                visited.insert(*id);
-                            }
-                        }
-                    }
-                }
+                return Traversal::new(self, origin_cid, TraversalOrder::DepthFirst, None)
+                    .from_origin(from.id)
+                    .any(|(id, _)| id == to.id);
             }
         }
         return false;
@@ -516,26 +661,77 @@ impl Bookkeeping {
         None
     }
 
+    /// Number of directly related partners
+    pub fn relation_partner_count(&self, relation_cid: ComponentId, e: Entity) -> usize {
+        if self.has_component(e, relation_cid) {
+            let ptr = self.get_component(e, relation_cid) as *const RelationVec;
+            let rel_vec = unsafe { &*ptr };
+            return rel_vec.len() as usize;
+        }
+        0
+    }
+
     /// Returns all directly related pairs
     /// DOES NOT follow transitive relations
     pub fn relation_pairs(&self, tid: TypeId) -> Vec<(Entity, Entity)> {
         let cid = self.get_component_id(tid).unwrap(); // TODO error msg
-        let c = &self.components[cid.as_index()];
+        self.relation_pairs_iter(cid).collect()
+    }
+
+    /// Lazily returns all directly related pairs
+    /// DOES NOT follow transitive relations
+    pub fn relation_pairs_iter(
+        &self,
+        relation_cid: ComponentId,
+    ) -> impl Iterator<Item = (Entity, Entity)> + use<'_> {
+        let c = &self.components[relation_cid.as_index()];
         let archetypes = c.get_archetypes();
         let entities = archetypes
             .flat_map(|aid| self.archetypes[aid.as_index()].entities.iter())
             .map(|id| self.entities.get_from_id(*id));
-        entities
-            .flat_map(|e| {
-                self.relation_partners(cid, e)
-                    .into_iter()
-                    .flatten()
-                    .map(move |other| (e, other))
-            })
-            .collect()
+        entities.flat_map(move |e| {
+            self.relation_partners(relation_cid, e)
+                .into_iter()
+                .flatten()
+                .map(move |other| (e, other))
+        })
     }
 }
 
+/// The relations `try_add_relation` unlinked to make room for the new one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Displaced {
+    /// the old target of `from`, if the relation is `EXCLUSIVE`
+    pub old_target: Option<Entity>,
+    /// the old origin of `to`, if the relation is `EXCLUSIVE_TARGET`
+    pub old_origin: Option<Entity>,
+}
+
+/// Error Type for `try_add_relation`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddRelationError {
+    /// The relation is `ACYCLIC` and `to` already reaches `from`.
+    Cycle {
+        /// origin of the rejected relation
+        from: Entity,
+        /// target of the rejected relation
+        to: Entity,
+    },
+}
+
+impl std::fmt::Display for AddRelationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddRelationError::Cycle { from, to } => write!(
+                f,
+                "{from:?} -> {to:?} would close a cycle in an ACYCLIC relation."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AddRelationError {}
+
 pub(crate) enum EnsureComponentResult {
     /// The component was freshly created.
     NewComponent(*mut u8),
@@ -1,8 +1,8 @@
 use std::{alloc::Layout, fmt};
 
 use crate::{
-    archetype::ArchetypeId, layout_vec::layout_vec_args, relation_vec::RelationVec,
-    world::ReregisterError,
+    archetype::ArchetypeId, entity_store::Entity, layout_vec::layout_vec_args,
+    relation_vec::RelationVec, world::ReregisterError,
 };
 
 type BitSet = hi_sparse_bitset::BitSet<hi_sparse_bitset::config::_128bit>;
@@ -44,13 +44,79 @@ pub const CASCADING_DESTRUCT: u32 = RELATION >> 4;
 /// A relation is symmetric if `Rel(a,b)` and `Rel(b,c)` implies `Rel(a,c)`.
 pub const TRANSITIVE: u32 = RELATION >> 5;
 
+/// Marks acyclic relationships.
+/// Adding `Rel(a,b)` panics if `a` can already be reached from `b`,
+/// because the new relation would close a cycle.
+/// `World::try_add_relation` returns an error instead.
+///
+/// For example if the relation `ChildOf(child, parent)` is acyclic
+/// then no entity can become its own ancestor.
+pub const ACYCLIC: u32 = RELATION >> 6;
+
+/// Marks relationships where a target can only have a single origin.
+///
+/// For example if the relation `Equips(hand, sword)` is exclusive on the target
+/// then a sword can only be held by a single hand.
+/// Relating another hand to the sword takes it away from the old hand.
+pub const EXCLUSIVE_TARGET: u32 = RELATION >> 7;
+
+/// Marks one-to-one relationships,
+/// every origin has at most one target and every target has at most one origin.
+pub const ONE_TO_ONE: u32 = EXCLUSIVE | EXCLUSIVE_TARGET;
+
+/// Mark a relationship with cascading destruction from the target side.
+/// When a target in such a relation gets destroyed,
+/// then all its origins in that relation also get destroyed.
+///
+/// For example if the relation `AppliesTo(buff, unit)` is target cascading
+/// then once the unit is destroyed all buffs applied to it are also destroyed.
+pub const TARGET_CASCADING_DESTRUCT: u32 = RELATION >> 8;
+
+/// Destroying an origin that still has targets in this relation panics.
+///
+/// Useful for relations that must be removed explicitly before the origin goes away.
+/// Origins reached by cascading destruction are checked too,
+/// the panic happens before any entity is destroyed.
+pub const PANIC_ON_ORIGIN_DESTRUCT: u32 = RELATION >> 9;
+
+/// Destroying a target that still has origins in this relation panics.
+///
+/// For example if `InstanceOf(item, prefab)` panics on target destruction
+/// then a prefab can't be destroyed while there are still items made from it.
+/// Targets reached by cascading destruction are checked too,
+/// the panic happens before any entity is destroyed.
+pub const PANIC_ON_TARGET_DESTRUCT: u32 = RELATION >> 10;
+
+/// What happens to the relation partners of an entity when it gets destroyed.
+///
+/// The policy of a relation is set with flags:
+///
+/// | destroyed entity | `Remove` | `Destroy`                   | `Panic`                    |
+/// |------------------|----------|-----------------------------|----------------------------|
+/// | origin           | default  | `CASCADING_DESTRUCT`        | `PANIC_ON_ORIGIN_DESTRUCT` |
+/// | target           | default  | `TARGET_CASCADING_DESTRUCT` | `PANIC_ON_TARGET_DESTRUCT` |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupPolicy {
+    /// The partners only lose the relation.
+    Remove,
+    /// The partners are destroyed too.
+    Destroy,
+    /// Destroying an entity that still has partners panics.
+    Panic,
+}
+
 impl ComponentId {
-    /// 24 bit ought to be enough component ids
+    /// 20 bit ought to be enough component ids
     /// the rest is reserved for flags
-    const MASK: u32 = 0b00000000111111111111111111111111;
+    const MASK: u32 = 0b00000000000011111111111111111111;
 
+    #[track_caller]
     pub fn new(id: u32) -> Self {
-        debug_assert!(id <= Self::MASK);
+        assert!(
+            id <= Self::MASK,
+            "Too many components, at most {} can be registered.",
+            Self::MASK + 1
+        );
         Self(id)
     }
 
@@ -101,6 +167,20 @@ impl ComponentId {
         self.is_relation() && (self.0 & EXCLUSIVE) > 0 && !self.is_target()
     }
 
+    #[must_use]
+    pub fn set_exclusive_target(self) -> Self {
+        debug_assert!(self.is_relation());
+        Self(self.0 ^ EXCLUSIVE_TARGET)
+    }
+
+    /// only returns true for the relation origin
+    ///
+    /// Exclusive symmetric relations are pairings, so they are exclusive on both ends.
+    pub fn is_exclusive_target(&self) -> bool {
+        let pairing = self.is_symmetric() && (self.0 & EXCLUSIVE) > 0;
+        self.is_relation() && ((self.0 & EXCLUSIVE_TARGET) > 0 || pairing) && !self.is_target()
+    }
+
     #[must_use]
     pub fn set_cascading(self) -> Self {
         debug_assert!(self.is_relation());
@@ -112,6 +192,26 @@ impl ComponentId {
         self.is_relation() && (self.0 & CASCADING_DESTRUCT) > 0 && !self.is_target()
     }
 
+    /// What happens to the partners in this relation,
+    /// when the entity on this side of the relation is destroyed.
+    ///
+    /// Symmetric relations have no target side, they use the origin policy.
+    pub fn cleanup_policy(&self) -> CleanupPolicy {
+        debug_assert!(self.is_relation());
+        let (destroy, panic) = if self.is_target() {
+            (TARGET_CASCADING_DESTRUCT, PANIC_ON_TARGET_DESTRUCT)
+        } else {
+            (CASCADING_DESTRUCT, PANIC_ON_ORIGIN_DESTRUCT)
+        };
+        if self.0 & panic > 0 {
+            CleanupPolicy::Panic
+        } else if self.0 & destroy > 0 {
+            CleanupPolicy::Destroy
+        } else {
+            CleanupPolicy::Remove
+        }
+    }
+
     #[must_use]
     pub fn set_symmetric(self) -> Self {
         debug_assert!(self.is_relation());
@@ -133,6 +233,16 @@ impl ComponentId {
         self.is_relation() && (self.0 & TRANSITIVE) > 0
     }
 
+    #[must_use]
+    pub fn set_acyclic(self) -> Self {
+        debug_assert!(self.is_relation());
+        Self(self.0 ^ ACYCLIC)
+    }
+
+    pub fn is_acyclic(&self) -> bool {
+        self.is_relation() && (self.0 & ACYCLIC) > 0
+    }
+
     #[track_caller]
     pub fn from_usize(id: usize) -> Self {
         Self::new(u32::try_from(id).unwrap())
@@ -147,6 +257,10 @@ impl ComponentId {
     }
 }
 
+/// Type erased formatting function for a component
+/// The pointer points to the `RefCell<T>` stored in the archetype column.
+pub type DebugFn = fn(*const u8, &mut fmt::Formatter<'_>) -> Result<(), fmt::Error>;
+
 /// MetaData about a Component registered in froql
 pub struct Component {
     /// ComponentID for this component
@@ -164,7 +278,9 @@ pub struct Component {
     /// separately from origin archetypes
     target_archetypes: Box<BitSet>,
     /// formats debug output for this component type
-    pub debug_fn: Option<fn(*const u8, &mut fmt::Formatter<'_>) -> Result<(), fmt::Error>>,
+    pub debug_fn: Option<DebugFn>,
+    /// the entity standing for this component type, created by `World::type_entity`
+    pub type_entity: Option<Entity>,
 }
 
 impl Component {
@@ -178,6 +294,7 @@ impl Component {
             archetypes: Box::new(BitSet::new()),
             target_archetypes: Box::new(BitSet::new()),
             debug_fn: None,
+            type_entity: None,
         }
     }
 
@@ -253,6 +370,12 @@ mod test {
         assert!(ci.is_target());
     }
 
+    #[test]
+    #[should_panic(expected = "Too many components")]
+    fn id_overflows_into_flags() {
+        let _ = ComponentId::new(ComponentId::MASK + 1);
+    }
+
     use hi_sparse_bitset::ops::*;
 
     #[test]
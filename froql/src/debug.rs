@@ -1,19 +1,16 @@
 use std::fmt::{self, Debug};
 
-use crate::{entity_store::Entity, relation_vec::RelationVec, world::World};
+use crate::{component::DebugFn, entity_store::Entity, relation_vec::RelationVec, world::World};
 
-enum ComponentDebugHelper<'a> {
-    DebugFn {
-        ptr: *const u8,
-        debug_fn: fn(*const u8, &mut fmt::Formatter<'_>) -> Result<(), fmt::Error>,
-    },
+pub(crate) enum ComponentDebugHelper<'a> {
+    DebugFn { ptr: *const u8, debug_fn: DebugFn },
     JustName(&'a str),
 }
 
 impl Debug for ComponentDebugHelper<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::DebugFn { debug_fn, ptr } => (debug_fn)(ptr.clone(), f),
+            Self::DebugFn { debug_fn, ptr } => (debug_fn)(*ptr, f),
             Self::JustName(name) => f.debug_struct(name).finish_non_exhaustive(),
         }
     }
@@ -0,0 +1,226 @@
+#![deny(missing_docs)]
+//! contains `DotOptions` and `World::export_dot`
+//! This module intended for direct use by the library user.
+//!
+//! The output is a graph in the DOT language, which can be rendered with graphviz:
+//! `dot -Tsvg world.dot > world.svg`
+
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::{
+    component::ComponentId,
+    debug::ComponentDebugHelper,
+    entity_store::{Entity, EntityId},
+    relation::Relation,
+    world::World,
+};
+
+/// Edge colors, one per relation type.
+/// Wraps around if there are more relation types than colors.
+const PALETTE: [&str; 8] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// Options for `World::export_dot`
+#[derive(Default, Clone, Debug)]
+pub struct DotOptions {
+    relations: Vec<TypeId>,
+    label: Option<TypeId>,
+    neighbourhood: Option<(Entity, usize)>,
+}
+
+impl DotOptions {
+    /// Exports all relations with default labels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only export relations of type `T`.
+    /// Can be called multiple times to export a subset of relation types.
+    pub fn relation<T: 'static>(mut self) -> Self {
+        self.relations.push(TypeId::of::<Relation<T>>());
+        self
+    }
+
+    /// Label nodes with the component `T` instead of all components with a debug formatter.
+    /// `T` needs a debug formatter registered with `register_debug`.
+    pub fn label<T: 'static>(mut self) -> Self {
+        self.label = Some(TypeId::of::<RefCell<T>>());
+        self
+    }
+
+    /// Only export entities that are at most `depth` relation hops away from `entity`.
+    /// The direction of the relations is ignored for this.
+    pub fn neighbourhood(mut self, entity: Entity, depth: usize) -> Self {
+        self.neighbourhood = Some((entity, depth));
+        self
+    }
+}
+
+struct DotEdge {
+    from: EntityId,
+    to: EntityId,
+    /// index into the relation list, used for the color
+    rel_index: usize,
+}
+
+impl World {
+    /// Exports the relation graph of this `World` in the DOT language.
+    ///
+    /// Nodes are labeled with all components that have a debug formatter,
+    /// or with the component chosen in `DotOptions::label`.
+    /// Entities without a label are shown by their id.
+    ///
+    /// Every relation type gets its own edge color.
+    /// Exclusive relations have a dot as arrowhead,
+    /// symmetric relations are drawn without direction
+    /// and transitive relations are dashed.
+    ///
+    /// Panics if a relation in the options is not registered.
+    pub fn export_dot(&self, options: &DotOptions) -> String {
+        let bk = &self.bookkeeping;
+
+        // collect relation types, sorted by cid for determinism
+        let mut relations: Vec<(ComponentId, TypeId)> = if options.relations.is_empty() {
+            bk.component_map
+                .iter()
+                .filter(|(_, cid)| cid.is_relation())
+                .map(|(tid, cid)| (*cid, *tid))
+                .collect()
+        } else {
+            options
+                .relations
+                .iter()
+                .map(|tid| {
+                    let cid = bk
+                        .get_component_id(*tid)
+                        .expect("Relation in DotOptions is not registered.");
+                    (cid, *tid)
+                })
+                .collect()
+        };
+        relations.sort_by_key(|(cid, _)| cid.id());
+        relations.dedup_by_key(|(cid, _)| cid.id());
+
+        let mut edges = Vec::new();
+        for (rel_index, (cid, tid)) in relations.iter().enumerate() {
+            for (from, to) in bk.relation_pairs(*tid) {
+                // symmetric relations are stored on both sides, only draw them once
+                if cid.is_symmetric() && from.id.0 > to.id.0 {
+                    continue;
+                }
+                edges.push(DotEdge {
+                    from: from.id,
+                    to: to.id,
+                    rel_index,
+                });
+            }
+        }
+        edges.sort_by_key(|edge| (edge.rel_index, edge.from.0, edge.to.0));
+
+        if let Some((root, depth)) = options.neighbourhood {
+            let mut adjacent: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+            for edge in &edges {
+                adjacent.entry(edge.from.0).or_default().push(edge.to.0);
+                adjacent.entry(edge.to.0).or_default().push(edge.from.0);
+            }
+            let mut reached = BTreeSet::new();
+            let mut work = VecDeque::new();
+            if self.is_alive(root) {
+                reached.insert(root.id.0);
+                work.push_back((root.id.0, 0));
+            }
+            while let Some((current, distance)) = work.pop_front() {
+                if distance >= depth {
+                    continue;
+                }
+                for other in adjacent.get(&current).into_iter().flatten() {
+                    if reached.insert(*other) {
+                        work.push_back((*other, distance + 1));
+                    }
+                }
+            }
+            edges.retain(|edge| reached.contains(&edge.from.0) && reached.contains(&edge.to.0));
+        }
+
+        let mut nodes = BTreeSet::new();
+        for edge in &edges {
+            nodes.insert(edge.from.0);
+            nodes.insert(edge.to.0);
+        }
+        if let Some((root, _)) = options.neighbourhood
+            && self.is_alive(root)
+        {
+            nodes.insert(root.id.0);
+        }
+
+        let label_cid = options.label.map(|tid| {
+            bk.get_component_id(tid)
+                .expect("Label component in DotOptions is not registered.")
+        });
+
+        let mut result = String::new();
+        result.push_str("digraph froql {\n    node [shape=box];\n");
+        for id in nodes {
+            let label = escape(&self.dot_label(EntityId(id), label_cid));
+            writeln!(result, "    e{id} [label=\"{label}\"];").unwrap();
+        }
+        for edge in &edges {
+            let (cid, _) = relations[edge.rel_index];
+            let name = escape(&bk.components[cid.as_index()].name);
+            let color = PALETTE[edge.rel_index % PALETTE.len()];
+            let mut attributes =
+                format!("label=\"{name}\", color=\"{color}\", fontcolor=\"{color}\"");
+            if cid.is_exclusive() {
+                attributes.push_str(", arrowhead=dot");
+            }
+            if cid.is_symmetric() {
+                attributes.push_str(", dir=none");
+            }
+            if cid.is_transitive() {
+                attributes.push_str(", style=dashed");
+            }
+            let from = edge.from.0;
+            let to = edge.to.0;
+            writeln!(result, "    e{from} -> e{to} [{attributes}];").unwrap();
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    fn dot_label(&self, id: EntityId, label_cid: Option<ComponentId>) -> String {
+        let bk = &self.bookkeeping;
+        let (aid, row) = bk.entities.get_archetype_unchecked(id);
+        let a = &bk.archetypes[aid.as_index()];
+        let mut parts = Vec::new();
+        for (index, cid) in a.components.iter().enumerate() {
+            if label_cid.is_some_and(|it| it != *cid) {
+                continue;
+            }
+            if let Some(debug_fn) = bk.components[cid.as_index()].debug_fn {
+                let ptr = unsafe { a.columns[index].get(row.0) } as *const u8;
+                parts.push(format!(
+                    "{:?}",
+                    ComponentDebugHelper::DebugFn { ptr, debug_fn }
+                ));
+            }
+        }
+        if parts.is_empty() {
+            format!("{}", id.0)
+        } else {
+            parts.join("\n")
+        }
+    }
+}
+
+fn escape(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
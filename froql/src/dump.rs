@@ -0,0 +1,139 @@
+#![deny(missing_docs)]
+//! contains `TableFilter` and `World::dump_tables`
+//! This module intended for direct use by the library user.
+//!
+//! The dump prints every archetype as a table, which is handy for bug reports
+//! and as a snapshot of the whole world state in tests.
+
+use std::{any::TypeId, cell::RefCell, fmt::Write};
+
+use crate::{
+    archetype::Archetype, bookkeeping::Bookkeeping, component::ComponentId,
+    debug::ComponentDebugHelper, relation::Relation, relation_vec::RelationVec, world::World,
+};
+
+/// Restricts `World::dump_tables_filtered` to archetypes that contain certain components.
+#[derive(Default, Clone, Debug)]
+pub struct TableFilter {
+    components: Vec<TypeId>,
+    relations: Vec<TypeId>,
+}
+
+impl TableFilter {
+    /// A filter that lets every archetype through.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only dump archetypes that have the component `T`.
+    pub fn component<T: 'static>(mut self) -> Self {
+        self.components.push(TypeId::of::<RefCell<T>>());
+        self
+    }
+
+    /// Only dump archetypes that have the relation `T`, either as origin or as target.
+    pub fn relation<T: 'static>(mut self) -> Self {
+        self.relations.push(TypeId::of::<Relation<T>>());
+        self
+    }
+}
+
+impl World {
+    /// Prints every non empty archetype as a table with one row per entity.
+    ///
+    /// Components with a debug formatter show their value,
+    /// other components are marked with `*`.
+    /// Relation columns list the ids of the entities on the other side.
+    pub fn dump_tables(&self) -> String {
+        self.dump_tables_filtered(&TableFilter::new())
+    }
+
+    /// Like `dump_tables`, but only prints archetypes that match the filter.
+    ///
+    /// Panics if a type in the filter is not registered.
+    pub fn dump_tables_filtered(&self, filter: &TableFilter) -> String {
+        let bk = &self.bookkeeping;
+        let lookup = |tid: &TypeId| {
+            bk.get_component_id(*tid)
+                .expect("Type in TableFilter is not registered.")
+        };
+        let components: Vec<ComponentId> = filter.components.iter().map(lookup).collect();
+        let relations: Vec<ComponentId> = filter.relations.iter().map(lookup).collect();
+
+        let mut result = String::new();
+        for (aid, a) in bk.archetypes.iter().enumerate() {
+            if a.entities.is_empty() {
+                continue;
+            }
+            if !components.iter().all(|cid| a.components.contains(cid)) {
+                continue;
+            }
+            if !relations
+                .iter()
+                .all(|cid| a.components.contains(cid) || a.components.contains(&cid.flip_target()))
+            {
+                continue;
+            }
+            if !result.is_empty() {
+                result.push('\n');
+            }
+            writeln!(result, "Archetype {aid}").unwrap();
+            write_table(&mut result, bk, a);
+        }
+        result
+    }
+}
+
+fn write_table(result: &mut String, bk: &Bookkeeping, a: &Archetype) {
+    let mut header = vec!["entity".to_string()];
+    for cid in &a.components {
+        let name = &bk.components[cid.as_index()].name;
+        if cid.is_target() {
+            header.push(format!("{name}<target>"));
+        } else if cid.is_relation() {
+            header.push(format!("{name}<origin>"));
+        } else {
+            header.push(name.clone());
+        }
+    }
+
+    let mut rows = Vec::with_capacity(a.entities.len());
+    for (row, id) in a.entities.iter().enumerate() {
+        let mut cells = vec![id.0.to_string()];
+        for (index, cid) in a.components.iter().enumerate() {
+            let ptr = unsafe { a.columns[index].get(row as u32) } as *const u8;
+            let cell = if cid.is_relation() {
+                let rel_vec = unsafe { &*(ptr as *const RelationVec) };
+                format!("{:?}", &rel_vec[..])
+            } else if let Some(debug_fn) = bk.components[cid.as_index()].debug_fn {
+                format!("{:?}", ComponentDebugHelper::DebugFn { ptr, debug_fn })
+            } else {
+                "*".to_string()
+            };
+            cells.push(cell.replace('\n', " "));
+        }
+        rows.push(cells);
+    }
+
+    let mut widths: Vec<usize> = header.iter().map(|it| it.chars().count()).collect();
+    for cells in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    write_row(result, &header, &widths);
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    write_row(result, &separator, &widths);
+    for cells in &rows {
+        write_row(result, cells, &widths);
+    }
+}
+
+fn write_row(result: &mut String, cells: &[String], widths: &[usize]) {
+    result.push('|');
+    for (cell, width) in cells.iter().zip(widths) {
+        write!(result, " {cell:<width$} |").unwrap();
+    }
+    result.push('\n');
+}
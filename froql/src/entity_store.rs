@@ -35,6 +35,14 @@ impl std::fmt::Debug for Entity {
     }
 }
 
+impl Entity {
+    /// never alive, because no slot has the sentinel generation 0
+    pub(crate) const DEAD: Entity = Entity {
+        generation: EntityGeneration(0),
+        id: EntityId(0),
+    };
+}
+
 // needed for some trickery in the macro
 impl From<&Entity> for Entity {
     fn from(value: &Entity) -> Self {
@@ -293,6 +301,34 @@ impl EntityStore {
         }
     }
 
+    /// Returns id, archetype and row of every alive entity
+    /// used for validating the world
+    pub(crate) fn alive_slots(
+        &self,
+    ) -> impl Iterator<Item = (EntityId, ArchetypeId, ArchetypeRow)> + use<'_> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| !slot.is_empty())
+            .map(|(index, slot)| (EntityId(index as u32), slot.archetype, slot.row))
+    }
+
+    /// Returns true if the free list contains every empty slot exactly once
+    pub(crate) fn free_list_is_valid(&self) -> bool {
+        let empty_count = self.slots.iter().filter(|slot| slot.is_empty()).count();
+        let mut visited = 0;
+        let mut index = self.next_free;
+        while index < self.slots.len() {
+            let slot = &self.slots[index];
+            if !slot.is_empty() || visited >= empty_count {
+                return false;
+            }
+            visited += 1;
+            index = slot.row.0 as usize;
+        }
+        visited == empty_count
+    }
+
     pub fn override_generation(&mut self, id: EntityId, new_gen: EntityGeneration) {
         assert!(new_gen.is_alive(), "New generation must be alive!");
         assert!(self.is_alive_id(id));
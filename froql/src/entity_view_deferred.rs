@@ -52,7 +52,7 @@ impl Deref for EntityViewDeferred<'_> {
 
 impl Debug for EntityViewDeferred<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        debug_view(f, &self.world, self.entity, "EntityViewDeferred")
+        debug_view(f, self.world, self.entity, "EntityViewDeferred")
     }
 }
 
@@ -20,7 +20,7 @@ pub struct EntityViewMut<'a> {
 
 impl Debug for EntityViewMut<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        debug_view(f, &self.world, self.entity, "EntityViewMut")
+        debug_view(f, self.world, self.entity, "EntityViewMut")
     }
 }
 
@@ -0,0 +1,189 @@
+#![deny(missing_docs)]
+//! contains the registration manifest and the `ReloadRegistry` used for hotreloading
+//! This module intended for direct use by the library user.
+//!
+//! The `World` remembers every type that was registered with it.
+//! After a dylib reload the new binary fills a `ReloadRegistry` with all of its types
+//! and hands it to `World::reload_all`, which rebinds everything in one go.
+
+use std::fmt::Debug;
+
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::world::{ReregisterError, World};
+
+/// Is the registered type a component or a relation?
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationKind {
+    /// registered with `register_component`
+    Component,
+    /// registered with `register_relation` or `register_relation_flags`
+    Relation,
+}
+
+/// A single entry in the registration manifest of a `World`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// full typename as returned by `std::any::type_name`
+    pub type_name: String,
+    /// component or relation
+    pub kind: RegistrationKind,
+    /// flags the type was registered with, always 0 for components
+    pub flags: u32,
+    /// true if a debug formatter was registered via `register_debug`
+    pub debug: bool,
+}
+
+type ReloadFn = unsafe fn(&mut World) -> Result<(), ReregisterError>;
+
+struct RegistryEntry {
+    kind: RegistrationKind,
+    reload: ReloadFn,
+    debug: Option<fn(&mut World)>,
+}
+
+/// Collects the types of a freshly loaded binary, so that `World::reload_all`
+/// can rebind all of them.
+///
+/// Build this in the reloaded code, so that `TypeId`s, drop functions and debug formatters
+/// all come from the new binary.
+pub struct ReloadRegistry {
+    entries: HashMap<String, RegistryEntry>,
+}
+
+impl ReloadRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::default(),
+        }
+    }
+
+    /// Adds a component type.
+    pub fn component<T: 'static>(&mut self) -> &mut Self {
+        self.insert::<T>(
+            RegistrationKind::Component,
+            World::re_register_component::<T>,
+            None,
+        )
+    }
+
+    /// Adds a component type that can restore its debug formatter.
+    pub fn component_debug<T: 'static + Debug>(&mut self) -> &mut Self {
+        self.insert::<T>(
+            RegistrationKind::Component,
+            World::re_register_component::<T>,
+            Some(World::register_debug::<T>),
+        )
+    }
+
+    /// Adds a relation type.
+    ///
+    /// There is no `relation_debug`, because relations can't have debug formatters.
+    /// Their debug output is built from the type name and the related entities,
+    /// so nothing is lost on reload.
+    pub fn relation<T: 'static>(&mut self) -> &mut Self {
+        self.insert::<T>(
+            RegistrationKind::Relation,
+            World::re_register_relation::<T>,
+            None,
+        )
+    }
+
+    /// Returns true if a type with that name was added to the registry.
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.entries.contains_key(type_name)
+    }
+
+    fn insert<T: 'static>(
+        &mut self,
+        kind: RegistrationKind,
+        reload: ReloadFn,
+        debug: Option<fn(&mut World)>,
+    ) -> &mut Self {
+        let name = std::any::type_name::<T>().to_string();
+        self.entries.insert(
+            name,
+            RegistryEntry {
+                kind,
+                reload,
+                debug,
+            },
+        );
+        self
+    }
+}
+
+/// Result of `World::reload_all`
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// typenames from the manifest that were rebound successfully
+    pub reloaded: Vec<String>,
+    /// typenames from the manifest that are not in the registry
+    /// these types can't be used safely anymore
+    pub missing: Vec<String>,
+    /// typenames whose kind changed, for example from component to relation
+    pub kind_mismatch: Vec<String>,
+    /// typenames that had a debug formatter before, but the registry did not provide one
+    pub debug_lost: Vec<String>,
+    /// typenames that could not be rebound
+    pub failed: Vec<(String, ReregisterError)>,
+}
+
+impl ReloadReport {
+    /// Returns true if every type in the manifest was rebound without losing anything
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+            && self.kind_mismatch.is_empty()
+            && self.debug_lost.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+impl World {
+    /// Returns every type registered in this `World`, in registration order.
+    pub fn registration_manifest(&self) -> &[ManifestEntry] {
+        &self.manifest
+    }
+
+    /// Rebinds every type from the registration manifest to the types in `registry`.
+    /// Debug formatters are reattached if the manifest recorded one and the registry
+    /// provides one.
+    ///
+    /// Types that are missing from the registry are left untouched and reported.
+    ///
+    /// # SAFETY
+    /// Same as `re_register_component` and `re_register_relation`.
+    /// Components of types reported as missing must not be accessed or dropped
+    /// after the old binary was unloaded.
+    pub unsafe fn reload_all(&mut self, registry: &ReloadRegistry) -> ReloadReport {
+        let mut report = ReloadReport::default();
+        for i in 0..self.manifest.len() {
+            let entry = &self.manifest[i];
+            let name = entry.type_name.clone();
+            let Some(reg) = registry.entries.get(&name) else {
+                report.missing.push(name);
+                continue;
+            };
+            if reg.kind != entry.kind {
+                report.kind_mismatch.push(name);
+                continue;
+            }
+            let had_debug = entry.debug;
+            if let Err(err) = unsafe { (reg.reload)(self) } {
+                report.failed.push((name, err));
+                continue;
+            }
+            // reloading resets the debug formatter
+            self.manifest[i].debug = false;
+            if had_debug {
+                match reg.debug {
+                    Some(debug) => debug(self),
+                    None => report.debug_lost.push(name.clone()),
+                }
+            }
+            report.reloaded.push(name);
+        }
+        report
+    }
+}
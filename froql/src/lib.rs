@@ -1,17 +1,31 @@
 #![allow(clippy::needless_return)]
 #![allow(clippy::new_without_default)]
 
+// lets the query macro refer to `::froql` from inside this crate
+#[cfg(feature = "reference_model")]
+extern crate self as froql;
+
 pub mod archetype;
 pub mod bookkeeping;
 pub mod component;
 mod debug;
+pub mod dot;
+pub mod dump;
 pub mod entity_store;
+pub mod hot_reload;
 mod layout_vec;
+pub mod query_builder;
+pub mod query_cache;
 pub mod query_helper;
+pub mod query_str;
+#[cfg(feature = "reference_model")]
+pub mod reference_model;
 pub mod relation;
 pub mod relation_vec;
+pub mod traversal;
 mod util;
+pub mod validate;
 pub mod world;
-pub use froql_macro::query;
+pub use froql_macro::{cached_query, count, exists, query};
 pub mod entity_view_deferred;
 pub mod entity_view_mut;
@@ -0,0 +1,622 @@
+#![deny(missing_docs)]
+//! contains the `QueryBuilder` for queries assembled at runtime
+//! This module intended for direct use by the library user.
+//!
+//! The `query!` macro needs every term at compile time.
+//! The `QueryBuilder` accepts the same kind of terms at runtime and executes them
+//! with the same join strategy, but yields raw column pointers instead of typed references.
+//!
+//! ```
+//! # use froql::world::World;
+//! # use froql::query_builder::QueryBuilder;
+//! # use std::{any::TypeId, cell::RefCell};
+//! struct Name(&'static str);
+//! let mut world = World::new();
+//! world.create().add(Name("Bob"));
+//!
+//! let mut builder = QueryBuilder::new();
+//! let this = builder.var();
+//! builder.component(this, TypeId::of::<RefCell<Name>>());
+//! let query = builder.build(&world).unwrap();
+//! for row in query.iter(&world) {
+//!     let name = unsafe { &*(row.column(0) as *const RefCell<Name>) };
+//!     assert_eq!("Bob", name.borrow().0);
+//! }
+//! ```
+
+mod join_order;
+
+use std::any::TypeId;
+
+use join_order::{JoinOrderComputer, Plan, Step};
+
+use crate::{
+    archetype::{ArchetypeId, ArchetypeRow},
+    bookkeeping::Bookkeeping,
+    component::ComponentId,
+    entity_store::{Entity, EntityId},
+    query_helper::{RelationHelper, UnrelationHelper},
+    world::World,
+};
+
+/// A variable of a runtime query, created by `QueryBuilder::var` or `QueryBuilder::invar`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Var(usize);
+
+impl Var {
+    /// Stands for any entity, like `_` in the `query!` macro.
+    /// Only allowed in relation and unrelation terms.
+    pub const ANY: Var = Var(usize::MAX);
+
+    /// Index of the variable in `QueryRow::entities`.
+    pub fn as_index(&self) -> usize {
+        self.0
+    }
+}
+
+/// Identifies a component or relation in a term.
+///
+/// When using a `TypeId`, it must be the type as it is stored in the world:
+/// `RefCell<T>` for components and `Relation<T>` for relations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TermKey {
+    /// an already resolved component id
+    Id(ComponentId),
+    /// a type that is resolved when the query is built
+    Type(TypeId),
+}
+
+impl From<ComponentId> for TermKey {
+    fn from(cid: ComponentId) -> Self {
+        TermKey::Id(cid)
+    }
+}
+
+impl From<TypeId> for TermKey {
+    fn from(tid: TypeId) -> Self {
+        TermKey::Type(tid)
+    }
+}
+
+/// Errors that can occur when building a runtime query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryError {
+    /// The query has no variables.
+    NoVariables,
+    /// The variable does not belong to this builder or `Var::ANY` was used outside a relation.
+    UnknownVariable(Var),
+    /// The type is not registered in the world.
+    UnknownType(TypeId),
+    /// The component id is not registered in the world.
+    UnknownComponent(ComponentId),
+    /// A component term refers to a relation or vice versa.
+    KindMismatch(TermKey),
+    /// A relation has `Var::ANY` on both sides.
+    AnyOnBothSides,
+    /// The variable has no component or relation term,
+    /// so it would match every entity, including internal ones.
+    UnconstrainedVariable(Var),
+    /// The variable is not connected by relations to the rest of the query
+    /// and `QueryBuilder::cross_join` was not called.
+    CrossJoin(Var),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::NoVariables => write!(f, "Query has no variables."),
+            QueryError::UnknownVariable(var) => write!(f, "Unknown variable {var:?}."),
+            QueryError::UnknownType(tid) => write!(f, "Type {tid:?} is not registered."),
+            QueryError::UnknownComponent(cid) => write!(f, "Component {cid:?} is not registered."),
+            QueryError::KindMismatch(key) => {
+                write!(
+                    f,
+                    "{key:?} is used as component and relation at the same time."
+                )
+            }
+            QueryError::AnyOnBothSides => {
+                write!(f, "`_` is not allowed on both sides of a relation.")
+            }
+            QueryError::UnconstrainedVariable(var) => write!(
+                f,
+                "Variable {var:?} needs a component or relation term that is not negated."
+            ),
+            QueryError::CrossJoin(var) => write!(
+                f,
+                "Variable {var:?} is not connected to the rest of the query by a relation, cross joins are not supported."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Collects the terms of a query at runtime.
+#[derive(Default, Clone, Debug)]
+pub struct QueryBuilder {
+    var_count: usize,
+    invars: Vec<(Var, Entity)>,
+    components: Vec<(Var, TermKey)>,
+    uncomponents: Vec<(Var, TermKey)>,
+    relations: Vec<(TermKey, Var, Var)>,
+    unrelations: Vec<(TermKey, Var, Var)>,
+    unequals: Vec<(Var, Var)>,
+    cross_join: bool,
+}
+
+impl QueryBuilder {
+    /// Creates a builder without any terms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new variable.
+    pub fn var(&mut self) -> Var {
+        self.var_count += 1;
+        Var(self.var_count - 1)
+    }
+
+    /// Adds a new variable that is bound to `entity`, like `*entity` in the `query!` macro.
+    pub fn invar(&mut self, entity: Entity) -> Var {
+        let var = self.var();
+        self.invars.push((var, entity));
+        var
+    }
+
+    /// Requires `var` to have the component.
+    ///
+    /// Every component term produces a column in `QueryRow::column`,
+    /// in the order the terms were added.
+    pub fn component(&mut self, var: Var, key: impl Into<TermKey>) -> &mut Self {
+        self.components.push((var, key.into()));
+        self
+    }
+
+    /// Requires `var` to not have the component, like `!Comp(var)`.
+    pub fn uncomponent(&mut self, var: Var, key: impl Into<TermKey>) -> &mut Self {
+        self.uncomponents.push((var, key.into()));
+        self
+    }
+
+    /// Requires `from` to be related to `to`, like `Rel(from, to)`.
+    /// One side may be `Var::ANY`.
+    pub fn relation(&mut self, key: impl Into<TermKey>, from: Var, to: Var) -> &mut Self {
+        self.relations.push((key.into(), from, to));
+        self
+    }
+
+    /// Requires `from` to not be related to `to`, like `!Rel(from, to)`.
+    /// One side may be `Var::ANY`.
+    pub fn unrelation(&mut self, key: impl Into<TermKey>, from: Var, to: Var) -> &mut Self {
+        self.unrelations.push((key.into(), from, to));
+        self
+    }
+
+    /// Requires `a` and `b` to be different entities, like `a != b`.
+    pub fn unequal(&mut self, a: Var, b: Var) -> &mut Self {
+        self.unequals.push((a, b));
+        self
+    }
+
+    /// Allows variables that are not connected by relations, like `cross` in the `query!` macro.
+    ///
+    /// Every match of one unconnected part is combined with every match of the others.
+    pub fn cross_join(&mut self) -> &mut Self {
+        self.cross_join = true;
+        self
+    }
+
+    /// Resolves all terms against the world and computes the join order.
+    ///
+    /// The resulting query can be executed as long as no component types are unregistered,
+    /// which never happens in froql.
+    pub fn build(&self, world: &World) -> Result<Query, QueryError> {
+        let bk = &world.bookkeeping;
+        let any_on_both_sides =
+            |(_, from, to): &(TermKey, Var, Var)| (*from, *to) == (Var::ANY, Var::ANY);
+        if self
+            .relations
+            .iter()
+            .chain(&self.unrelations)
+            .any(any_on_both_sides)
+        {
+            return Err(QueryError::AnyOnBothSides);
+        }
+        if self.var_count == 0 {
+            return Err(QueryError::NoVariables);
+        }
+        let check_var = |var: Var| {
+            if var.0 < self.var_count {
+                Ok(var.0)
+            } else {
+                Err(QueryError::UnknownVariable(var))
+            }
+        };
+
+        let mut with = vec![Vec::new(); self.var_count];
+        let mut without = vec![Vec::new(); self.var_count];
+        let mut outputs = Vec::new();
+        for (var, key) in &self.components {
+            let var = check_var(*var)?;
+            let cid = resolve(bk, *key, false)?;
+            with[var].push(cid);
+            outputs.push((var, cid));
+        }
+        for (var, key) in &self.uncomponents {
+            let var = check_var(*var)?;
+            without[var].push(resolve(bk, *key, false)?);
+        }
+
+        let mut relations = Vec::new();
+        for (key, from, to) in &self.relations {
+            let cid = resolve(bk, *key, true)?;
+            match (*from, *to) {
+                (Var::ANY, Var::ANY) => return Err(QueryError::AnyOnBothSides),
+                (Var::ANY, to) => with[check_var(to)?].push(cid.flip_target()),
+                (from, Var::ANY) => with[check_var(from)?].push(cid),
+                (from, to) => {
+                    let (from, to) = (check_var(from)?, check_var(to)?);
+                    with[from].push(cid);
+                    with[to].push(cid.flip_target());
+                    relations.push((cid, from, to));
+                }
+            }
+        }
+
+        let mut unrelations = Vec::new();
+        for (key, from, to) in &self.unrelations {
+            let cid = resolve(bk, *key, true)?;
+            match (*from, *to) {
+                (Var::ANY, Var::ANY) => return Err(QueryError::AnyOnBothSides),
+                (Var::ANY, to) => without[check_var(to)?].push(cid.flip_target()),
+                (from, Var::ANY) => without[check_var(from)?].push(cid),
+                (from, to) => unrelations.push((cid, check_var(from)?, check_var(to)?)),
+            }
+        }
+
+        let mut unequals = Vec::new();
+        for (a, b) in &self.unequals {
+            unequals.push((check_var(*a)?, check_var(*b)?));
+        }
+
+        let mut invars = Vec::new();
+        for (var, entity) in &self.invars {
+            invars.push((var.0, *entity));
+        }
+
+        // same rule as in the query macros, invars are bound directly
+        let unconstrained = (0..self.var_count)
+            .find(|var| with[*var].is_empty() && !invars.iter().any(|(it, _)| it == var));
+        if let Some(var) = unconstrained {
+            return Err(QueryError::UnconstrainedVariable(Var(var)));
+        }
+
+        for cids in with.iter_mut().chain(without.iter_mut()) {
+            cids.sort_by_key(|cid| cid.id());
+            cids.dedup();
+        }
+
+        let component_counts: Vec<usize> = with.iter().map(|it| it.len()).collect();
+        let invar_vars: Vec<usize> = invars.iter().map(|(var, _)| *var).collect();
+        let plan = JoinOrderComputer::new(
+            &component_counts,
+            &invar_vars,
+            &relations,
+            &unrelations,
+            &unequals,
+            self.cross_join,
+        )
+        .compute_join_order()?;
+
+        Ok(Query {
+            with,
+            without,
+            invars,
+            outputs,
+            plan,
+        })
+    }
+}
+
+fn resolve(bk: &Bookkeeping, key: TermKey, relation: bool) -> Result<ComponentId, QueryError> {
+    let cid = match key {
+        TermKey::Id(cid) => {
+            if cid.as_index() >= bk.components.len() {
+                return Err(QueryError::UnknownComponent(cid));
+            }
+            cid
+        }
+        TermKey::Type(tid) => bk
+            .get_component_id(tid)
+            .ok_or(QueryError::UnknownType(tid))?,
+    };
+    if cid.is_relation() != relation {
+        return Err(QueryError::KindMismatch(key));
+    }
+    // terms always refer to the origin side
+    if cid.is_target() {
+        Ok(cid.flip_target())
+    } else {
+        Ok(cid)
+    }
+}
+
+/// A runtime query produced by `QueryBuilder::build`.
+///
+/// The query can be executed many times, the matching archetypes are determined
+/// every time `Query::iter` is called.
+#[derive(Debug)]
+pub struct Query {
+    /// required components per variable
+    with: Vec<Vec<ComponentId>>,
+    /// forbidden components per variable
+    without: Vec<Vec<ComponentId>>,
+    invars: Vec<(usize, Entity)>,
+    /// variable and component of each component term
+    outputs: Vec<(usize, ComponentId)>,
+    plan: Plan,
+}
+
+impl Query {
+    /// Number of variables in each row.
+    pub fn var_count(&self) -> usize {
+        self.with.len()
+    }
+
+    /// Number of columns in each row.
+    pub fn column_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Executes the query.
+    pub fn iter<'a>(&'a self, world: &'a World) -> QueryIter<'a> {
+        let bk = &world.bookkeeping;
+        let archetype_sets = self
+            .with
+            .iter()
+            .zip(&self.without)
+            .map(|(with, without)| {
+                if with.is_empty() {
+                    // only invars, they are never scanned
+                    Vec::new()
+                } else {
+                    bk.matching_archetypes(with, without)
+                }
+            })
+            .collect();
+        let helpers = self
+            .plan
+            .helpers
+            .iter()
+            .map(|it| RelationHelper::new(it.cid))
+            .collect();
+        let unhelpers = self
+            .plan
+            .unhelpers
+            .iter()
+            .map(|it| UnrelationHelper::new(it.cid))
+            .collect();
+        QueryIter {
+            query: self,
+            bk,
+            archetype_sets,
+            bindings: vec![(ArchetypeId(0), ArchetypeRow(0), EntityId(0)); self.var_count()],
+            helpers,
+            unhelpers,
+            cursors: vec![Cursor::default(); self.plan.steps.len()],
+            current_step: 0,
+            done: false,
+        }
+    }
+}
+
+/// One result of a runtime query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryRow {
+    entities: Vec<Entity>,
+    columns: Vec<*mut u8>,
+}
+
+impl QueryRow {
+    /// The entity bound to `var`.
+    pub fn entity(&self, var: Var) -> Entity {
+        self.entities[var.0]
+    }
+
+    /// The entities of all variables, in the order they were created.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Pointer to the component of the `index`-th component term.
+    ///
+    /// Points to a `RefCell<T>`, it is only valid while the world is not mutated.
+    pub fn column(&self, index: usize) -> *mut u8 {
+        self.columns[index]
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Cursor {
+    set_index: usize,
+    row: u32,
+    row_count: u32,
+    started: bool,
+}
+
+/// Iterator over the results of a runtime query.
+pub struct QueryIter<'a> {
+    query: &'a Query,
+    bk: &'a Bookkeeping,
+    /// matching archetypes for each variable, sorted by id
+    archetype_sets: Vec<Vec<ArchetypeId>>,
+    /// current archetype, row and id of each variable
+    bindings: Vec<(ArchetypeId, ArchetypeRow, EntityId)>,
+    helpers: Vec<RelationHelper<'a>>,
+    unhelpers: Vec<UnrelationHelper<'a>>,
+    cursors: Vec<Cursor>,
+    current_step: usize,
+    done: bool,
+}
+
+impl<'a> QueryIter<'a> {
+    fn bind(&mut self, var: usize, id: EntityId) {
+        let bk = self.bk;
+        let (aid, row) = bk.entities.get_archetype_unchecked(id);
+        self.bindings[var] = (aid, row, id);
+        let archetype = &bk.archetypes[aid.as_index()];
+        for (helper, info) in self.helpers.iter_mut().zip(&self.query.plan.helpers) {
+            if info.owner == var {
+                helper.set_col(archetype.find_column(info.cid));
+                helper.set_row(bk, row.0);
+            }
+        }
+        for (helper, info) in self.unhelpers.iter_mut().zip(&self.query.plan.unhelpers) {
+            if info.owner == var {
+                helper.set_col(archetype);
+                helper.set_row(bk, row.0);
+            }
+        }
+    }
+
+    fn checks_pass(&self, step: usize) -> bool {
+        let checks = match &self.query.plan.steps[step] {
+            Step::Invars { checks } | Step::Start { checks, .. } | Step::Join { checks, .. } => {
+                checks
+            }
+        };
+        let id = |var: usize| self.bindings[var].2;
+        checks.unequals.iter().all(|(a, b)| id(*a) != id(*b))
+            && checks
+                .relations
+                .iter()
+                .all(|(helper, other)| self.helpers[*helper].has_relation(id(*other)))
+            && checks
+                .unrelations
+                .iter()
+                .all(|(helper, other)| self.unhelpers[*helper].satisfied(id(*other)))
+    }
+
+    /// binds the next candidate of the step, returns false if there are none left
+    fn advance(&mut self, step: usize) -> bool {
+        let bk = self.bk;
+        match self.query.plan.steps[step] {
+            Step::Invars { .. } => {
+                if self.cursors[step].started {
+                    return false;
+                }
+                self.cursors[step].started = true;
+                for (var, entity) in &self.query.invars {
+                    if !bk.is_alive(*entity) {
+                        return false;
+                    }
+                    let (aid, _) = bk.entities.get_archetype(*entity);
+                    let components = &bk.archetypes[aid.as_index()].components;
+                    if !self.query.with[*var]
+                        .iter()
+                        .all(|it| components.contains(it))
+                        || self.query.without[*var]
+                            .iter()
+                            .any(|it| components.contains(it))
+                    {
+                        return false;
+                    }
+                    self.bind(*var, entity.id);
+                }
+                self.checks_pass(step)
+            }
+            Step::Start { var, .. } => loop {
+                let cursor = &mut self.cursors[step];
+                if !cursor.started {
+                    cursor.started = true;
+                    cursor.set_index = usize::MAX; // rolls over to 0
+                    cursor.row_count = 0;
+                    cursor.row = 0;
+                }
+                cursor.row = cursor.row.wrapping_add(1);
+                if cursor.row >= cursor.row_count {
+                    cursor.set_index = cursor.set_index.wrapping_add(1);
+                    let Some(aid) = self.archetype_sets[var].get(cursor.set_index) else {
+                        cursor.started = false;
+                        return false;
+                    };
+                    cursor.row = u32::MAX; // rolls over to 0
+                    cursor.row_count = bk.archetypes[aid.as_index()].entities.len() as u32;
+                    continue;
+                }
+                let aid = self.archetype_sets[var][cursor.set_index];
+                let id = bk.archetypes[aid.as_index()].entities[cursor.row as usize];
+                self.bind(var, id);
+                if self.checks_pass(step) {
+                    return true;
+                }
+            },
+            Step::Join { new, helper, .. } => loop {
+                let Some(id) = self.helpers[helper].next_related() else {
+                    return false;
+                };
+                let (aid, _) = bk.entities.get_archetype_unchecked(id);
+                let set = &self.archetype_sets[new];
+                if set.binary_search_by_key(&aid.0, |it| it.0).is_err() {
+                    continue;
+                }
+                self.bind(new, id);
+                if self.checks_pass(step) {
+                    return true;
+                }
+            },
+        }
+    }
+
+    /// the owner of a join helper may have been bound several steps earlier,
+    /// so the helper has to be rewound whenever its step is entered
+    fn restart(&mut self, step: usize) {
+        if let Step::Join { helper, .. } = self.query.plan.steps[step] {
+            let owner = self.query.plan.helpers[helper].owner;
+            let (_, row, _) = self.bindings[owner];
+            self.helpers[helper].set_row(self.bk, row.0);
+        }
+    }
+
+    fn current_row(&self) -> QueryRow {
+        let bk = self.bk;
+        let entities = self
+            .bindings
+            .iter()
+            .map(|(_, _, id)| bk.entities.get_from_id(*id))
+            .collect();
+        let columns = self
+            .query
+            .outputs
+            .iter()
+            .map(|(var, cid)| {
+                let (aid, row, _) = self.bindings[*var];
+                let column = bk.archetypes[aid.as_index()].find_column(*cid);
+                unsafe { column.get(row.0) }
+            })
+            .collect();
+        QueryRow { entities, columns }
+    }
+}
+
+impl Iterator for QueryIter<'_> {
+    type Item = QueryRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last_step = self.query.plan.steps.len() - 1;
+        while !self.done {
+            if self.advance(self.current_step) {
+                if self.current_step == last_step {
+                    return Some(self.current_row());
+                }
+                self.current_step += 1;
+                self.restart(self.current_step);
+            } else if self.current_step == 0 {
+                self.done = true;
+            } else {
+                self.current_step -= 1;
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,348 @@
+//! Runtime version of the join order computation in `froql_macro/src/generator/join_order.rs`
+//!
+//! Variables are bound one after another.
+//! The first variable is either an invar or the variable with the most components.
+//! After that we follow relations from already bound variables to new variables.
+//! Relations, unrelations and unequalities between already bound variables become checks.
+
+use crate::component::ComponentId;
+
+use super::{QueryError, Var};
+
+/// a relation helper that is set whenever its owner variable is bound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct HelperInfo {
+    pub owner: usize,
+    /// oriented for the owner, so a target cid if the owner is the relation target
+    pub cid: ComponentId,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Checks {
+    pub unequals: Vec<(usize, usize)>,
+    /// relation helper, other variable
+    pub relations: Vec<(usize, usize)>,
+    /// unrelation helper, other variable
+    pub unrelations: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step {
+    /// binds all invars at once
+    Invars { checks: Checks },
+    /// iterates over all archetypes of the variable
+    Start { var: usize, checks: Checks },
+    /// follows the relation helper to bind a new variable
+    Join {
+        new: usize,
+        helper: usize,
+        checks: Checks,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Plan {
+    pub steps: Vec<Step>,
+    pub helpers: Vec<HelperInfo>,
+    pub unhelpers: Vec<HelperInfo>,
+}
+
+/// relation origin cid, from, to
+pub(crate) type RelationTerm = (ComponentId, usize, usize);
+
+pub(crate) struct JoinOrderComputer<'a> {
+    /// number of required components for each variable
+    component_counts: &'a [usize],
+    invars: &'a [usize],
+    relations_left: Vec<RelationTerm>,
+    unrelations_left: Vec<RelationTerm>,
+    unequals_left: Vec<(usize, usize)>,
+    /// bound variables in the order they are bound
+    available: Vec<usize>,
+    helpers: Vec<HelperInfo>,
+    unhelpers: Vec<HelperInfo>,
+    /// unconnected variables get their own `Step::Start` instead of an error
+    cross_join: bool,
+}
+
+impl<'a> JoinOrderComputer<'a> {
+    pub fn new(
+        component_counts: &'a [usize],
+        invars: &'a [usize],
+        relations: &[RelationTerm],
+        unrelations: &[RelationTerm],
+        unequals: &[(usize, usize)],
+        cross_join: bool,
+    ) -> Self {
+        Self {
+            component_counts,
+            invars,
+            relations_left: Vec::from(relations),
+            unrelations_left: Vec::from(unrelations),
+            unequals_left: Vec::from(unequals),
+            available: Vec::new(),
+            helpers: Vec::new(),
+            unhelpers: Vec::new(),
+            cross_join,
+        }
+    }
+
+    pub fn compute_join_order(mut self) -> Result<Plan, QueryError> {
+        let var_count = self.component_counts.len();
+        let mut steps = Vec::new();
+
+        if !self.invars.is_empty() {
+            let mut invars = Vec::from(self.invars);
+            invars.sort();
+            invars.dedup();
+            self.available.extend(invars);
+            let checks = self.newly_available_checks();
+            steps.push(Step::Invars { checks });
+        }
+
+        let mut start_count = 0;
+        loop {
+            self.inner_joins(&mut steps);
+            if self.available.len() == var_count {
+                break;
+            }
+            start_count += 1;
+            // I think its a decent metric to use the most constrained variable first
+            let var = (0..var_count)
+                .filter(|var| !self.available.contains(var))
+                .max_by_key(|var| self.component_counts[*var])
+                .expect("Internal: no variable left to start with");
+            if start_count > 1 && !self.cross_join {
+                return Err(QueryError::CrossJoin(Var(var)));
+            }
+            self.available.push(var);
+            let checks = self.newly_available_checks();
+            steps.push(Step::Start { var, checks });
+        }
+
+        debug_assert!(self.relations_left.is_empty());
+        debug_assert!(self.unrelations_left.is_empty());
+        debug_assert!(self.unequals_left.is_empty());
+        Ok(Plan {
+            steps,
+            helpers: self.helpers,
+            unhelpers: self.unhelpers,
+        })
+    }
+
+    fn inner_joins(&mut self, steps: &mut Vec<Step>) {
+        // every relation that touches a bound variable binds its other variable
+        while let Some(pos) = self
+            .relations_left
+            .iter()
+            .position(|(_, from, to)| self.available.contains(from) || self.available.contains(to))
+        {
+            let (cid, from, to) = self.relations_left.remove(pos);
+            let (old, new, cid) = if self.available.contains(&from) {
+                (from, to, cid)
+            } else {
+                (to, from, cid.flip_target())
+            };
+            let helper = self.helpers.len();
+            self.helpers.push(HelperInfo { owner: old, cid });
+            self.available.push(new);
+            let checks = self.newly_available_checks();
+            steps.push(Step::Join {
+                new,
+                helper,
+                checks,
+            });
+        }
+    }
+
+    fn rank(&self, var: usize) -> usize {
+        self.available.iter().position(|it| *it == var).unwrap()
+    }
+
+    /// the helper is owned by the variable that was bound first
+    fn orient(&self, (cid, from, to): RelationTerm) -> (HelperInfo, usize) {
+        if self.rank(from) <= self.rank(to) {
+            (HelperInfo { owner: from, cid }, to)
+        } else {
+            let cid = cid.flip_target();
+            (HelperInfo { owner: to, cid }, from)
+        }
+    }
+
+    fn newly_available_checks(&mut self) -> Checks {
+        let mut checks = Checks::default();
+        let bound = |available: &[usize], a: &usize, b: &usize| {
+            available.contains(a) && available.contains(b)
+        };
+
+        while let Some(pos) = self
+            .unequals_left
+            .iter()
+            .position(|(a, b)| bound(&self.available, a, b))
+        {
+            checks.unequals.push(self.unequals_left.swap_remove(pos));
+        }
+
+        while let Some(pos) = self
+            .relations_left
+            .iter()
+            .position(|(_, a, b)| bound(&self.available, a, b))
+        {
+            let relation = self.relations_left.swap_remove(pos);
+            let (helper, other) = self.orient(relation);
+            checks.relations.push((self.helpers.len(), other));
+            self.helpers.push(helper);
+        }
+
+        while let Some(pos) = self
+            .unrelations_left
+            .iter()
+            .position(|(_, a, b)| bound(&self.available, a, b))
+        {
+            let unrelation = self.unrelations_left.swap_remove(pos);
+            let (helper, other) = self.orient(unrelation);
+            checks.unrelations.push((self.unhelpers.len(), other));
+            self.unhelpers.push(helper);
+        }
+        checks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::component::RELATION;
+
+    fn rel(id: u32) -> ComponentId {
+        ComponentId::new(id).set_flags(RELATION)
+    }
+
+    #[test]
+    fn join_order_single_var() {
+        let plan = JoinOrderComputer::new(&[2], &[], &[], &[], &[], false)
+            .compute_join_order()
+            .unwrap();
+        insta::assert_debug_snapshot!(plan, @r"
+        Plan {
+            steps: [
+                Start {
+                    var: 0,
+                    checks: Checks {
+                        unequals: [],
+                        relations: [],
+                        unrelations: [],
+                    },
+                },
+            ],
+            helpers: [],
+            unhelpers: [],
+        }
+        ");
+    }
+
+    #[test]
+    fn join_order_relation() {
+        // Unit(a), Health(a), Attack(b, a), Unit(b), a != b
+        let attack = rel(2);
+        let plan = JoinOrderComputer::new(&[3, 2], &[], &[(attack, 1, 0)], &[], &[(0, 1)], false)
+            .compute_join_order()
+            .unwrap();
+        assert_eq!(
+            plan,
+            Plan {
+                steps: vec![
+                    Step::Start {
+                        var: 0,
+                        checks: Checks::default()
+                    },
+                    Step::Join {
+                        new: 1,
+                        helper: 0,
+                        checks: Checks {
+                            unequals: vec![(0, 1)],
+                            ..Default::default()
+                        }
+                    },
+                ],
+                helpers: vec![HelperInfo {
+                    owner: 0,
+                    cid: attack.flip_target()
+                }],
+                unhelpers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn join_order_unrelation_hop() {
+        // Circle, !Inside(this, rect), Inside(*e_circle, rect)
+        let inside = rel(1);
+        let plan = JoinOrderComputer::new(
+            &[1, 1, 1],
+            &[2],
+            &[(inside, 2, 1)],
+            &[(inside, 0, 1)],
+            &[],
+            false,
+        )
+        .compute_join_order()
+        .unwrap();
+        assert_eq!(
+            plan,
+            Plan {
+                steps: vec![
+                    Step::Invars {
+                        checks: Checks::default()
+                    },
+                    Step::Join {
+                        new: 1,
+                        helper: 0,
+                        checks: Checks::default()
+                    },
+                    Step::Start {
+                        var: 0,
+                        checks: Checks {
+                            unrelations: vec![(0, 0)],
+                            ..Default::default()
+                        }
+                    },
+                ],
+                helpers: vec![HelperInfo {
+                    owner: 2,
+                    cid: inside
+                }],
+                unhelpers: vec![HelperInfo {
+                    owner: 1,
+                    cid: inside.flip_target()
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn join_order_cross_join() {
+        let result =
+            JoinOrderComputer::new(&[1, 1], &[], &[], &[], &[], false).compute_join_order();
+        assert_eq!(Err(QueryError::CrossJoin(Var(0))), result);
+
+        let plan = JoinOrderComputer::new(&[2, 1], &[], &[], &[], &[(0, 1)], true)
+            .compute_join_order()
+            .unwrap();
+        assert_eq!(
+            vec![
+                Step::Start {
+                    var: 0,
+                    checks: Checks::default()
+                },
+                Step::Start {
+                    var: 1,
+                    checks: Checks {
+                        unequals: vec![(0, 1)],
+                        ..Default::default()
+                    }
+                },
+            ],
+            plan.steps
+        );
+    }
+}
@@ -0,0 +1,141 @@
+#![deny(missing_docs)]
+//! contains `QueryCache`, the per call site state of `cached_query!`
+//! This module intended for direct use by the library user.
+//!
+//! `query!` looks up every `ComponentId` by `TypeId` and computes the matching archetypes
+//! every time it is called.
+//! `cached_query!` keeps both in a thread local `QueryCache` per call site.
+//! When new archetypes were created since the last call, only those are checked.
+//! When the query runs on a different world, everything is computed again.
+//! The same happens when a generic function runs the query for different types,
+//! because all instantiations share the statics of the function.
+
+use std::{any::TypeId, rc::Rc};
+
+use crate::{archetype::ArchetypeId, bookkeeping::Bookkeeping, component::ComponentId};
+
+struct CachedVar {
+    components: Rc<[ComponentId]>,
+    uncomponents: Vec<ComponentId>,
+    /// at least one component of each group is needed, from OR terms
+    any_of: Vec<Vec<ComponentId>>,
+    /// invars are not matched against archetypes
+    is_invar: bool,
+    archetype_ids: Rc<Vec<ArchetypeId>>,
+}
+
+/// Resolved components and matching archetypes of a query.
+///
+/// Used by the code generated by `cached_query!`, you should not need to touch it yourself.
+pub struct QueryCache {
+    /// 0 if nothing is cached yet
+    world_id: u64,
+    archetype_generation: u64,
+    /// archetypes with a lower id were already checked
+    archetype_count: usize,
+    /// the types the cached components were resolved for
+    type_ids: Vec<TypeId>,
+    vars: Vec<CachedVar>,
+}
+
+impl QueryCache {
+    /// Creates an empty cache.
+    pub const fn new() -> Self {
+        Self {
+            world_id: 0,
+            archetype_generation: 0,
+            archetype_count: 0,
+            type_ids: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Brings the cache up to date.
+    ///
+    /// `resolve` is only called when the cache is used with a world or `type_ids` for the first time.
+    /// It must add every variable of the query in order.
+    pub fn update(
+        &mut self,
+        bk: &Bookkeeping,
+        type_ids: &[TypeId],
+        resolve: impl FnOnce(&mut Self),
+    ) {
+        if self.world_id != bk.world_id || self.type_ids != type_ids {
+            self.vars.clear();
+            self.type_ids.clear();
+            self.type_ids.extend_from_slice(type_ids);
+            resolve(self);
+            for var in &mut self.vars {
+                if !var.is_invar {
+                    let any_of: Vec<&[ComponentId]> = var.any_of.iter().map(|g| &g[..]).collect();
+                    let ids =
+                        bk.matching_archetypes_any(&var.components, &var.uncomponents, &any_of);
+                    var.archetype_ids = Rc::new(ids);
+                }
+            }
+            self.world_id = bk.world_id;
+            self.archetype_generation = bk.archetype_generation;
+            self.archetype_count = bk.archetypes.len();
+        } else if self.archetype_generation != bk.archetype_generation {
+            let new_archetypes = self.archetype_count..bk.archetypes.len();
+            for var in self.vars.iter_mut().filter(|var| !var.is_invar) {
+                // iterators borrow the world, so none of them can still hold the set here
+                let ids = Rc::make_mut(&mut var.archetype_ids);
+                for index in new_archetypes.clone() {
+                    let a = &bk.archetypes[index];
+                    if var.components.iter().all(|cid| a.components.contains(cid))
+                        && !var
+                            .uncomponents
+                            .iter()
+                            .any(|cid| a.components.contains(cid))
+                        && var
+                            .any_of
+                            .iter()
+                            .all(|group| group.iter().any(|cid| a.components.contains(cid)))
+                    {
+                        ids.push(ArchetypeId(index as u32));
+                    }
+                }
+            }
+            self.archetype_generation = bk.archetype_generation;
+            self.archetype_count = bk.archetypes.len();
+        }
+    }
+
+    /// Adds a variable that is matched against archetypes.
+    pub fn add_var(
+        &mut self,
+        components: &[ComponentId],
+        uncomponents: &[ComponentId],
+        any_of: &[&[ComponentId]],
+    ) {
+        self.vars.push(CachedVar {
+            components: components.into(),
+            uncomponents: uncomponents.to_vec(),
+            any_of: any_of.iter().map(|group| group.to_vec()).collect(),
+            is_invar: false,
+            archetype_ids: Rc::default(),
+        });
+    }
+
+    /// Adds a variable that is bound to a fixed entity.
+    pub fn add_invar(&mut self, components: &[ComponentId]) {
+        self.vars.push(CachedVar {
+            components: components.into(),
+            uncomponents: Vec::new(),
+            any_of: Vec::new(),
+            is_invar: true,
+            archetype_ids: Rc::default(),
+        });
+    }
+
+    /// Components of the variable, as used by the generated code.
+    pub fn components(&self, var: usize) -> Rc<[ComponentId]> {
+        self.vars[var].components.clone()
+    }
+
+    /// Archetypes matching the variable, empty for invars.
+    pub fn archetype_ids(&self, var: usize) -> Rc<Vec<ArchetypeId>> {
+        self.vars[var].archetype_ids.clone()
+    }
+}
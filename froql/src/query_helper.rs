@@ -1,8 +1,19 @@
-use std::{any::TypeId, cell::RefCell, collections::HashSet};
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::{HashMap, HashSet, hash_map::Entry},
+    ops::Add,
+};
 
 use crate::{
-    archetype::Archetype, bookkeeping::Bookkeeping, component::ComponentId, entity_store::EntityId,
-    layout_vec::LayoutVec, relation_vec::RelationVec, world::World,
+    archetype::Archetype,
+    bookkeeping::Bookkeeping,
+    component::ComponentId,
+    entity_store::{Entity, EntityId},
+    layout_vec::LayoutVec,
+    relation_vec::RelationVec,
+    traversal::{Traversal, TraversalOrder},
+    world::World,
 };
 
 /// Helps with Relation Traversal
@@ -11,21 +22,35 @@ pub struct RelationHelper<'a> {
     column: Option<&'a LayoutVec>,
     row: u32,
     rel_index: u32,
-    // only used for transitive relations
+    /// smallest and largest number of hops, if related entities are found by traversal
+    /// set for transitive relations and path terms like `Rel+(a, b)` or `Rel{1,3}(a, b)`
+    hops: Option<(u32, u32)>,
+    /// entities of the archetype, a path of 0 hops leads back to the entity itself
+    entities: &'a [EntityId],
+    // only used when traversing
     transitive_vec: Vec<u32>,
-    transitive_set: HashSet<u32>,
+    /// related entity => smallest number of hops in bounds
+    transitive_hops: HashMap<u32, u32>,
 }
 
 impl<'a> RelationHelper<'a> {
     pub fn new(cid: ComponentId) -> Self {
+        let hops = cid.is_transitive().then_some((1, u32::MAX));
+        Self::with_hops(cid, hops)
+    }
+
+    /// `hops` is `(min, max)`, `u32::MAX` as max means unbounded
+    pub fn with_hops(cid: ComponentId, hops: Option<(u32, u32)>) -> Self {
         RelationHelper {
             cid,
+            hops,
             // all of the following are overwritten before use
             column: None,
+            entities: &[],
             row: 0,
             rel_index: 0,
             transitive_vec: Vec::new(),
-            transitive_set: HashSet::new(),
+            transitive_hops: HashMap::new(),
         }
     }
 
@@ -33,41 +58,71 @@ impl<'a> RelationHelper<'a> {
         self.column = Some(column);
     }
 
+    /// used by path terms, the relation component may be missing from the archetype
+    pub fn set_archetype(&mut self, archetype: &'a Archetype) {
+        self.column = archetype.find_column_opt(self.cid);
+        self.entities = &archetype.entities;
+    }
+
     pub fn set_row(&mut self, bk: &Bookkeeping, row_counter: u32) {
         self.row = row_counter;
         self.rel_index = u32::MAX; // rolls over to 0
 
         // compute related eagerly
-        if self.cid.is_transitive() {
-            self.transitive_set.clear();
+        if let Some((min, max)) = self.hops {
+            self.transitive_hops.clear();
             self.transitive_vec.clear();
-            let rel_vec = unsafe { &*(self.column.unwrap().get(self.row) as *const RelationVec) };
-            let mut work = Vec::new();
-            let visited = &mut self.transitive_set;
-            work.extend_from_slice(rel_vec);
-            visited.extend(work.iter().copied());
-            self.transitive_vec.extend_from_slice(rel_vec);
-
-            while !work.is_empty() {
-                let current = EntityId(work.pop().unwrap());
-                let comp_opt = bk.get_component_opt_unchecked(current, self.cid);
-                if let Some(ptr) = comp_opt {
-                    let rel_vec = unsafe { &*(ptr as *const RelationVec) };
-                    for id in &rel_vec[..] {
-                        if !visited.contains(id) {
-                            work.push(*id);
-                            self.transitive_vec.push(*id);
-                            visited.insert(*id);
-                        }
+            if min == 0 {
+                self.found(self.entities[row_counter as usize].0, 0);
+            }
+            let mut frontier: Vec<u32> = match self.column {
+                Some(column) => {
+                    let rel_vec = unsafe { &*(column.get(self.row) as *const RelationVec) };
+                    rel_vec[..].to_vec()
+                }
+                None => Vec::new(),
+            };
+            // below `min` every number of hops has to be visited on its own,
+            // because an entity may be reachable over paths of different lengths
+            let mut level = HashSet::new();
+            let mut hops = 1;
+            while hops < min && !frontier.is_empty() {
+                level.clear();
+                let mut next = Vec::new();
+                for id in &frontier {
+                    let comp_opt = bk.get_component_opt_unchecked(EntityId(*id), self.cid);
+                    if let Some(ptr) = comp_opt {
+                        let rel_vec = unsafe { &*(ptr as *const RelationVec) };
+                        next.extend(rel_vec.iter().filter(|id| level.insert(**id)));
                     }
                 }
+                frontier = next;
+                hops += 1;
+            }
+            let max_depth = (max != u32::MAX).then_some(max);
+            let mut traversal =
+                Traversal::new(bk, self.cid, TraversalOrder::BreadthFirst, max_depth);
+            for id in frontier {
+                traversal.push(EntityId(id), hops);
+            }
+            for (id, hops) in traversal {
+                self.found(id.0, hops);
             }
         }
     }
 
+    fn found(&mut self, id: u32, hops: u32) {
+        // entities are found breadth first, the first find has the fewest hops,
+        // with 0 hops the entity itself may be found again over a cycle
+        if let Entry::Vacant(entry) = self.transitive_hops.entry(id) {
+            entry.insert(hops);
+            self.transitive_vec.push(id);
+        }
+    }
+
     pub fn next_related(&mut self) -> Option<EntityId> {
         self.rel_index = self.rel_index.wrapping_add(1);
-        if self.cid.is_transitive() {
+        if self.hops.is_some() {
             return self
                 .transitive_vec
                 .get(self.rel_index as usize)
@@ -83,14 +138,27 @@ impl<'a> RelationHelper<'a> {
         }
     }
 
+    /// starts over with the first related entity of the current row
+    pub fn rewind(&mut self) {
+        self.rel_index = u32::MAX; // rolls over to 0
+    }
+
     pub fn has_relation(&self, id: EntityId) -> bool {
-        if self.cid.is_transitive() {
-            self.transitive_set.contains(&id.0)
+        if self.hops.is_some() {
+            self.transitive_hops.contains_key(&id.0)
         } else {
             let rel_vec = unsafe { &*(self.column.unwrap().get(self.row) as *const RelationVec) };
             rel_vec.contains(&id.0)
         }
     }
+
+    /// smallest number of hops to a related entity, within the bounds of the term
+    pub fn hops_to(&self, id: EntityId) -> usize {
+        match self.hops {
+            Some(_) => self.transitive_hops[&id.0] as usize,
+            None => 1,
+        }
+    }
 }
 
 #[repr(transparent)] // same size as RelationHelper
@@ -125,6 +193,141 @@ impl<'a> UnrelationHelper<'a> {
     }
 }
 
+/// RelationHelper of an optional relation term
+/// the relation component may be missing from the archetype
+#[repr(transparent)] // same size as RelationHelper
+pub struct OptRelationHelper<'a> {
+    rel: RelationHelper<'a>,
+}
+
+impl<'a> OptRelationHelper<'a> {
+    pub fn new(cid: ComponentId) -> Self {
+        Self {
+            rel: RelationHelper::new(cid),
+        }
+    }
+
+    pub fn set_col(&mut self, archetype: &'a Archetype) {
+        self.rel.column = archetype.find_column_opt(self.rel.cid);
+    }
+
+    pub fn set_row(&mut self, bk: &Bookkeeping, row_counter: u32) {
+        if self.rel.column.is_some() {
+            self.rel.set_row(bk, row_counter);
+        }
+    }
+
+    pub fn rewind(&mut self) {
+        self.rel.rewind();
+    }
+
+    pub fn next_related(&mut self) -> Option<EntityId> {
+        // without the relation component nothing is related
+        self.rel.column?;
+        self.rel.next_related()
+    }
+}
+
+/// Index of the first branch of an OR term that the entity's archetype contains.
+pub fn first_branch(world: &World, e: Entity, branches: &[ComponentId]) -> usize {
+    let bk = &world.bookkeeping;
+    let (aid, _) = bk.entities.get_archetype(e);
+    let a = &bk.archetypes[aid.as_index()];
+    branches
+        .iter()
+        .position(|cid| a.components.contains(cid))
+        .expect("Internal: entity matches no branch")
+}
+
+/// Binds the world argument of the query macros by method call,
+/// so that `world`, `&world` and `ctx.world_mut()` all end up as `&World`
+/// without borrowing a temporary.
+pub trait AsWorld {
+    fn as_world(&self) -> &World;
+}
+
+impl AsWorld for World {
+    fn as_world(&self) -> &World {
+        self
+    }
+}
+
+/// Fixes the argument type of a `where(Comp(var) => closure)` predicate,
+/// so that the closure can be written without type annotations.
+pub fn predicate<T>(f: impl Fn(&T) -> bool) -> impl Fn(&T) -> bool {
+    f
+}
+
+/// State of one aggregate in one group of an aggregating query.
+///
+/// Every entity is only counted once, even if it is matched by several rows.
+pub struct Aggregate<T> {
+    seen: HashSet<Entity>,
+    value: Option<T>,
+}
+
+impl<T> Default for Aggregate<T> {
+    fn default() -> Self {
+        Self {
+            seen: HashSet::new(),
+            value: None,
+        }
+    }
+}
+
+impl<T> Aggregate<T> {
+    /// returns false if the entity was already seen
+    pub fn insert(&mut self, e: Entity) -> bool {
+        self.seen.insert(e)
+    }
+
+    pub fn count(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn sum(&mut self, e: Entity, value: impl FnOnce() -> T)
+    where
+        T: Add<Output = T>,
+    {
+        if self.insert(e) {
+            let value = value();
+            self.value = Some(match self.value.take() {
+                Some(acc) => acc + value,
+                None => value,
+            });
+        }
+    }
+
+    pub fn min(&mut self, e: Entity, value: impl FnOnce() -> T)
+    where
+        T: PartialOrd,
+    {
+        if self.insert(e) {
+            let value = value();
+            if self.value.as_ref().is_none_or(|old| value < *old) {
+                self.value = Some(value);
+            }
+        }
+    }
+
+    pub fn max(&mut self, e: Entity, value: impl FnOnce() -> T)
+    where
+        T: PartialOrd,
+    {
+        if self.insert(e) {
+            let value = value();
+            if self.value.as_ref().is_none_or(|old| value > *old) {
+                self.value = Some(value);
+            }
+        }
+    }
+
+    /// every group has at least one row, so there always is a value
+    pub fn into_value(self) -> T {
+        self.value.expect("Internal: aggregate without value")
+    }
+}
+
 /// This function exists as a helper for user macros that care about compile time
 /// You need to wrap the Type you care about in RefCell<>, since all components are RefCells
 pub fn trivial_query_one_component(world: &World, ty: TypeId) -> Vec<EntityId> {
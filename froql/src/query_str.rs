@@ -0,0 +1,355 @@
+#![deny(missing_docs)]
+//! contains `World::query_str`, queries written as text and evaluated at runtime
+//! This module intended for direct use by the library user.
+//!
+//! The text uses the same term grammar as the `query!` macro, for example
+//! `Name(a), IsA(a, b), !Dead(a)`.
+//! Types are looked up by their full path or by their short name.
+//! Type entities can be used as relation targets with `@Type`, like `Eats(a, @Apples)`.
+//! Invars, optional components, optional relations, predicates and singletons are not supported,
+//! since there is no surrounding Rust scope.
+
+use std::{any::type_name, cell::RefCell};
+
+use froql_grammar::{
+    ParseError, RelationVarKind as RVK, Span, Term, VarKind as VK, lex, parse_term, split_terms,
+    transform_anyvars,
+};
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+    bookkeeping::Bookkeeping,
+    component::ComponentId,
+    debug::ComponentDebugHelper,
+    entity_store::Entity,
+    query_builder::{QueryBuilder, QueryError, Var},
+    relation::Relation,
+    world::World,
+};
+
+/// Error of `World::query_str` with the character range of the offending input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStrError {
+    /// index of the first character
+    pub start: usize,
+    /// index after the last character
+    pub end: usize,
+    /// what went wrong
+    pub message: String,
+}
+
+impl std::fmt::Display for QueryStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.start, self.end)
+    }
+}
+
+impl std::error::Error for QueryStrError {}
+
+impl From<ParseError<Span>> for QueryStrError {
+    fn from(err: ParseError<Span>) -> Self {
+        Self {
+            start: err.start.start,
+            end: err.end.end,
+            message: err.message,
+        }
+    }
+}
+
+/// Result of `World::query_str`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextQueryResult {
+    /// variable names, in the order they appear in the query
+    pub variables: Vec<String>,
+    /// one header per output component, like `Name(a)`
+    pub columns: Vec<String>,
+    /// the matching rows
+    pub rows: Vec<TextRow>,
+}
+
+/// One row of a `TextQueryResult`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRow {
+    /// the entity of each variable, same order as `TextQueryResult::variables`
+    pub entities: Vec<Entity>,
+    /// debug formatted components, same order as `TextQueryResult::columns`
+    pub components: Vec<String>,
+}
+
+impl World {
+    /// Parses and runs a query at runtime, for example in a debug console.
+    ///
+    /// Components without a registered debug formatter are shown by name.
+    ///
+    /// ```
+    /// # use froql::world::World;
+    /// #[derive(Debug)]
+    /// struct Name(&'static str);
+    /// enum IsA {}
+    ///
+    /// let mut world = World::new();
+    /// world.register_debug::<Name>();
+    /// world.register_relation::<IsA>();
+    /// let animal = world.create().add(Name("Animal")).entity;
+    /// world.create().add(Name("Cat")).relate_to::<IsA>(animal);
+    ///
+    /// let result = world.query_str("Name(a), IsA(a, b)").unwrap();
+    /// assert_eq!(vec!["Name(a)"], result.columns);
+    /// assert_eq!(r#"Name("Cat")"#, result.rows[0].components[0]);
+    /// assert_eq!(animal, result.rows[0].entities[1]);
+    /// ```
+    pub fn query_str(&self, query: &str) -> Result<TextQueryResult, QueryStrError> {
+        let bk = &self.bookkeeping;
+        let tokens = lex(query)?;
+
+        let mut builder = QueryBuilder::new();
+        let mut variables: Vec<String> = Vec::new();
+        let mut var_map: HashMap<String, Var> = HashMap::default();
+        // span of the term that uses the variable first, for errors of the QueryBuilder
+        let mut first_use: Vec<(usize, usize)> = Vec::new();
+        let mut var = |name: &str, builder: &mut QueryBuilder, span: (usize, usize)| {
+            if name == "_" {
+                return Var::ANY;
+            }
+            *var_map.entry(name.to_string()).or_insert_with(|| {
+                variables.push(name.to_string());
+                first_use.push(span);
+                match name.strip_prefix('@') {
+                    // the type was already resolved by `relation_var`
+                    Some(ty) => builder.invar(type_entity(bk, ty).unwrap()),
+                    None => builder.var(),
+                }
+            })
+        };
+        let relation_var = |kind: RVK| match kind {
+            RVK::Var(name) => Ok(name),
+            RVK::AnyVar => Ok("_".to_string()),
+            RVK::InVar(name) if name.starts_with('@') => type_entity(bk, &name[1..]).map(|_| name),
+            RVK::InVar(_) => Err("Invars are not supported in text queries.".to_string()),
+        };
+
+        // every component term produces a column in the QueryBuilder
+        // but `_ Comp` terms are not shown
+        let mut columns = Vec::new();
+        let mut shown = Vec::new();
+        for term in split_terms(&tokens) {
+            let term_span = (term[0].span().start, term.last().unwrap().span().end);
+            let span = |message: String| QueryStrError {
+                start: term_span.0,
+                end: term_span.1,
+                message,
+            };
+            let unsupported = || span("Invars are not supported in text queries.".to_string());
+            let parsed = transform_anyvars(parse_term(term)?).map_err(span)?;
+            match parsed {
+                Term::ComponentVar(ty, VK::Var(name))
+                | Term::MutComponentVar(ty, VK::Var(name)) => {
+                    let cid = resolve(bk, &ty, false).map_err(span)?;
+                    let v = var(&name, &mut builder, term_span);
+                    builder.component(v, cid);
+                    shown.push(true);
+                    columns.push((format!("{ty}({name})"), cid));
+                }
+                Term::NoOutComponentVar(ty, VK::Var(name)) => {
+                    let cid = resolve(bk, &ty, false).map_err(span)?;
+                    let v = var(&name, &mut builder, term_span);
+                    builder.component(v, cid);
+                    shown.push(false);
+                    columns.push((format!("{ty}({name})"), cid));
+                }
+                Term::Uncomponent(ty, name) => {
+                    let cid = resolve(bk, &ty, false).map_err(span)?;
+                    let v = var(&name, &mut builder, term_span);
+                    builder.uncomponent(v, cid);
+                }
+                Term::Relation(ty, a, b) => {
+                    let cid = resolve(bk, &ty, true).map_err(span)?;
+                    let a = relation_var(a).map_err(span)?;
+                    let b = relation_var(b).map_err(span)?;
+                    if a == "_" && b == "_" {
+                        return Err(span(format!(
+                            "{ty}(_, _) does not constrain any variable, at least one side has to be a variable."
+                        )));
+                    }
+                    let (a, b) = (
+                        var(&a, &mut builder, term_span),
+                        var(&b, &mut builder, term_span),
+                    );
+                    builder.relation(cid, a, b);
+                }
+                Term::Unrelation(ty, a, b) => {
+                    let cid = resolve(bk, &ty, true).map_err(span)?;
+                    let a = relation_var(a).map_err(span)?;
+                    let b = relation_var(b).map_err(span)?;
+                    if a == "_" && b == "_" {
+                        return Err(span(format!(
+                            "!{ty}(_, _) does not constrain any variable, at least one side has to be a variable."
+                        )));
+                    }
+                    let (a, b) = (
+                        var(&a, &mut builder, term_span),
+                        var(&b, &mut builder, term_span),
+                    );
+                    builder.unrelation(cid, a, b);
+                }
+                Term::OutVar(name) => {
+                    var(&name, &mut builder, term_span);
+                }
+                Term::CrossJoin => {
+                    builder.cross_join();
+                }
+                Term::ConstraintUnequal(VK::Var(a), VK::Var(b)) => {
+                    let (a, b) = (
+                        var(&a, &mut builder, term_span),
+                        var(&b, &mut builder, term_span),
+                    );
+                    builder.unequal(a, b);
+                }
+                Term::ComponentVar(_, VK::InVar(_))
+                | Term::MutComponentVar(_, VK::InVar(_))
+                | Term::NoOutComponentVar(_, VK::InVar(_))
+                | Term::ConstraintUnequal(..) => return Err(unsupported()),
+                Term::OptionalComponent(..) | Term::OptionalMutComponent(..) => {
+                    return Err(span(
+                        "Optional components are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::Where(..) => {
+                    return Err(span(
+                        "Predicates are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::OptionalRelation(..) => {
+                    return Err(span(
+                        "Optional relations are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::Singleton(..) | Term::SingletonMut(..) => {
+                    return Err(span(
+                        "Singletons are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::Aggregate(..) => {
+                    return Err(span(
+                        "Aggregates are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::OrderBy(..) => {
+                    return Err(span(
+                        "order_by is not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::Or(..) => {
+                    return Err(span(
+                        "OR terms are not supported in text queries.".to_string(),
+                    ));
+                }
+                Term::Path(..) | Term::Hops(..) => {
+                    return Err(span(
+                        "Path terms are not supported in text queries.".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let query = builder.build(self).map_err(|err| {
+            let at_var = |var: Var, message: String| QueryStrError {
+                start: first_use[var.as_index()].0,
+                end: first_use[var.as_index()].1,
+                message,
+            };
+            let name = |var: Var| &variables[var.as_index()];
+            match err {
+                QueryError::UnconstrainedVariable(var) => at_var(
+                    var,
+                    format!(
+                        "Variable `{}` needs a Component or Relation term that is not negated.",
+                        name(var)
+                    ),
+                ),
+                QueryError::CrossJoin(var) => at_var(
+                    var,
+                    format!(
+                        "Variable `{}` is not connected to the rest of the query by a relation. Cross joins are not supported, add `cross` to the query.",
+                        name(var)
+                    ),
+                ),
+                err => QueryStrError {
+                    start: 0,
+                    end: query.chars().count(),
+                    message: err.to_string(),
+                },
+            }
+        })?;
+        let rows = query
+            .iter(self)
+            .map(|row| {
+                let components = columns
+                    .iter()
+                    .zip(&shown)
+                    .enumerate()
+                    .filter(|(_, (_, shown))| **shown)
+                    .map(|(index, ((_, cid), _))| format_component(bk, *cid, row.column(index)))
+                    .collect();
+                TextRow {
+                    entities: row.entities().to_vec(),
+                    components,
+                }
+            })
+            .collect();
+        let columns = columns
+            .into_iter()
+            .zip(shown)
+            .filter(|(_, shown)| *shown)
+            .map(|((header, _), _)| header)
+            .collect();
+        Ok(TextQueryResult {
+            variables,
+            columns,
+            rows,
+        })
+    }
+}
+
+/// finds a registered type by its full path or its short name
+fn resolve(bk: &Bookkeeping, name: &str, relation: bool) -> Result<ComponentId, String> {
+    let full_name = if relation {
+        let prefix = type_name::<Relation<u8>>().strip_suffix("u8>").unwrap();
+        format!("{prefix}{name}>")
+    } else {
+        let prefix = type_name::<RefCell<u8>>().strip_suffix("u8>").unwrap();
+        format!("{prefix}{name}>")
+    };
+    if let Some(tid) = bk.component_name_map.get(&full_name) {
+        return Ok(bk.get_component_id(*tid).unwrap());
+    }
+
+    let kind = if relation { "Relation" } else { "Component" };
+    let mut candidates = bk
+        .components
+        .iter()
+        .filter(|c| c.name == name && c.id.is_relation() == relation);
+    match (candidates.next(), candidates.next()) {
+        (Some(c), None) => Ok(c.id),
+        (Some(_), Some(_)) => Err(format!(
+            "{kind} name '{name}' is ambiguous, use the full path."
+        )),
+        (None, _) => Err(format!("{kind} '{name}' is not registered.")),
+    }
+}
+
+/// the entity of a `@Type` term, a dead one if it was never created
+fn type_entity(bk: &Bookkeeping, name: &str) -> Result<Entity, String> {
+    let cid = resolve(bk, name, false)?;
+    Ok(bk.components[cid.as_index()]
+        .type_entity
+        .unwrap_or(Entity::DEAD))
+}
+
+fn format_component(bk: &Bookkeeping, cid: ComponentId, ptr: *mut u8) -> String {
+    let component = &bk.components[cid.as_index()];
+    match component.debug_fn {
+        Some(debug_fn) => format!("{:?}", ComponentDebugHelper::DebugFn { ptr, debug_fn }),
+        None => format!("{:?}", ComponentDebugHelper::JustName(&component.name)),
+    }
+}
@@ -0,0 +1,317 @@
+#![deny(missing_docs)]
+//! contains the `ReferenceWorld`, a deliberately naive implementation of the `World` semantics
+//! This module intended for direct use by the library user.
+//!
+//! Only available with the `reference_model` feature.
+//!
+//! The reference model stores every entity as a hashmap of boxed components and
+//! a hashmap of relation sets. It has no archetypes, no columns and no unsafe code,
+//! so it is easy to convince yourself that it is correct.
+//! The `driver` module applies the same random operations to a `ReferenceWorld`
+//! and a real `World` and compares the results of a set of queries.
+
+pub mod driver;
+
+use std::any::{Any, TypeId};
+
+use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
+
+use crate::{
+    bookkeeping::{AddRelationError, Displaced},
+    component::{
+        ACYCLIC, CASCADING_DESTRUCT, EXCLUSIVE, EXCLUSIVE_TARGET, SYMMETRIC,
+        TARGET_CASCADING_DESTRUCT, TRANSITIVE,
+    },
+    entity_store::Entity,
+};
+
+#[derive(Default)]
+struct ReferenceEntity {
+    components: HashMap<TypeId, Box<dyn Any>>,
+    /// relation type -> targets
+    /// symmetric relations are stored on both sides
+    relations: HashMap<TypeId, HashSet<Entity>>,
+}
+
+/// A naive, slow and obviously correct model of a `World`.
+///
+/// Entities are not allocated by the model, they are handed in from the real `World`,
+/// so that both sides can be compared.
+#[derive(Default)]
+pub struct ReferenceWorld {
+    entities: HashMap<Entity, ReferenceEntity>,
+    /// relation type -> flags
+    relation_flags: HashMap<TypeId, u32>,
+    /// created in deferred mode, but not yet alive
+    pending: Vec<Entity>,
+}
+
+impl ReferenceWorld {
+    /// Creates an empty model.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a relation type with its flags, like `World::register_relation_flags`.
+    pub fn register_relation_flags<T: 'static>(&mut self, flags: u32) {
+        self.relation_flags.insert(TypeId::of::<T>(), flags);
+    }
+
+    fn flags(&self, tid: TypeId) -> u32 {
+        *self
+            .relation_flags
+            .get(&tid)
+            .expect("Relation is not registered in the reference model.")
+    }
+
+    /// Returns true if the entity is alive.
+    pub fn is_alive(&self, e: Entity) -> bool {
+        self.entities.contains_key(&e)
+    }
+
+    /// Returns every alive entity, sorted by id.
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut result: Vec<Entity> = self.entities.keys().copied().collect();
+        result.sort_by_key(|e| e.id.0);
+        result
+    }
+
+    /// Adds an entity that was created by the real `World`.
+    /// Entities created in deferred mode become alive first, like in the `World`.
+    pub fn create(&mut self, e: Entity) {
+        self.realize_deferred();
+        self.insert(e);
+    }
+
+    /// Adds an entity that was created with `World::create_deferred`.
+    /// It becomes alive once `realize_deferred` is called.
+    pub fn create_deferred(&mut self, e: Entity) {
+        self.pending.push(e);
+    }
+
+    /// Makes all entities created in deferred mode alive.
+    pub fn realize_deferred(&mut self) {
+        for e in std::mem::take(&mut self.pending) {
+            self.insert(e);
+        }
+    }
+
+    fn insert(&mut self, e: Entity) {
+        let old = self.entities.insert(e, ReferenceEntity::default());
+        assert!(old.is_none(), "Entity {e:?} was already alive.");
+    }
+
+    /// Destroys the entity, its relations and cascades to targets of cascading relations
+    /// and to origins of target cascading relations.
+    /// Does nothing if the entity is not alive.
+    pub fn destroy(&mut self, e: Entity) {
+        self.realize_deferred();
+        let Some(removed) = self.entities.remove(&e) else {
+            return;
+        };
+        let mut origins = Vec::new();
+        for (other_e, other) in self.entities.iter_mut() {
+            for (tid, targets) in other.relations.iter_mut() {
+                let flags = self.relation_flags[tid];
+                if targets.remove(&e) && flags & TARGET_CASCADING_DESTRUCT > 0 {
+                    origins.push(*other_e);
+                }
+            }
+            other.relations.retain(|_, targets| !targets.is_empty());
+        }
+        for origin in origins {
+            self.destroy(origin);
+        }
+        for (tid, targets) in removed.relations {
+            if self.flags(tid) & CASCADING_DESTRUCT > 0 {
+                for target in targets {
+                    self.destroy(target);
+                }
+            }
+        }
+    }
+
+    /// Adds or replaces a component.
+    /// Panics if the entity is not alive.
+    pub fn add_component<T: 'static>(&mut self, e: Entity, val: T) {
+        self.entity_mut(e)
+            .components
+            .insert(TypeId::of::<T>(), Box::new(val));
+    }
+
+    /// Removes a component, if the entity has it.
+    /// Panics if the entity is not alive.
+    pub fn remove_component<T: 'static>(&mut self, e: Entity) {
+        self.entity_mut(e).components.remove(&TypeId::of::<T>());
+    }
+
+    /// Returns the component, if the entity has it.
+    /// Panics if the entity is not alive.
+    pub fn get_component<T: 'static>(&self, e: Entity) -> Option<&T> {
+        self.entity(e)
+            .components
+            .get(&TypeId::of::<T>())
+            .map(|it| it.downcast_ref::<T>().unwrap())
+    }
+
+    /// Returns true if the entity has the component.
+    /// Panics if the entity is not alive.
+    pub fn has_component<T: 'static>(&self, e: Entity) -> bool {
+        self.get_component::<T>(e).is_some()
+    }
+
+    /// Adds a relation between two entities.
+    /// Panics if one of the entities is not alive or if the relation would close a cycle.
+    pub fn add_relation<T: 'static>(&mut self, from: Entity, to: Entity) {
+        if let Err(err) = self.try_add_relation::<T>(from, to) {
+            panic!("{err}");
+        }
+    }
+
+    /// Adds a relation between two entities,
+    /// unless the relation is `ACYCLIC` and it would close a cycle.
+    /// Returns the relations that were unlinked to make room for it.
+    /// Panics if one of the entities is not alive.
+    pub fn try_add_relation<T: 'static>(
+        &mut self,
+        from: Entity,
+        to: Entity,
+    ) -> Result<Displaced, AddRelationError> {
+        let tid = TypeId::of::<T>();
+        let flags = self.flags(tid);
+        assert!(self.is_alive(to), "Entity {to:?} is not alive.");
+        if flags & ACYCLIC > 0 && self.reaches(tid, to, from) {
+            return Err(AddRelationError::Cycle { from, to });
+        }
+        let mut displaced = Displaced::default();
+        if flags & EXCLUSIVE > 0 {
+            let old: Vec<Entity> = self.targets(tid, from).into_iter().collect();
+            displaced.old_target = old.iter().copied().find(|it| *it != to);
+            for old in old {
+                self.remove_relation::<T>(from, old);
+            }
+        }
+        let pairing = flags & (EXCLUSIVE | SYMMETRIC) == EXCLUSIVE | SYMMETRIC;
+        if flags & EXCLUSIVE_TARGET > 0 || pairing {
+            let old: Vec<Entity> = self
+                .entities()
+                .into_iter()
+                .filter(|e| self.targets(tid, *e).contains(&to))
+                .collect();
+            displaced.old_origin = old.iter().copied().find(|it| *it != from);
+            for old in old {
+                self.remove_relation::<T>(old, to);
+            }
+        }
+        self.link(tid, from, to);
+        if flags & SYMMETRIC > 0 {
+            self.link(tid, to, from);
+        }
+        Ok(displaced)
+    }
+
+    /// true if `goal` can be reached from `start` by following the relation, or is `start`
+    fn reaches(&self, tid: TypeId, start: Entity, goal: Entity) -> bool {
+        let mut reached = HashSet::default();
+        let mut work = vec![start];
+        while let Some(current) = work.pop() {
+            if current == goal {
+                return true;
+            }
+            if reached.insert(current) {
+                work.extend(self.targets(tid, current));
+            }
+        }
+        false
+    }
+
+    /// Removes a relation between two entities, if it exists.
+    /// Panics if one of the entities is not alive.
+    pub fn remove_relation<T: 'static>(&mut self, from: Entity, to: Entity) {
+        let tid = TypeId::of::<T>();
+        let flags = self.flags(tid);
+        assert!(self.is_alive(to), "Entity {to:?} is not alive.");
+        self.unlink(tid, from, to);
+        if flags & SYMMETRIC > 0 {
+            self.unlink(tid, to, from);
+        }
+    }
+
+    fn link(&mut self, tid: TypeId, from: Entity, to: Entity) {
+        self.entity_mut(from)
+            .relations
+            .entry(tid)
+            .or_default()
+            .insert(to);
+    }
+
+    fn unlink(&mut self, tid: TypeId, from: Entity, to: Entity) {
+        let relations = &mut self.entity_mut(from).relations;
+        if let Some(targets) = relations.get_mut(&tid) {
+            targets.remove(&to);
+            if targets.is_empty() {
+                relations.remove(&tid);
+            }
+        }
+    }
+
+    fn targets(&self, tid: TypeId, from: Entity) -> HashSet<Entity> {
+        self.entity(from)
+            .relations
+            .get(&tid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns every entity `from` is related to.
+    /// Follows transitive relations.
+    /// Panics if the entity is not alive.
+    pub fn related<T: 'static>(&self, from: Entity) -> HashSet<Entity> {
+        let tid = TypeId::of::<T>();
+        let direct = self.targets(tid, from);
+        if self.flags(tid) & TRANSITIVE == 0 {
+            return direct;
+        }
+        let mut reached = HashSet::default();
+        let mut work: Vec<Entity> = direct.into_iter().collect();
+        while let Some(current) = work.pop() {
+            if reached.insert(current) {
+                work.extend(self.targets(tid, current));
+            }
+        }
+        reached
+    }
+
+    /// Returns true if `from` is related to `to`.
+    /// Follows transitive relations.
+    /// Panics if `from` is not alive.
+    pub fn has_relation<T: 'static>(&self, from: Entity, to: Entity) -> bool {
+        self.related::<T>(from).contains(&to)
+    }
+
+    /// Returns every related pair, sorted by the ids of the entities.
+    /// Follows transitive relations.
+    pub fn relation_pairs<T: 'static>(&self) -> Vec<(Entity, Entity)> {
+        let mut result = Vec::new();
+        for from in self.entities() {
+            for to in self.related::<T>(from) {
+                result.push((from, to));
+            }
+        }
+        result.sort_by_key(|(a, b)| (a.id.0, b.id.0));
+        result
+    }
+
+    fn entity(&self, e: Entity) -> &ReferenceEntity {
+        self.entities
+            .get(&e)
+            .unwrap_or_else(|| panic!("Entity {e:?} is not alive."))
+    }
+
+    fn entity_mut(&mut self, e: Entity) -> &mut ReferenceEntity {
+        self.entities
+            .get_mut(&e)
+            .unwrap_or_else(|| panic!("Entity {e:?} is not alive."))
+    }
+}
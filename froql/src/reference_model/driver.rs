@@ -0,0 +1,699 @@
+//! applies random operation sequences to a `World` and a `ReferenceWorld` and compares them
+//!
+//! ```
+//! use froql::reference_model::driver::run;
+//!
+//! for seed in 0..10 {
+//!     if let Err(mismatch) = run(seed, 200) {
+//!         panic!("{mismatch:#?}");
+//!     }
+//! }
+//! ```
+
+use std::fmt::Debug;
+
+use crate::{
+    component::{
+        ACYCLIC, CASCADING_DESTRUCT, EXCLUSIVE, ONE_TO_ONE, SYMMETRIC, TARGET_CASCADING_DESTRUCT,
+        TRANSITIVE,
+    },
+    entity_store::Entity,
+    query,
+    world::World,
+};
+
+use super::ReferenceWorld;
+
+/// Component used by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompA(pub u32);
+/// Component used by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompB(pub u32);
+/// Marker component used by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tag;
+
+/// Relation without flags.
+pub enum Plain {}
+/// Relation with the `EXCLUSIVE` flag.
+pub enum Exclusive {}
+/// Relation with the `SYMMETRIC` flag.
+pub enum Symmetric {}
+/// Relation with the `CASCADING_DESTRUCT` flag.
+pub enum Cascading {}
+/// Relation with the `TRANSITIVE` flag.
+pub enum Transitive {}
+/// Relation with the `ONE_TO_ONE` flag.
+pub enum OneToOne {}
+/// Relation with the `SYMMETRIC` and `EXCLUSIVE` flags.
+pub enum Pairing {}
+/// Relation with the `TARGET_CASCADING_DESTRUCT` flag.
+pub enum TargetCascading {}
+/// Relation with the `ACYCLIC` flag.
+pub enum Acyclic {}
+
+/// The components an operation can work on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentKind {
+    /// `CompA`
+    A,
+    /// `CompB`
+    B,
+    /// `Tag`
+    Tag,
+}
+
+/// The relations an operation can work on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelationKind {
+    /// `Plain`
+    Plain,
+    /// `Exclusive`
+    Exclusive,
+    /// `Symmetric`
+    Symmetric,
+    /// `Cascading`
+    Cascading,
+    /// `Transitive`
+    Transitive,
+    /// `OneToOne`
+    OneToOne,
+    /// `Pairing`
+    Pairing,
+    /// `TargetCascading`
+    TargetCascading,
+    /// `Acyclic`
+    Acyclic,
+}
+
+const RELATION_KINDS: [RelationKind; 9] = [
+    RelationKind::Plain,
+    RelationKind::Exclusive,
+    RelationKind::Symmetric,
+    RelationKind::Cascading,
+    RelationKind::Transitive,
+    RelationKind::OneToOne,
+    RelationKind::Pairing,
+    RelationKind::TargetCascading,
+    RelationKind::Acyclic,
+];
+
+/// Runs `$body` with `$r` as type alias for the relation type of `$kind`.
+macro_rules! with_relation {
+    ($kind:expr, $r:ident => $body:expr) => {
+        match $kind {
+            RelationKind::Plain => {
+                type $r = Plain;
+                $body
+            }
+            RelationKind::Exclusive => {
+                type $r = Exclusive;
+                $body
+            }
+            RelationKind::Symmetric => {
+                type $r = Symmetric;
+                $body
+            }
+            RelationKind::Cascading => {
+                type $r = Cascading;
+                $body
+            }
+            RelationKind::Transitive => {
+                type $r = Transitive;
+                $body
+            }
+            RelationKind::OneToOne => {
+                type $r = OneToOne;
+                $body
+            }
+            RelationKind::Pairing => {
+                type $r = Pairing;
+                $body
+            }
+            RelationKind::TargetCascading => {
+                type $r = TargetCascading;
+                $body
+            }
+            RelationKind::Acyclic => {
+                type $r = Acyclic;
+                $body
+            }
+        }
+    };
+}
+
+/// A structural change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// create an entity
+    Create,
+    /// destroy an entity
+    Destroy(Entity),
+    /// add or overwrite a component, the value is ignored for `Tag`
+    Add(Entity, ComponentKind, u32),
+    /// remove a component
+    Remove(Entity, ComponentKind),
+    /// relate from, to
+    Relate(RelationKind, Entity, Entity),
+    /// unrelate from, to
+    Unrelate(RelationKind, Entity, Entity),
+}
+
+/// An operation applied by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// applied directly
+    Immediate(Action),
+    /// applied through `create_deferred` or `EntityViewDeferred`
+    Deferred(Action),
+    /// `World::process()`
+    Process,
+}
+
+/// The `World` and the `ReferenceWorld` disagree.
+#[derive(Debug)]
+pub struct Mismatch {
+    /// seed the operations were generated with
+    pub seed: u64,
+    /// every operation that was applied, the last one caused the mismatch
+    pub operations: Vec<Operation>,
+    /// what was compared
+    pub check: String,
+    /// result of the reference model
+    pub expected: Vec<String>,
+    /// result of the world
+    pub actual: Vec<String>,
+}
+
+/// xorshift64*, good enough for generating test cases
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // the state must not be 0
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// random number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+/// Applies the same operations to a `World` and a `ReferenceWorld`.
+pub struct Driver {
+    seed: u64,
+    rng: Rng,
+    world: World,
+    model: ReferenceWorld,
+    /// deferred actions of the model, applied on `Operation::Process`
+    queue: Vec<Action>,
+    operations: Vec<Operation>,
+    /// results of the last immediate relate, if they differ
+    relate_mismatch: Option<(String, String)>,
+}
+
+impl Driver {
+    /// Creates a driver with all driver types registered in both worlds.
+    pub fn new(seed: u64) -> Self {
+        let mut world = World::new();
+        world.register_component::<CompA>();
+        world.register_component::<CompB>();
+        world.register_component::<Tag>();
+        let mut model = ReferenceWorld::new();
+        for kind in RELATION_KINDS {
+            let flags = match kind {
+                RelationKind::Plain => 0,
+                RelationKind::Exclusive => EXCLUSIVE,
+                RelationKind::Symmetric => SYMMETRIC,
+                RelationKind::Cascading => CASCADING_DESTRUCT,
+                RelationKind::Transitive => TRANSITIVE,
+                RelationKind::OneToOne => ONE_TO_ONE,
+                RelationKind::Pairing => SYMMETRIC | EXCLUSIVE,
+                RelationKind::TargetCascading => TARGET_CASCADING_DESTRUCT,
+                RelationKind::Acyclic => ACYCLIC,
+            };
+            with_relation!(kind, R => {
+                world.register_relation_flags::<R>(flags);
+                model.register_relation_flags::<R>(flags);
+            });
+        }
+        Self {
+            seed,
+            rng: Rng::new(seed),
+            world,
+            model,
+            queue: Vec::new(),
+            operations: Vec::new(),
+            relate_mismatch: None,
+        }
+    }
+
+    /// The real world.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The reference model.
+    pub fn model(&self) -> &ReferenceWorld {
+        &self.model
+    }
+
+    /// Every operation that was applied so far.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Generates a random operation and applies it.
+    pub fn step(&mut self) -> Operation {
+        let op = self.random_operation();
+        self.apply(op);
+        op
+    }
+
+    /// Applies an operation to both worlds.
+    ///
+    /// Immediate actions must only reference alive entities.
+    pub fn apply(&mut self, op: Operation) {
+        self.operations.push(op);
+        self.relate_mismatch = None;
+        match op {
+            Operation::Immediate(action) => self.apply_immediate(action),
+            Operation::Deferred(action) => self.apply_deferred(action),
+            Operation::Process => {
+                self.world.process();
+                self.model.realize_deferred();
+                for action in std::mem::take(&mut self.queue) {
+                    self.apply_model_deferred(action);
+                }
+            }
+        }
+    }
+
+    fn apply_immediate(&mut self, action: Action) {
+        let world = &mut self.world;
+        let model = &mut self.model;
+        match action {
+            Action::Create => {
+                let e = world.create_entity();
+                model.create(e);
+            }
+            Action::Destroy(e) => {
+                world.destroy(e);
+                model.destroy(e);
+            }
+            Action::Add(e, kind, val) => match kind {
+                ComponentKind::A => {
+                    world.add_component(e, CompA(val));
+                    model.add_component(e, CompA(val));
+                }
+                ComponentKind::B => {
+                    world.add_component(e, CompB(val));
+                    model.add_component(e, CompB(val));
+                }
+                ComponentKind::Tag => {
+                    world.add_component(e, Tag);
+                    model.add_component(e, Tag);
+                }
+            },
+            Action::Remove(e, kind) => match kind {
+                ComponentKind::A => {
+                    world.remove_component::<CompA>(e);
+                    model.remove_component::<CompA>(e);
+                }
+                ComponentKind::B => {
+                    world.remove_component::<CompB>(e);
+                    model.remove_component::<CompB>(e);
+                }
+                ComponentKind::Tag => {
+                    world.remove_component::<Tag>(e);
+                    model.remove_component::<Tag>(e);
+                }
+            },
+            // relations that would close a cycle are rejected by both sides
+            Action::Relate(kind, from, to) => with_relation!(kind, R => {
+                let actual = world.try_add_relation::<R>(from, to);
+                let expected = model.try_add_relation::<R>(from, to);
+                if expected != actual {
+                    self.relate_mismatch = Some((format!("{expected:?}"), format!("{actual:?}")));
+                }
+            }),
+            Action::Unrelate(kind, from, to) => with_relation!(kind, R => {
+                world.remove_relation::<R>(from, to);
+                model.remove_relation::<R>(from, to);
+            }),
+        }
+    }
+
+    fn apply_deferred(&mut self, action: Action) {
+        let world = &self.world;
+        match action {
+            Action::Create => {
+                let e = world.create_deferred().entity;
+                self.model.create_deferred(e);
+                return;
+            }
+            Action::Destroy(e) => world.view_deferred(e).destroy(),
+            Action::Add(e, kind, val) => {
+                let view = world.view_deferred(e);
+                match kind {
+                    ComponentKind::A => view.add(CompA(val)),
+                    ComponentKind::B => view.add(CompB(val)),
+                    ComponentKind::Tag => view.add(Tag),
+                };
+            }
+            Action::Remove(e, kind) => {
+                let view = world.view_deferred(e);
+                match kind {
+                    ComponentKind::A => view.remove::<CompA>(),
+                    ComponentKind::B => view.remove::<CompB>(),
+                    ComponentKind::Tag => view.remove::<Tag>(),
+                };
+            }
+            Action::Relate(kind, from, to) => with_relation!(kind, R => {
+                world.view_deferred(from).relate_to::<R>(to);
+            }),
+            Action::Unrelate(kind, from, to) => with_relation!(kind, R => {
+                world.view_deferred(from).unrelate_to::<R>(to);
+            }),
+        }
+        self.queue.push(action);
+    }
+
+    /// deferred actions are skipped if an entity died before `process()` got to them
+    fn apply_model_deferred(&mut self, action: Action) {
+        let alive = |e: &Entity| self.model.is_alive(*e);
+        let skip = match &action {
+            Action::Create => unreachable!("Deferred creation is not queued."),
+            Action::Destroy(_) => false,
+            Action::Add(e, _, _) | Action::Remove(e, _) => !alive(e),
+            Action::Relate(_, from, to) | Action::Unrelate(_, from, to) => {
+                !alive(from) || !alive(to)
+            }
+        };
+        if skip {
+            return;
+        }
+        let model = &mut self.model;
+        match action {
+            Action::Create => unreachable!(),
+            Action::Destroy(e) => model.destroy(e),
+            Action::Add(e, kind, val) => match kind {
+                ComponentKind::A => model.add_component(e, CompA(val)),
+                ComponentKind::B => model.add_component(e, CompB(val)),
+                ComponentKind::Tag => model.add_component(e, Tag),
+            },
+            Action::Remove(e, kind) => match kind {
+                ComponentKind::A => model.remove_component::<CompA>(e),
+                ComponentKind::B => model.remove_component::<CompB>(e),
+                ComponentKind::Tag => model.remove_component::<Tag>(e),
+            },
+            Action::Relate(kind, from, to) => {
+                with_relation!(kind, R => model.add_relation::<R>(from, to))
+            }
+            Action::Unrelate(kind, from, to) => {
+                with_relation!(kind, R => model.remove_relation::<R>(from, to))
+            }
+        }
+    }
+
+    fn random_operation(&mut self) -> Operation {
+        let alive = self.model.entities();
+        if !self.queue.is_empty() && self.rng.below(10) == 0 {
+            return Operation::Process;
+        }
+        let deferred = self.rng.below(4) == 0;
+        let candidates = if deferred {
+            let mut candidates = alive;
+            candidates.extend_from_slice(&self.model.pending);
+            candidates
+        } else {
+            alive
+        };
+        // keep the world small, so that relations and joins actually happen
+        if candidates.is_empty() || (candidates.len() < 20 && self.rng.below(8) == 0) {
+            return if deferred {
+                Operation::Deferred(Action::Create)
+            } else {
+                Operation::Immediate(Action::Create)
+            };
+        }
+        let rng = &mut self.rng;
+        let components = [ComponentKind::A, ComponentKind::B, ComponentKind::Tag];
+        // `process()` panics if a deferred relation closes a cycle,
+        // so acyclic relations are only added immediately
+        let relate_kinds: Vec<RelationKind> = RELATION_KINDS
+            .into_iter()
+            .filter(|kind| !deferred || *kind != RelationKind::Acyclic)
+            .collect();
+        let e = rng.pick(&candidates);
+        let action = match rng.below(16) {
+            0 => Action::Destroy(e),
+            1..=5 => Action::Add(e, rng.pick(&components), rng.below(100) as u32),
+            6 | 7 => Action::Remove(e, rng.pick(&components)),
+            8..=13 => Action::Relate(rng.pick(&relate_kinds), e, rng.pick(&candidates)),
+            _ => Action::Unrelate(rng.pick(&RELATION_KINDS), e, rng.pick(&candidates)),
+        };
+        if deferred {
+            Operation::Deferred(action)
+        } else {
+            Operation::Immediate(action)
+        }
+    }
+
+    /// Compares the world with the reference model.
+    pub fn compare(&self) -> Result<(), Mismatch> {
+        let world = &self.world;
+        let model = &self.model;
+        let entities = model.entities();
+
+        if let Some((expected, actual)) = &self.relate_mismatch {
+            return self.mismatch(
+                "try_add_relation",
+                vec![expected.clone()],
+                vec![actual.clone()],
+            );
+        }
+
+        if let Err(errors) = world.validate() {
+            let actual = errors.iter().map(|it| format!("{it:?}")).collect();
+            return self.mismatch("World::validate", Vec::new(), actual);
+        }
+
+        let expected: Vec<Entity> = entities.clone();
+        let mut actual: Vec<Entity> = self
+            .operations_entities()
+            .into_iter()
+            .filter(|e| world.is_alive(*e))
+            .collect();
+        actual.sort_by_key(|e| e.id.0);
+        actual.dedup();
+        self.compare_results("alive entities", expected, actual)?;
+
+        let expected = entities
+            .iter()
+            .filter_map(|e| model.get_component::<CompA>(*e).map(|a| (*e, *a)))
+            .collect();
+        let actual = query!(world, &this, CompA)
+            .map(|(e, a)| (e.entity, *a))
+            .collect();
+        self.compare_results("CompA", expected, actual)?;
+
+        let expected = entities
+            .iter()
+            .filter(|e| !model.has_component::<CompB>(**e))
+            .filter_map(|e| model.get_component::<CompA>(*e).map(|a| (*e, *a)))
+            .collect();
+        let actual = query!(world, &this, CompA, !CompB)
+            .map(|(e, a)| (e.entity, *a))
+            .collect();
+        self.compare_results("CompA, !CompB", expected, actual)?;
+
+        let expected = entities
+            .iter()
+            .filter(|e| model.has_component::<Tag>(**e))
+            .filter_map(|e| model.get_component::<CompB>(*e).map(|b| (*e, *b)))
+            .collect();
+        let actual = query!(world, &this, _ Tag, CompB)
+            .map(|(e, b)| (e.entity, *b))
+            .collect();
+        self.compare_results("_ Tag, CompB", expected, actual)?;
+
+        let expected = model.relation_pairs::<Plain>();
+        let actual = query!(world, &a, &b, Plain(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Plain(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Exclusive>();
+        let actual = query!(world, &a, &b, Exclusive(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Exclusive(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Symmetric>();
+        let actual = query!(world, &a, &b, Symmetric(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Symmetric(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Cascading>();
+        let actual = query!(world, &a, &b, Cascading(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Cascading(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Transitive>();
+        let actual = query!(world, &a, &b, Transitive(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Transitive(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<OneToOne>();
+        let actual = query!(world, &a, &b, OneToOne(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("OneToOne(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Pairing>();
+        let actual = query!(world, &a, &b, Pairing(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Pairing(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<TargetCascading>();
+        let actual = query!(world, &a, &b, TargetCascading(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("TargetCascading(a, b)", expected, actual)?;
+
+        let expected = model.relation_pairs::<Acyclic>();
+        let actual = query!(world, &a, &b, Acyclic(a, b))
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+        self.compare_results("Acyclic(a, b)", expected, actual)?;
+
+        let expected = model
+            .relation_pairs::<Plain>()
+            .into_iter()
+            .filter_map(|(a, b)| {
+                let comp_a = model.get_component::<CompA>(a)?;
+                let comp_b = model.get_component::<CompB>(b)?;
+                Some((a, b, *comp_a, *comp_b))
+            })
+            .collect();
+        let actual = query!(world, &a, &b, CompA(a), Plain(a, b), CompB(b))
+            .map(|(a, b, comp_a, comp_b)| (a.entity, b.entity, *comp_a, *comp_b))
+            .collect();
+        self.compare_results("CompA(a), Plain(a, b), CompB(b)", expected, actual)?;
+
+        let expected = entities
+            .iter()
+            .filter(|e| !model.related::<Exclusive>(**e).is_empty())
+            .filter_map(|e| model.get_component::<CompA>(*e).map(|a| (*e, *a)))
+            .collect();
+        let actual = query!(world, &this, CompA, Exclusive(this, _))
+            .map(|(e, a)| (e.entity, *a))
+            .collect();
+        self.compare_results("CompA, Exclusive(this, _)", expected, actual)?;
+
+        let expected = model
+            .relation_pairs::<Transitive>()
+            .into_iter()
+            .filter(|(a, b)| a != b)
+            .filter_map(|(a, b)| model.get_component::<CompB>(b).map(|comp| (a, b, *comp)))
+            .collect();
+        let actual = query!(world, &a, &b, CompB(b), Transitive(a, b), a != b)
+            .map(|(a, b, comp)| (a.entity, b.entity, *comp))
+            .collect();
+        self.compare_results("CompB(b), Transitive(a, b), a != b", expected, actual)?;
+
+        if let Some(x) = entities.first().copied() {
+            let expected = entities
+                .iter()
+                .filter(|e| !model.related::<Plain>(**e).contains(&x))
+                .filter_map(|e| model.get_component::<CompA>(*e).map(|a| (*e, *a)))
+                .collect();
+            let actual = query!(world, &this, CompA, !Plain(this, *x))
+                .map(|(e, a)| (e.entity, *a))
+                .collect();
+            self.compare_results("CompA, !Plain(this, *x)", expected, actual)?;
+        }
+        Ok(())
+    }
+
+    /// every entity that was mentioned or created so far, alive or not
+    fn operations_entities(&self) -> Vec<Entity> {
+        let mut result = self.model.entities();
+        for op in &self.operations {
+            let (Operation::Immediate(action) | Operation::Deferred(action)) = op else {
+                continue;
+            };
+            match action {
+                Action::Create => {}
+                Action::Destroy(e) | Action::Add(e, _, _) | Action::Remove(e, _) => result.push(*e),
+                Action::Relate(_, a, b) | Action::Unrelate(_, a, b) => {
+                    result.push(*a);
+                    result.push(*b);
+                }
+            }
+        }
+        result
+    }
+
+    fn compare_results<T: Debug>(
+        &self,
+        check: &str,
+        expected: Vec<T>,
+        actual: Vec<T>,
+    ) -> Result<(), Mismatch> {
+        let mut expected: Vec<String> = expected.iter().map(|it| format!("{it:?}")).collect();
+        let mut actual: Vec<String> = actual.iter().map(|it| format!("{it:?}")).collect();
+        expected.sort();
+        actual.sort();
+        if expected == actual {
+            Ok(())
+        } else {
+            self.mismatch(check, expected, actual)
+        }
+    }
+
+    fn mismatch(
+        &self,
+        check: &str,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    ) -> Result<(), Mismatch> {
+        Err(Mismatch {
+            seed: self.seed,
+            operations: self.operations.clone(),
+            check: check.to_string(),
+            expected,
+            actual,
+        })
+    }
+}
+
+/// Applies `steps` random operations generated from `seed`
+/// and compares the world with the reference model after each of them.
+pub fn run(seed: u64, steps: usize) -> Result<(), Mismatch> {
+    let mut driver = Driver::new(seed);
+    for _ in 0..steps {
+        driver.step();
+        driver.compare()?;
+    }
+    driver.apply(Operation::Process);
+    driver.compare()
+}
@@ -0,0 +1,125 @@
+#![deny(missing_docs)]
+//! contains `Traversal` and `TraversalOrder`
+//! This module intended for direct use by the library user.
+//!
+//! A `Traversal` follows a relation from entity to entity, no matter if it is `TRANSITIVE`.
+//! It is used by `World::relation_targets_transitive`, `Bookkeeping::has_relation`
+//! and the `RelationHelper` of queries.
+
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+    bookkeeping::Bookkeeping, component::ComponentId, entity_store::EntityId,
+    relation_vec::RelationVec,
+};
+
+/// Order in which a `Traversal` visits related entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraversalOrder {
+    /// all entities one hop away first, then all entities two hops away and so on
+    #[default]
+    BreadthFirst,
+    /// follows every relation as far as possible before going back
+    DepthFirst,
+}
+
+/// Lazily visits the entities that are related over one or more hops.
+///
+/// Every entity is visited once, even if the relation has cycles.
+/// The starting entity is only visited if a cycle leads back to it.
+pub struct Traversal<'a> {
+    bk: &'a Bookkeeping,
+    cid: ComponentId,
+    order: TraversalOrder,
+    max_depth: Option<u32>,
+    work: VecDeque<(u32, u32)>,
+    /// entity => smallest depth it was pushed with and if it was visited already
+    seen: HashMap<u32, (u32, bool)>,
+}
+
+impl<'a> Traversal<'a> {
+    /// Creates a traversal without a start, see `from_origin` and `push`.
+    ///
+    /// `cid` is the relation component that is followed,
+    /// use the flipped target id to go from targets to origins.
+    /// Entities more than `max_depth` hops away are not visited.
+    pub fn new(
+        bk: &'a Bookkeeping,
+        cid: ComponentId,
+        order: TraversalOrder,
+        max_depth: Option<u32>,
+    ) -> Self {
+        Self {
+            bk,
+            cid,
+            order,
+            max_depth,
+            work: VecDeque::new(),
+            seen: HashMap::default(),
+        }
+    }
+
+    /// Starts at the entities directly related to `origin`.
+    pub fn from_origin(mut self, origin: EntityId) -> Self {
+        self.push_related(origin, 0);
+        self
+    }
+
+    /// Adds an entity that is `depth` hops away from the start.
+    pub fn push(&mut self, id: EntityId, depth: u32) {
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+        match self.seen.get_mut(&id.0) {
+            // in depth first order an entity may be found again over a shorter path,
+            // then it has to be followed again, so that the depth limit doesn't cut off too much
+            Some((old, _)) if *old <= depth || self.max_depth.is_none() => return,
+            Some((old, _)) => *old = depth,
+            None => {
+                self.seen.insert(id.0, (depth, false));
+            }
+        }
+        self.work.push_back((id.0, depth));
+    }
+
+    fn push_related(&mut self, id: EntityId, depth: u32) {
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+        if let Some(ptr) = self.bk.get_component_opt_unchecked(id, self.cid) {
+            let rel_vec = unsafe { &*(ptr as *const RelationVec) };
+            for related in rel_vec.iter() {
+                self.push(EntityId(*related), depth + 1);
+            }
+        }
+    }
+}
+
+impl Iterator for Traversal<'_> {
+    /// the entity and the number of hops it was found at
+    ///
+    /// In breadth first order that is the smallest number of hops.
+    type Item = (EntityId, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (id, depth) = match self.order {
+                TraversalOrder::BreadthFirst => self.work.pop_front()?,
+                TraversalOrder::DepthFirst => self.work.pop_back()?,
+            };
+            let (smallest, visited) = self.seen.get_mut(&id).unwrap();
+            if *smallest < depth {
+                // pushed again with a smaller depth
+                continue;
+            }
+            let first_visit = !*visited;
+            *visited = true;
+            self.push_related(EntityId(id), depth);
+            if first_visit {
+                return Some((EntityId(id), depth));
+            }
+        }
+    }
+}
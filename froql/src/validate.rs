@@ -0,0 +1,291 @@
+#![deny(missing_docs)]
+//! contains `IntegrityError` and `World::validate`
+//! This module intended for direct use by the library user.
+//!
+//! Most invariants of the storage are only checked with `debug_assert!` where they are used.
+//! `World::validate` checks all of them at once, which helps to find the place
+//! where the storage got corrupted.
+
+use crate::{
+    archetype::{ArchetypeId, ArchetypeRow},
+    bookkeeping::Bookkeeping,
+    component::ComponentId,
+    entity_store::EntityId,
+    relation_vec::RelationVec,
+    world::World,
+};
+
+/// A violated invariant found by `World::validate`
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityError {
+    /// The archetype has a different number of components and columns.
+    ColumnCount {
+        /// the broken archetype
+        archetype: ArchetypeId,
+        /// number of components
+        components: usize,
+        /// number of columns
+        columns: usize,
+    },
+    /// A column has a different length than the archetype has entities.
+    ColumnLength {
+        /// the broken archetype
+        archetype: ArchetypeId,
+        /// name of the component stored in the column
+        component: String,
+        /// length of the column
+        column_len: u32,
+        /// number of entities in the archetype
+        entity_count: usize,
+    },
+    /// The component does not know that it is part of the archetype,
+    /// so queries will skip the archetype.
+    ArchetypeNotIndexed {
+        /// the archetype that is missing in the index
+        archetype: ArchetypeId,
+        /// name of the component
+        component: String,
+    },
+    /// An archetype contains an entity that is dead.
+    DeadEntityInArchetype {
+        /// the dead entity
+        entity: EntityId,
+        /// the archetype containing it
+        archetype: ArchetypeId,
+    },
+    /// The entity store and the archetype disagree about where an entity is stored.
+    EntityLocation {
+        /// the misplaced entity
+        entity: EntityId,
+        /// location according to the entity store
+        expected: (ArchetypeId, ArchetypeRow),
+        /// entity found at that location, if any
+        found: Option<EntityId>,
+    },
+    /// The free list of the entity store does not contain every empty slot exactly once.
+    FreeList,
+    /// An entity has a relation component without any partners.
+    EmptyRelation {
+        /// the entity with the empty relation
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+    },
+    /// A relation contains the same partner more than once.
+    DuplicatePartner {
+        /// the entity with the duplicate
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+        /// the duplicate partner
+        partner: EntityId,
+    },
+    /// An entity is related to a dead entity.
+    DeadPartner {
+        /// the entity with the broken relation
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+        /// the dead partner
+        partner: EntityId,
+    },
+    /// The other side of a relation does not point back.
+    MissingMirror {
+        /// the entity whose relation is not mirrored
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+        /// the partner that does not point back
+        partner: EntityId,
+    },
+    /// The origin of an exclusive relation has more than one target.
+    ExclusiveViolation {
+        /// the origin with too many targets
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+        /// number of targets
+        targets: u32,
+    },
+    /// The target of an `EXCLUSIVE_TARGET` relation has more than one origin.
+    ExclusiveTargetViolation {
+        /// the target with too many origins
+        entity: EntityId,
+        /// name of the relation
+        relation: String,
+        /// number of origins
+        origins: u32,
+    },
+}
+
+impl World {
+    /// Checks all internal invariants of the storage.
+    /// Returns every violation that was found.
+    ///
+    /// This is slow, because it visits every entity and every relation.
+    /// It is intended for tests and debugging.
+    pub fn validate(&self) -> Result<(), Vec<IntegrityError>> {
+        let mut errors = Vec::new();
+        let bk = &self.bookkeeping;
+        validate_archetypes(bk, &mut errors);
+        validate_entities(bk, &mut errors);
+        validate_relations(bk, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn component_name(bk: &Bookkeeping, cid: ComponentId) -> String {
+    bk.components[cid.as_index()].name.clone()
+}
+
+fn validate_archetypes(bk: &Bookkeeping, errors: &mut Vec<IntegrityError>) {
+    for (index, a) in bk.archetypes.iter().enumerate() {
+        let aid = ArchetypeId(index as u32);
+        if a.components.len() != a.columns.len() {
+            errors.push(IntegrityError::ColumnCount {
+                archetype: aid,
+                components: a.components.len(),
+                columns: a.columns.len(),
+            });
+        }
+        for (cid, column) in a.components.iter().zip(&a.columns) {
+            if column.len() as usize != a.entities.len() {
+                errors.push(IntegrityError::ColumnLength {
+                    archetype: aid,
+                    component: component_name(bk, *cid),
+                    column_len: column.len(),
+                    entity_count: a.entities.len(),
+                });
+            }
+            if !bk.components[cid.as_index()].has_archetype(aid, *cid) {
+                errors.push(IntegrityError::ArchetypeNotIndexed {
+                    archetype: aid,
+                    component: component_name(bk, *cid),
+                });
+            }
+        }
+    }
+}
+
+fn validate_entities(bk: &Bookkeeping, errors: &mut Vec<IntegrityError>) {
+    // archetype -> entity store
+    for (index, a) in bk.archetypes.iter().enumerate() {
+        let aid = ArchetypeId(index as u32);
+        for id in &a.entities {
+            if !bk.entities.is_alive_id(*id) {
+                errors.push(IntegrityError::DeadEntityInArchetype {
+                    entity: *id,
+                    archetype: aid,
+                });
+            }
+        }
+    }
+
+    // entity store -> archetype
+    for (id, aid, row) in bk.entities.alive_slots() {
+        let found = bk
+            .archetypes
+            .get(aid.as_index())
+            .and_then(|a| a.entities.get(row.as_index()))
+            .copied();
+        if found != Some(id) {
+            errors.push(IntegrityError::EntityLocation {
+                entity: id,
+                expected: (aid, row),
+                found,
+            });
+        }
+    }
+
+    if !bk.entities.free_list_is_valid() {
+        errors.push(IntegrityError::FreeList);
+    }
+}
+
+fn validate_relations(bk: &Bookkeeping, errors: &mut Vec<IntegrityError>) {
+    for a in &bk.archetypes {
+        if a.components.len() != a.columns.len() {
+            continue;
+        }
+        for (index, cid) in a.components.iter().enumerate() {
+            // broken columns are already reported, reading them is not safe
+            if !cid.is_relation() || a.columns[index].len() as usize != a.entities.len() {
+                continue;
+            }
+            for (row, id) in a.entities.iter().enumerate() {
+                let ptr = unsafe { a.columns[index].get(row as u32) } as *const RelationVec;
+                let rel_vec = unsafe { &*ptr };
+                let relation = || component_name(bk, *cid);
+                if rel_vec.is_empty() {
+                    errors.push(IntegrityError::EmptyRelation {
+                        entity: *id,
+                        relation: relation(),
+                    });
+                }
+                if cid.is_exclusive() && rel_vec.len() > 1 {
+                    errors.push(IntegrityError::ExclusiveViolation {
+                        entity: *id,
+                        relation: relation(),
+                        targets: rel_vec.len(),
+                    });
+                }
+                if cid.is_target() && cid.flip_target().is_exclusive_target() && rel_vec.len() > 1 {
+                    errors.push(IntegrityError::ExclusiveTargetViolation {
+                        entity: *id,
+                        relation: relation(),
+                        origins: rel_vec.len(),
+                    });
+                }
+                for (i, partner) in rel_vec.iter().enumerate() {
+                    let partner = EntityId(*partner);
+                    if rel_vec[..i].contains(&partner.0) {
+                        errors.push(IntegrityError::DuplicatePartner {
+                            entity: *id,
+                            relation: relation(),
+                            partner,
+                        });
+                        continue;
+                    }
+                    if !bk.entities.is_alive_id(partner) {
+                        errors.push(IntegrityError::DeadPartner {
+                            entity: *id,
+                            relation: relation(),
+                            partner,
+                        });
+                        continue;
+                    }
+                    if !points_back(bk, partner, cid.flip_target(), *id) {
+                        errors.push(IntegrityError::MissingMirror {
+                            entity: *id,
+                            relation: relation(),
+                            partner,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// checks that `partner` has `id` in its relation vec for `cid`
+fn points_back(bk: &Bookkeeping, partner: EntityId, cid: ComponentId, id: EntityId) -> bool {
+    let (aid, row) = bk.entities.get_archetype_unchecked(partner);
+    let Some(a) = bk.archetypes.get(aid.as_index()) else {
+        return false;
+    };
+    if row.as_index() >= a.entities.len() {
+        return false;
+    }
+    let Some(column) = a.find_column_opt(cid) else {
+        return false;
+    };
+    if column.len() <= row.0 {
+        return false;
+    }
+    let rel_vec = unsafe { &*(column.get(row.0) as *const RelationVec) };
+    rel_vec.contains(&id.0)
+}
@@ -11,14 +11,21 @@ use std::{
 
 use crate::{
     bookkeeping::{Bookkeeping, EnsureComponentResult},
-    component::{Component, ComponentId, RELATION},
+    component::{
+        ACYCLIC, CASCADING_DESTRUCT, Component, ComponentId, PANIC_ON_ORIGIN_DESTRUCT,
+        PANIC_ON_TARGET_DESTRUCT, RELATION, SYMMETRIC, TARGET_CASCADING_DESTRUCT,
+    },
     entity_store::{Entity, EntityId},
     entity_view_deferred::{DeferredOperation, EntityViewDeferred},
     entity_view_mut::EntityViewMut,
+    hot_reload::{ManifestEntry, RegistrationKind},
     relation::Relation,
+    traversal::{Traversal, TraversalOrder},
     util::short_type_name,
 };
 
+pub use crate::bookkeeping::{AddRelationError, Displaced};
+
 /// The `World` is the central datastructure in froql that holds all state.
 pub struct World {
     /// internal state management
@@ -28,6 +35,8 @@ pub struct World {
     pub(crate) deferred_queue: RefCell<DeferredQueue>,
     // TODO move into query or something
     singleton: Entity,
+    /// every registered type in order of registration, used for hotreloading
+    pub(crate) manifest: Vec<ManifestEntry>,
 }
 
 /// This is a queue of operations that will be executed during `world.process()`
@@ -49,6 +58,7 @@ impl World {
                 operations: Vec::new(),
             }),
             singleton,
+            manifest: Vec::new(),
         }
     }
 
@@ -59,6 +69,7 @@ impl World {
         &mut self,
         flags: u32,
         short_name: &str,
+        full_name: &str,
     ) -> ComponentId {
         let tid = TypeId::of::<T>();
         if let Some(cid) = self.bookkeeping.get_component_id(tid) {
@@ -70,9 +81,23 @@ impl World {
             .components
             .push(Component::new::<T>(cid, short_name));
         self.bookkeeping.component_map.insert(tid, cid);
+        if flags & (PANIC_ON_ORIGIN_DESTRUCT | PANIC_ON_TARGET_DESTRUCT) > 0 {
+            self.bookkeeping.has_panic_policy = true;
+        }
         let tname = type_name::<T>().to_string();
         let old = self.bookkeeping.component_name_map.insert(tname, tid);
         assert_eq!(None, old, "Typename was already registered.");
+        let kind = if flags & RELATION > 0 {
+            RegistrationKind::Relation
+        } else {
+            RegistrationKind::Component
+        };
+        self.manifest.push(ManifestEntry {
+            type_name: full_name.to_string(),
+            kind,
+            flags: flags & !RELATION,
+            debug: false,
+        });
         return cid;
     }
 
@@ -89,7 +114,7 @@ impl World {
             let arch = &mut self.bookkeeping.archetypes[aid.as_index()];
             let col = arch.find_column_mut(cid);
             unsafe {
-                col.change_drop_function(component.drop_fn.clone());
+                col.change_drop_function(component.drop_fn);
             }
         }
         Ok(())
@@ -110,26 +135,30 @@ impl World {
             debug_fn(&val.borrow(), formatter)
         };
         self.bookkeeping.components[cid.as_index()].debug_fn = Some(debug_fn_wrapped);
+        let name = type_name::<T>();
+        if let Some(entry) = self.manifest.iter_mut().find(|it| it.type_name == name) {
+            entry.debug = true;
+        }
     }
 
     /// Convenience method for getting a Component of the singleton entity.
     ///
     /// The singleton entity is meant to be used for things that only exist once.
-    pub fn singleton<T: 'static>(&self) -> Ref<T> {
+    pub fn singleton<T: 'static>(&self) -> Ref<'_, T> {
         self.get_component::<T>(self.singleton)
     }
 
     /// Convenience method for getting an optional Component of the singleton entity.
     ///
     /// The singleton entity is meant to be used for things that only exist once.
-    pub fn singleton_opt<T: 'static>(&self) -> Option<Ref<T>> {
+    pub fn singleton_opt<T: 'static>(&self) -> Option<Ref<'_, T>> {
         self.get_component_opt::<T>(self.singleton)
     }
 
     /// Convenience method for getting a mutable ref to a Component of the singleton entity.
     ///
     /// The singleton entity is meant to be used for things that only exist once.
-    pub fn singleton_mut<T: 'static>(&self) -> RefMut<T> {
+    pub fn singleton_mut<T: 'static>(&self) -> RefMut<'_, T> {
         self.get_component_mut::<T>(self.singleton)
     }
 
@@ -137,7 +166,7 @@ impl World {
     /// the singleton entity.
     ///
     /// The singleton entity is meant to be used for things that only exist once.
-    pub fn singleton_mut_opt<T: 'static>(&self) -> Option<RefMut<T>> {
+    pub fn singleton_mut_opt<T: 'static>(&self) -> Option<RefMut<'_, T>> {
         self.get_component_mut_opt::<T>(self.singleton)
     }
 
@@ -164,7 +193,7 @@ impl World {
 
     /// Registers component type for later use.
     pub fn register_component<T: 'static>(&mut self) -> ComponentId {
-        self.register_component_inner::<RefCell<T>>(0, short_type_name::<T>())
+        self.register_component_inner::<RefCell<T>>(0, short_type_name::<T>(), type_name::<T>())
     }
 
     /// This allows reusing the same world in hotreloading scenarios.
@@ -174,6 +203,9 @@ impl World {
     /// DO NOT ship code that calls this method in production.
     /// Use feature flags to accomplish this.
     ///
+    /// This resets the debug formatter of the component.
+    /// Use `reload_all` to rebind every registered type at once.
+    ///
     /// # SAFETY
     /// This finds the type by using its typename.
     /// If you have two components/relations with the exact same name you are in trouble.
@@ -234,6 +266,38 @@ impl World {
             .unwrap_or_else(|| panic!("ComponentType '{}' is not registered.", type_name::<T>()))
     }
 
+    /// Returns the entity that stands for the component type `T`, it is created on first use.
+    /// Registers the component type if it is not already.
+    ///
+    /// The type entity is a normal entity, so it can be the target of relations,
+    /// like `State(unit, Idle)` or `Eats(unit, Apples)`.
+    /// In queries it is written as `@T`, for example `Eats(this, @Apples)`.
+    pub fn type_entity<T: 'static>(&mut self) -> Entity {
+        let cid = if cfg!(feature = "manual_registration") {
+            self.get_component_id::<T>()
+        } else {
+            self.register_component::<T>()
+        };
+        let component = &self.bookkeeping.components[cid.as_index()];
+        if let Some(e) = component.type_entity
+            && self.is_alive(e)
+        {
+            return e;
+        }
+        let e = self.bookkeeping.create();
+        self.bookkeeping.components[cid.as_index()].type_entity = Some(e);
+        e
+    }
+
+    /// Returns the entity that stands for the component type `T`,
+    /// if it was already created by `type_entity`.
+    pub fn get_type_entity<T: 'static>(&self) -> Option<Entity> {
+        let e = self
+            .bookkeeping
+            .type_entity_or_dead(TypeId::of::<RefCell<T>>());
+        self.is_alive(e).then_some(e)
+    }
+
     /// Creates an Entity and returns it.
     ///
     /// This Entity is not wrapped in a view, so it doesn't carry a lifetime.
@@ -253,7 +317,7 @@ impl World {
     /// Useful for convenience.
     ///
     /// The wrapped Entity can be accessed as `.entity` member on the view.
-    pub fn create(&mut self) -> EntityViewMut {
+    pub fn create(&mut self) -> EntityViewMut<'_> {
         EntityViewMut {
             entity: self.bookkeeping.create(),
             world: self,
@@ -261,7 +325,7 @@ impl World {
     }
 
     /// Wraps an existing Entity in an `EntityViewMut`.
-    pub fn view_mut(&mut self, e: Entity) -> EntityViewMut {
+    pub fn view_mut(&mut self, e: Entity) -> EntityViewMut<'_> {
         EntityViewMut {
             entity: e,
             world: self,
@@ -269,7 +333,7 @@ impl World {
     }
 
     /// Wraps an existing Entity in an `EntityViewDeferred`.
-    pub fn view_deferred(&self, e: Entity) -> EntityViewDeferred {
+    pub fn view_deferred(&self, e: Entity) -> EntityViewDeferred<'_> {
         EntityViewDeferred {
             entity: e,
             world: self,
@@ -280,7 +344,7 @@ impl World {
     /// Useful when you only have shared reference to `World`.
     ///
     /// The wrapped Entity can be accessed as `.entity` member on the view.
-    pub fn create_deferred(&self) -> EntityViewDeferred {
+    pub fn create_deferred(&self) -> EntityViewDeferred<'_> {
         EntityViewDeferred {
             entity: self.bookkeeping.create_deferred(),
             world: self,
@@ -324,7 +388,7 @@ impl World {
     ///
     /// Panics if `Entity` is not alive or does not have the component.
     /// Panics if component type is not registered.
-    pub fn get_component<T: 'static>(&self, e: Entity) -> Ref<T> {
+    pub fn get_component<T: 'static>(&self, e: Entity) -> Ref<'_, T> {
         let cid = self.get_component_id::<T>();
         let ptr = self.bookkeeping.get_component(e, cid) as *const RefCell<T>;
         let cell = unsafe { &*ptr };
@@ -336,11 +400,9 @@ impl World {
     ///
     /// Panics if `Entity` is not alive.
     /// Panics if component type is not registered.
-    pub fn get_component_opt<T: 'static>(&self, e: Entity) -> Option<Ref<T>> {
+    pub fn get_component_opt<T: 'static>(&self, e: Entity) -> Option<Ref<'_, T>> {
         let cid = self.get_component_id::<T>();
-        let Some(ptr) = self.bookkeeping.get_component_opt(e, cid) else {
-            return None;
-        };
+        let ptr = self.bookkeeping.get_component_opt(e, cid)?;
         let ptr = ptr as *const RefCell<T>;
         let cell = unsafe { &*ptr };
         Some(cell.borrow())
@@ -351,7 +413,7 @@ impl World {
     /// Useful if you don't have a generation for whatever reason.
     ///
     /// Panics if `Entity` is not alive or does not have the component.
-    pub fn get_component_by_entityid<T: 'static>(&self, id: EntityId) -> Ref<T> {
+    pub fn get_component_by_entityid<T: 'static>(&self, id: EntityId) -> Ref<'_, T> {
         let cid = self.get_component_id::<T>();
         let ptr = self
             .bookkeeping
@@ -365,7 +427,7 @@ impl World {
     ///
     /// Panics if `Entity` is not alive or does not have the component.
     /// Panics if component type is not registered.
-    pub fn get_component_mut<T: 'static>(&self, e: Entity) -> RefMut<T> {
+    pub fn get_component_mut<T: 'static>(&self, e: Entity) -> RefMut<'_, T> {
         let cid = self.get_component_id::<T>();
         let ptr = self.bookkeeping.get_component(e, cid) as *const RefCell<T>;
         let cell = unsafe { &*ptr };
@@ -377,11 +439,9 @@ impl World {
     ///
     /// Panics if `Entity` is not alive.
     /// Panics if component type is not registered.
-    pub fn get_component_mut_opt<T: 'static>(&self, e: Entity) -> Option<RefMut<T>> {
+    pub fn get_component_mut_opt<T: 'static>(&self, e: Entity) -> Option<RefMut<'_, T>> {
         let cid = self.get_component_id::<T>();
-        let Some(ptr) = self.bookkeeping.get_component_opt(e, cid) else {
-            return None;
-        };
+        let ptr = self.bookkeeping.get_component_opt(e, cid)?;
         let ptr = ptr as *const RefCell<T>;
         let cell = unsafe { &*ptr };
         Some(cell.borrow_mut())
@@ -449,6 +509,10 @@ impl World {
     }
 
     /// Executes all queued deferred operations.
+    ///
+    /// Relations that would close a cycle in an `ACYCLIC` relation are skipped.
+    /// Once all other operations are executed, this panics with a list of them.
+    #[track_caller]
     pub fn process(&mut self) {
         self.bookkeeping.realize_deferred();
 
@@ -456,6 +520,7 @@ impl World {
         let queue = self.deferred_queue.get_mut();
         let ops = &mut queue.operations;
         std::mem::swap(&mut tmp, ops); // too lazy to work around partial borrows here atm
+        let mut rejected = Vec::new();
         for command in tmp {
             match command {
                 DeferredOperation::DestroyEntity(e) => {
@@ -464,27 +529,43 @@ impl World {
                 DeferredOperation::AddComponent(func) => {
                     func(self);
                 }
+                // operations on entities that were destroyed in the meantime are skipped
                 DeferredOperation::RemoveComponent(tid, e) => {
                     let cid = self.bookkeeping.get_component_id(tid).unwrap(); // TODO error msg
-                    self.bookkeeping.remove_component(e, cid, None);
+                    if self.is_alive(e) {
+                        self.bookkeeping.remove_component(e, cid, None);
+                    }
                 }
                 DeferredOperation::AddRelation(tid, from, to) => {
                     let Some(cid) = self.bookkeeping.get_component_id(tid) else {
                         panic!("Can't register relation in deferred context.");
                     };
-                    self.bookkeeping.add_relation(cid, from, to);
+                    if self.is_alive(from)
+                        && self.is_alive(to)
+                        && let Err(err) = self.bookkeeping.try_add_relation(cid, from, to)
+                    {
+                        let name = &self.bookkeeping.components[cid.as_index()].name;
+                        rejected.push(format!("{name}: {err}"));
+                    }
                 }
                 DeferredOperation::RemoveRelation(tid, from, to) => {
                     let Some(cid) = self.bookkeeping.get_component_id(tid) else {
                         panic!("Can't register relation in deferred context.");
                     };
-                    self.bookkeeping.remove_relation(cid, from, to);
+                    if self.is_alive(from) && self.is_alive(to) {
+                        self.bookkeeping.remove_relation(cid, from, to);
+                    }
                 }
                 DeferredOperation::Closure(func) => {
                     func(self);
                 }
             }
         }
+        assert!(
+            rejected.is_empty(),
+            "Deferred relations were not added: {}",
+            rejected.join(" ")
+        );
     }
 }
 
@@ -503,21 +584,49 @@ impl World {
     /// It's recommended to use an inhibited type (enum without variants)
     /// so that you don't confuse components and relations on accident.
     pub fn register_relation<T: 'static>(&mut self) -> ComponentId {
-        self.register_component_inner::<Relation<T>>(RELATION, short_type_name::<T>())
+        self.register_component_inner::<Relation<T>>(
+            RELATION,
+            short_type_name::<T>(),
+            type_name::<T>(),
+        )
     }
 
     /// Registers a relation type with specific flags.
-    /// Flag options are: `EXCLUSIVE`, `SYMMETRIC`, `CASCADING_DESTRUCT` and `TRANSITIVE`
+    /// Flag options are: `EXCLUSIVE`, `EXCLUSIVE_TARGET`, `ONE_TO_ONE`, `SYMMETRIC`,
+    /// `TRANSITIVE`, `ACYCLIC` and the cleanup policies `CASCADING_DESTRUCT`,
+    /// `TARGET_CASCADING_DESTRUCT`, `PANIC_ON_ORIGIN_DESTRUCT` and `PANIC_ON_TARGET_DESTRUCT`
     ///
     /// It's recommended to use an inhibited type (enum without variants)
     /// so that you don't confuse components and relations on accident.
+    #[track_caller]
     pub fn register_relation_flags<T: 'static>(&mut self, flags: u32) {
+        assert!(
+            flags & (SYMMETRIC | ACYCLIC) != SYMMETRIC | ACYCLIC,
+            "A relation can't be SYMMETRIC and ACYCLIC, every symmetric relation is a cycle."
+        );
+        assert!(
+            flags & (CASCADING_DESTRUCT | PANIC_ON_ORIGIN_DESTRUCT)
+                != CASCADING_DESTRUCT | PANIC_ON_ORIGIN_DESTRUCT,
+            "A relation can only have one cleanup policy for destroyed origins."
+        );
+        assert!(
+            flags & (TARGET_CASCADING_DESTRUCT | PANIC_ON_TARGET_DESTRUCT)
+                != TARGET_CASCADING_DESTRUCT | PANIC_ON_TARGET_DESTRUCT,
+            "A relation can only have one cleanup policy for destroyed targets."
+        );
         // TODO: error if component is already registered
-        self.register_component_inner::<Relation<T>>(flags | RELATION, short_type_name::<T>());
+        self.register_component_inner::<Relation<T>>(
+            flags | RELATION,
+            short_type_name::<T>(),
+            type_name::<T>(),
+        );
     }
 
     /// Adds a relationship between two entities.
     /// Registers the relationship type if it is not already.
+    ///
+    /// Panics if the relation is `ACYCLIC` and the new relation would close a cycle.
+    #[track_caller]
     pub fn add_relation<T: 'static>(&mut self, from: Entity, to: Entity) {
         let origin_cid = if cfg!(feature = "manual_registration") {
             self.get_relation_id::<T>()
@@ -528,6 +637,29 @@ impl World {
         self.bookkeeping.add_relation(origin_cid, from, to);
     }
 
+    /// Adds a relationship between two entities.
+    /// Registers the relationship type if it is not already.
+    ///
+    /// Returns an error instead of adding the relation
+    /// if the relation is `ACYCLIC` and the new relation would close a cycle.
+    ///
+    /// Returns the old target of `from` and the old origin of `to`,
+    /// if they were unlinked because the relation is `EXCLUSIVE` or `EXCLUSIVE_TARGET`.
+    /// `add_relation`, `relate_to` and deferred relations don't report them.
+    pub fn try_add_relation<T: 'static>(
+        &mut self,
+        from: Entity,
+        to: Entity,
+    ) -> Result<Displaced, AddRelationError> {
+        let origin_cid = if cfg!(feature = "manual_registration") {
+            self.get_relation_id::<T>()
+        } else {
+            self.register_relation::<T>()
+        };
+
+        self.bookkeeping.try_add_relation(origin_cid, from, to)
+    }
+
     /// Checks if there is a relation between two entities.
     /// Order matters for all relations that are not `SYMMETRIC`.
     pub fn has_relation<T: 'static>(&self, from: Entity, to: Entity) -> bool {
@@ -580,9 +712,75 @@ impl World {
         let o_tid = TypeId::of::<Relation<T>>();
         self.bookkeeping.relation_pairs(o_tid)
     }
+
+    /// Lazily returns all directly related pairs
+    /// DOES NOT follow transitive relations
+    pub fn relation_pairs_iter<T: 'static>(
+        &self,
+    ) -> impl Iterator<Item = (Entity, Entity)> + use<'_, T> {
+        let origin_cid = self.get_relation_id::<T>();
+        self.bookkeeping.relation_pairs_iter(origin_cid)
+    }
+
+    /// Number of directly related targets, without collecting them
+    pub fn relation_target_count<T: 'static>(&self, from: Entity) -> usize {
+        let origin_cid = self.get_relation_id::<T>();
+        self.bookkeeping.relation_partner_count(origin_cid, from)
+    }
+
+    /// Number of directly related origins, without collecting them
+    pub fn relation_origin_count<T: 'static>(&self, to: Entity) -> usize {
+        let target_cid = self.get_relation_id::<T>().flip_target();
+        self.bookkeeping.relation_partner_count(target_cid, to)
+    }
+
+    /// Returns all targets that can be reached over one or more hops, each once.
+    /// Follows the relation even if it is not `TRANSITIVE`.
+    /// Targets more than `max_depth` hops away are skipped.
+    pub fn relation_targets_transitive<T: 'static>(
+        &self,
+        from: Entity,
+        order: TraversalOrder,
+        max_depth: Option<u32>,
+    ) -> impl Iterator<Item = Entity> + use<'_, T> {
+        let origin_cid = self.get_relation_id::<T>();
+        self.traverse(origin_cid, from, order, max_depth)
+    }
+
+    /// Returns all origins that can reach `to` over one or more hops, each once.
+    /// Follows the relation even if it is not `TRANSITIVE`.
+    /// Origins more than `max_depth` hops away are skipped.
+    pub fn relation_origins_transitive<T: 'static>(
+        &self,
+        to: Entity,
+        order: TraversalOrder,
+        max_depth: Option<u32>,
+    ) -> impl Iterator<Item = Entity> + use<'_, T> {
+        let target_cid = self.get_relation_id::<T>().flip_target();
+        self.traverse(target_cid, to, order, max_depth)
+    }
+
+    fn traverse(
+        &self,
+        relation_cid: ComponentId,
+        start: Entity,
+        order: TraversalOrder,
+        max_depth: Option<u32>,
+    ) -> impl Iterator<Item = Entity> + use<'_> {
+        let bk = &self.bookkeeping;
+        // dead entities have no relations
+        let alive = bk.entities.is_alive(start);
+        let traversal = Traversal::new(bk, relation_cid, order, max_depth);
+        let traversal = alive.then(|| traversal.from_origin(start.id));
+        traversal
+            .into_iter()
+            .flatten()
+            .map(|(id, _)| bk.entities.get_from_id(id))
+    }
 }
 
 /// Error Type for `reregister_component`.
+#[derive(Debug, PartialEq, Eq)]
 pub enum ReregisterError {
     /// The new type has a different layout than the old type.
     DifferingLayout,
@@ -0,0 +1,109 @@
+use std::ops::Add;
+
+use froql::{cached_query, component::TRANSITIVE, query, world::World};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+struct Weight(u32);
+
+impl Add for Weight {
+    type Output = Weight;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Weight(self.0 + rhs.0)
+    }
+}
+
+struct Name(&'static str);
+enum ChildOf {}
+enum Inside {}
+
+#[test]
+fn aggregate_count_children() {
+    let mut world = World::new();
+    world.register_relation::<ChildOf>();
+    let alice = world.create().add(Name("Alice")).entity;
+    let bob = world.create().add(Name("Bob")).entity;
+    world.create().add(Name("Carol"));
+    for _ in 0..3 {
+        world.create().relate_to::<ChildOf>(alice);
+    }
+    world.create().relate_to::<ChildOf>(bob);
+
+    let mut counts: Vec<(&str, usize)> = query!(world, &p, count(c), ChildOf(c, p))
+        .map(|(p, count)| (p.get::<Name>().0, count))
+        .collect();
+    counts.sort();
+    assert_eq!(vec![("Alice", 3), ("Bob", 1)], counts);
+
+    // without outvars everything is one group
+    let result: Vec<_> = query!(world, count(c), ChildOf(c, _)).collect();
+    assert_eq!(vec![(4,)], result);
+}
+
+#[test]
+fn aggregate_sum_min_max() {
+    let mut world = World::new();
+    world.register_relation::<Inside>();
+    let bag = world.create().entity;
+    let box_ = world.create().entity;
+    for w in [1, 5, 3] {
+        world.create().add(Weight(w)).relate_to::<Inside>(bag);
+    }
+    world.create().add(Weight(10)).relate_to::<Inside>(box_);
+    // not counted, has no weight
+    world.create().relate_to::<Inside>(box_);
+
+    let mut result: Vec<_> = query!(
+        world,
+        &c,
+        sum(Weight(i)),
+        min(Weight(i)),
+        max(Weight(i)),
+        count(i),
+        Inside(i, c)
+    )
+    .map(|(c, sum, min, max, count)| (c.entity, sum, min, max, count))
+    .collect();
+    result.sort_by_key(|row| row.1.0);
+    assert_eq!(
+        vec![
+            (bag, Weight(9), Weight(1), Weight(5), 3),
+            (box_, Weight(10), Weight(10), Weight(10), 1),
+        ],
+        result
+    );
+}
+
+#[test]
+fn aggregate_transitive_duplicates() {
+    let mut world = World::new();
+    world.register_relation_flags::<Inside>(TRANSITIVE);
+    let house = world.create().entity;
+    let room = world.create().relate_to::<Inside>(house).entity;
+    let chest = world
+        .create()
+        .add(Weight(2))
+        .relate_to::<Inside>(room)
+        .entity;
+    world.create().add(Weight(7)).relate_to::<Inside>(chest);
+
+    // the coin is in two containers inside of the house, so it is matched by two rows
+    // but only counted once
+    assert_eq!(
+        3,
+        query!(world, &i, Inside(i, c), Inside(c, *house)).count()
+    );
+    let result: Vec<_> = cached_query!(
+        world,
+        sum(Weight(i)),
+        count(i),
+        Inside(i, c),
+        Inside(c, *house)
+    )
+    .collect();
+    assert_eq!(vec![(Weight(9), 2)], result);
+
+    // no matches, no groups
+    let result: Vec<_> = query!(world, &c, count(i), Inside(i, c), Inside(c, *chest)).collect();
+    assert!(result.is_empty());
+}
@@ -0,0 +1,108 @@
+use froql::cached_query;
+use froql::entity_store::Entity;
+use froql::query;
+use froql::world::World;
+
+struct Health(i32);
+struct Poisoned;
+struct Flying;
+enum Attack {}
+
+// same call site for every call, so the cache is shared
+fn healthy(world: &World) -> Vec<Entity> {
+    cached_query!(world, &this, _ Health, !Poisoned)
+        .map(|(e,)| e.entity)
+        .collect()
+}
+
+#[test]
+fn cached_query_new_archetypes() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Poisoned>();
+    world.register_component::<Flying>();
+    let a = world.create().add(Health(1)).entity;
+    let b = world.create().add(Health(2)).add(Poisoned).entity;
+    assert_eq!(vec![a], healthy(&world));
+
+    // creates a new archetype after the first run
+    let c = world.create().add(Health(3)).add(Flying).entity;
+    assert_eq!(vec![a, c], healthy(&world));
+
+    world.remove_component::<Poisoned>(b);
+    let mut result = healthy(&world);
+    result.sort_by_key(|e| e.id.0);
+    assert_eq!(vec![a, b, c], result);
+
+    // the cached result matches the uncached one
+    let mut expected: Vec<Entity> = query!(world, &this, _ Health, !Poisoned)
+        .map(|(e,)| e.entity)
+        .collect();
+    expected.sort_by_key(|e| e.id.0);
+    assert_eq!(expected, result);
+}
+
+#[test]
+fn cached_query_multiple_worlds() {
+    let mut first = World::new();
+    first.register_component::<Health>();
+    first.register_component::<Poisoned>();
+    let mut second = World::new();
+    second.register_component::<Health>();
+    second.register_component::<Poisoned>();
+    second.register_component::<Flying>();
+    let a = first.create().add(Health(1)).entity;
+    second.create().add(Health(1)).add(Poisoned);
+    second.create().add(Health(1)).add(Flying).add(Poisoned);
+
+    for _ in 0..3 {
+        assert_eq!(vec![a], healthy(&first));
+        assert_eq!(0, healthy(&second).len());
+    }
+}
+
+#[test]
+fn cached_query_relation_invar() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Flying>();
+    world.register_relation::<Attack>();
+    let player = world.create().add(Health(10)).entity;
+    for round in 0..4 {
+        let attacker = world.create().add(Health(round)).entity;
+        if round % 2 == 0 {
+            world.add_component(attacker, Flying);
+        }
+        world.add_relation::<Attack>(attacker, player);
+
+        let mut damage = 0;
+        for (hp,) in cached_query!(world, Health(a), Attack(a, *player)) {
+            damage += hp.0;
+        }
+        assert_eq!((0..=round).sum::<i32>(), damage);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Gold(u64);
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Level(u8);
+
+// every instantiation shares the same cache
+fn all_of<T: Copy + 'static>(world: &World) -> Vec<T> {
+    cached_query!(world, T(this)).map(|(t,)| *t).collect()
+}
+
+#[test]
+fn cached_query_generic_fn() {
+    let mut world = World::new();
+    world.register_component::<Gold>();
+    world.register_component::<Level>();
+    world.create().add(Gold(100));
+    world.create().add(Level(3));
+
+    for _ in 0..2 {
+        assert_eq!(vec![Gold(100)], all_of::<Gold>(&world));
+        assert_eq!(vec![Level(3)], all_of::<Level>(&world));
+    }
+}
@@ -0,0 +1,8 @@
+/// the expected messages are in the `.stderr` files next to the cases,
+/// regenerate them with `TRYBUILD=overwrite`
+#[test]
+#[cfg(not(miri))]
+fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,79 @@
+use froql::{count, exists, query, world::World};
+
+struct Health(i32);
+struct Enemy;
+struct Dead;
+struct Door;
+struct Key;
+enum Opens {}
+
+#[test]
+fn count_single_var() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Enemy>();
+    world.register_component::<Dead>();
+    world.register_component::<Door>();
+    for hp in 0..5 {
+        world.create().add(Health(hp)).add(Enemy);
+    }
+    world.create().add(Health(0)).add(Enemy).add(Dead);
+    world.create().add(Health(10));
+
+    assert_eq!(7, count!(world, Health));
+    assert_eq!(5, count!(world, Enemy, !Dead));
+    assert_eq!(0, count!(world, Door));
+    assert_eq!(
+        query!(world, _ Enemy, !Dead).count(),
+        count!(world, Enemy, !Dead)
+    );
+    assert!(exists!(world, Enemy, Dead));
+    assert!(!exists!(world, Door));
+}
+
+#[test]
+fn count_does_not_borrow() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Enemy>();
+    world.register_component::<Dead>();
+    for hp in 0..5 {
+        world.create().add(Health(hp)).add(Enemy);
+    }
+    world.create().add(Health(0)).add(Enemy).add(Dead);
+
+    for (mut health,) in query!(world, mut Health, _ Enemy) {
+        health.0 += 1;
+        // would panic if the components were borrowed
+        assert_eq!(6, count!(world, Health, Enemy));
+        assert!(exists!(world, Health, Dead));
+    }
+}
+
+#[test]
+fn count_relations() {
+    let mut world = World::new();
+    world.register_component::<Door>();
+    world.register_component::<Key>();
+    world.register_relation::<Opens>();
+    let key = world.create().add(Key).entity;
+    let other_key = world.create().add(Key).entity;
+    let door = world.create().add(Door).entity;
+    world.create().add(Door);
+    world.add_relation::<Opens>(key, door);
+
+    assert!(exists!(world, Key(k), Opens(k, d), Door(d)));
+    assert!(exists!(world, Opens(*key, d), Door(d)));
+    assert!(!exists!(world, Opens(*other_key, d), Door(d)));
+    assert_eq!(1, count!(world, Door, Opens(_, this)));
+    assert_eq!(1, count!(world, Door, !Opens(_, this)));
+
+    world.add_relation::<Opens>(other_key, door);
+    assert_eq!(2, count!(world, Key(k), Opens(k, d), Door(d)));
+    assert_eq!(1, count!(world, Door, Opens(_, this)));
+    // both orders of the two keys
+    assert_eq!(
+        2,
+        count!(world, Key(a), Key(b), Opens(a, d), Opens(b, d), a != b)
+    );
+}
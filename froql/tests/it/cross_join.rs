@@ -0,0 +1,113 @@
+use froql::query_builder::QueryBuilder;
+use froql::{count, exists, query, world::World};
+use std::any::TypeId;
+use std::cell::RefCell;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pos(i32);
+struct Unit;
+struct Flying;
+struct Wall;
+enum Targets {}
+
+#[test]
+fn cross_join_pairs() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_component::<Unit>();
+    world.register_component::<Flying>();
+    world.register_component::<Wall>();
+    // spread over two archetypes, so the inner scan has to start over
+    world.create().add(Pos(1)).add(Unit);
+    world.create().add(Pos(2)).add(Unit).add(Flying);
+    world.create().add(Pos(3)).add(Unit);
+    world.create().add(Pos(4));
+
+    let mut pairs: Vec<(i32, i32)> =
+        query!(world, Pos(a), Pos(b), _ Unit(a), _ Unit(b), a != b, cross)
+            .map(|(a, b)| (a.0, b.0))
+            .collect();
+    pairs.sort();
+    assert_eq!(vec![(1, 2), (1, 3), (2, 1), (2, 3), (3, 1), (3, 2)], pairs);
+
+    assert_eq!(9, count!(world, Unit(a), Unit(b), cross));
+    assert!(exists!(world, Flying(a), Unit(b), a != b, cross));
+    assert!(!exists!(world, Wall(a), Unit(b), cross));
+}
+
+#[test]
+fn cross_join_with_relations() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_component::<Unit>();
+    world.register_component::<Wall>();
+    world.register_relation::<Targets>();
+    let wall = world.create().add(Pos(10)).add(Wall).entity;
+    let target = world.create().add(Pos(20)).entity;
+    world
+        .create()
+        .add(Pos(1))
+        .add(Unit)
+        .relate_to::<Targets>(target);
+    world.create().add(Pos(2)).add(Unit);
+
+    // the unit is unconnected to the wall, but the target is joined over the relation
+    let mut result: Vec<(i32, i32, i32)> =
+        query!(world, Pos(w), _ Wall(w), Pos(u), _ Unit(u), Targets(u, t), Pos(t), cross)
+            .map(|(w, u, t)| (w.0, u.0, t.0))
+            .collect();
+    result.sort();
+    assert_eq!(vec![(10, 1, 20)], result);
+
+    // the invar is bound first, the other variables get their own scans
+    let mut result: Vec<(i32, i32)> =
+        query!(world, _ Wall(*wall), Pos(a), Pos(b), _ Unit(a), _ Unit(b), cross)
+            .map(|(a, b)| (a.0, b.0))
+            .collect();
+    result.sort();
+    assert_eq!(vec![(1, 1), (1, 2), (2, 1), (2, 2)], result);
+}
+
+#[test]
+fn cross_join_query_builder() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_component::<Unit>();
+    world.register_component::<Flying>();
+    world.create().add(Pos(1)).add(Unit);
+    world.create().add(Pos(2)).add(Unit);
+    world.create().add(Pos(3)).add(Unit).add(Flying);
+
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    let b = builder.var();
+    builder
+        .component(a, TypeId::of::<RefCell<Flying>>())
+        .component(b, TypeId::of::<RefCell<Unit>>())
+        .unequal(a, b);
+    assert!(builder.build(&world).is_err());
+    let query = builder.cross_join().build(&world).unwrap();
+    assert_eq!(2, query.iter(&world).count());
+
+    let result = world.query_str("Unit(a), Flying(b), cross").unwrap();
+    assert_eq!(3, result.rows.len());
+}
+
+#[test]
+fn cross_join_optional_and_negated() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_component::<Unit>();
+    world.register_component::<Wall>();
+    world.create().add(Pos(1)).add(Unit);
+    world.create().add(Unit);
+    world.create().add(Wall);
+
+    // optional and negated components are fine next to a component to scan for
+    let mut result: Vec<Option<i32>> =
+        query!(world, cross, _ Wall(a), Pos(b)?, _ Unit(b), !Wall(b))
+            .map(|(pos,)| pos.map(|it| it.0))
+            .collect();
+    result.sort();
+    assert_eq!(vec![None, Some(1)], result);
+}
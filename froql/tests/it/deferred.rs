@@ -103,3 +103,30 @@ fn deferred_creation_realised_by_undeferred() {
     world.destroy(a);
     assert!(world.is_alive(e));
 }
+
+#[test]
+fn deferred_operations_on_destroyed_entities() {
+    struct Comp;
+    enum Rel {}
+
+    let mut world = World::new();
+    world.register_component::<Comp>();
+    world.register_relation::<Rel>();
+    let a = world.create().add(Comp).entity;
+    let b = world.create().relate_to::<Rel>(a).entity;
+    let c = world.create_entity();
+
+    // a is destroyed first, the operations queued after it are skipped
+    world.view_deferred(a).destroy();
+    world.view_deferred(a).remove::<Comp>();
+    world.view_deferred(b).unrelate_to::<Rel>(a);
+    world.view_deferred(c).relate_to::<Rel>(a);
+    world.process();
+
+    assert!(!world.is_alive(a));
+    assert!(world.is_alive(b));
+    assert!(world.is_alive(c));
+    assert_eq!(0, world.relation_targets::<Rel>(b).count());
+    assert_eq!(0, world.relation_targets::<Rel>(c).count());
+    assert_eq!(Ok(()), world.validate());
+}
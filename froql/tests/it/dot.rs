@@ -0,0 +1,110 @@
+use froql::{
+    component::{EXCLUSIVE, SYMMETRIC, TRANSITIVE},
+    dot::DotOptions,
+    world::World,
+};
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Name(&'static str);
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Age(u32);
+enum IsA {}
+enum ChildOf {}
+enum Friends {}
+
+#[test]
+fn export_dot_all() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_debug::<Name>();
+    world.register_component::<Age>();
+    world.register_debug::<Age>();
+    world.register_relation_flags::<IsA>(TRANSITIVE);
+    world.register_relation_flags::<ChildOf>(EXCLUSIVE);
+    world.register_relation_flags::<Friends>(SYMMETRIC);
+
+    let food = world.create().add(Name("Food")).entity;
+    let fruit = world
+        .create()
+        .add(Name("Fruit"))
+        .relate_to::<IsA>(food)
+        .entity;
+    world.create().add(Name("Tomato")).relate_to::<IsA>(fruit);
+    let anna = world.create().add(Name("Anna")).add(Age(32)).entity;
+    let otto = world
+        .create()
+        .add(Name("Otto"))
+        .relate_to::<ChildOf>(anna)
+        .entity;
+    world.create().relate_to::<Friends>(otto);
+
+    insta::assert_snapshot!(world.export_dot(&DotOptions::new()), @r##"
+    digraph froql {
+        node [shape=box];
+        e1 [label="Name(\"Food\")"];
+        e2 [label="Name(\"Fruit\")"];
+        e3 [label="Name(\"Tomato\")"];
+        e4 [label="Name(\"Anna\")\nAge(32)"];
+        e5 [label="Name(\"Otto\")"];
+        e6 [label="6"];
+        e2 -> e1 [label="IsA", color="#1f77b4", fontcolor="#1f77b4", style=dashed];
+        e3 -> e2 [label="IsA", color="#1f77b4", fontcolor="#1f77b4", style=dashed];
+        e5 -> e4 [label="ChildOf", color="#d62728", fontcolor="#d62728", arrowhead=dot];
+        e5 -> e6 [label="Friends", color="#2ca02c", fontcolor="#2ca02c", dir=none];
+    }
+    "##);
+}
+
+#[test]
+fn export_dot_filtered() {
+    let mut world = World::new();
+    world.register_component::<Age>();
+    world.register_debug::<Age>();
+    world.register_relation_flags::<ChildOf>(EXCLUSIVE);
+    world.register_relation_flags::<Friends>(SYMMETRIC);
+
+    let anna = world.create().add(Age(32)).entity;
+    let otto = world.create().relate_to::<ChildOf>(anna).entity;
+    world.create().relate_to::<Friends>(otto);
+
+    let options = DotOptions::new().relation::<ChildOf>().label::<Age>();
+    insta::assert_snapshot!(world.export_dot(&options), @r##"
+    digraph froql {
+        node [shape=box];
+        e1 [label="Age(32)"];
+        e2 [label="2"];
+        e2 -> e1 [label="ChildOf", color="#1f77b4", fontcolor="#1f77b4", arrowhead=dot];
+    }
+    "##);
+}
+
+#[test]
+fn export_dot_neighbourhood() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_debug::<Name>();
+    world.register_relation_flags::<IsA>(TRANSITIVE);
+
+    let food = world.create().add(Name("Food")).entity;
+    let fruit = world
+        .create()
+        .add(Name("Fruit"))
+        .relate_to::<IsA>(food)
+        .entity;
+    let tomato = world
+        .create()
+        .add(Name("Tomato"))
+        .relate_to::<IsA>(fruit)
+        .entity;
+    let options = DotOptions::new().neighbourhood(tomato, 1);
+    insta::assert_snapshot!(world.export_dot(&options), @r##"
+    digraph froql {
+        node [shape=box];
+        e2 [label="Name(\"Fruit\")"];
+        e3 [label="Name(\"Tomato\")"];
+        e3 -> e2 [label="IsA", color="#1f77b4", fontcolor="#1f77b4", style=dashed];
+    }
+    "##);
+}
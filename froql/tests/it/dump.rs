@@ -0,0 +1,89 @@
+use froql::{dump::TableFilter, world::World};
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Name(&'static str);
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Age(u32);
+struct Marker;
+enum ChildOf {}
+
+#[test]
+fn dump_tables_all() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_debug::<Name>();
+    world.register_component::<Age>();
+    world.register_debug::<Age>();
+    world.register_component::<Marker>();
+    world.register_relation::<ChildOf>();
+
+    let anna = world.create().add(Name("Anna")).add(Age(32)).entity;
+    world.create().add(Name("Bob")).add(Age(4)).add(Marker);
+    world.create().add(Name("Otto")).relate_to::<ChildOf>(anna);
+    world.create();
+
+    insta::assert_snapshot!(world.dump_tables(), @r#"
+    Archetype 0
+    | entity |
+    | ------ |
+    | 0      |
+    | 4      |
+
+    Archetype 3
+    | entity | Name        | Age    | Marker |
+    | ------ | ----------- | ------ | ------ |
+    | 2      | Name("Bob") | Age(4) | *      |
+
+    Archetype 4
+    | entity | Name         | ChildOf<origin> |
+    | ------ | ------------ | --------------- |
+    | 3      | Name("Otto") | [1]             |
+
+    Archetype 5
+    | entity | Name         | Age     | ChildOf<target> |
+    | ------ | ------------ | ------- | --------------- |
+    | 1      | Name("Anna") | Age(32) | [3]             |
+    "#);
+}
+
+#[test]
+fn dump_tables_filtered() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_debug::<Name>();
+    world.register_component::<Age>();
+    world.register_debug::<Age>();
+    world.register_component::<Marker>();
+    world.register_relation::<ChildOf>();
+
+    let anna = world.create().add(Name("Anna")).add(Age(32)).entity;
+    world.create().add(Name("Bob")).add(Age(4)).add(Marker);
+    world.create().add(Name("Otto")).relate_to::<ChildOf>(anna);
+
+    let filter = TableFilter::new().component::<Age>();
+    insta::assert_snapshot!(world.dump_tables_filtered(&filter), @r#"
+    Archetype 3
+    | entity | Name        | Age    | Marker |
+    | ------ | ----------- | ------ | ------ |
+    | 2      | Name("Bob") | Age(4) | *      |
+
+    Archetype 5
+    | entity | Name         | Age     | ChildOf<target> |
+    | ------ | ------------ | ------- | --------------- |
+    | 1      | Name("Anna") | Age(32) | [3]             |
+    "#);
+    let filter = TableFilter::new().relation::<ChildOf>();
+    insta::assert_snapshot!(world.dump_tables_filtered(&filter), @r#"
+    Archetype 4
+    | entity | Name         | ChildOf<origin> |
+    | ------ | ------------ | --------------- |
+    | 3      | Name("Otto") | [1]             |
+
+    Archetype 5
+    | entity | Name         | Age     | ChildOf<target> |
+    | ------ | ------------ | ------- | --------------- |
+    | 1      | Name("Anna") | Age(32) | [3]             |
+    "#);
+}
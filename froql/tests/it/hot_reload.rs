@@ -0,0 +1,98 @@
+use froql::{
+    component::EXCLUSIVE,
+    hot_reload::{RegistrationKind, ReloadRegistry},
+    world::World,
+};
+
+#[test]
+fn manifest_records_registrations() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Pos(i32, i32);
+    #[allow(dead_code)]
+    struct Name(&'static str);
+    enum ChildOf {}
+
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_debug::<Pos>();
+    world.register_component::<Name>();
+    world.register_relation_flags::<ChildOf>(EXCLUSIVE);
+    // registering twice does not add a second entry
+    world.register_component::<Name>();
+
+    let manifest = world.registration_manifest();
+    assert_eq!(3, manifest.len());
+    assert!(manifest[0].type_name.ends_with("Pos"));
+    assert_eq!(RegistrationKind::Component, manifest[0].kind);
+    assert!(manifest[0].debug);
+    assert!(manifest[1].type_name.ends_with("Name"));
+    assert!(!manifest[1].debug);
+    assert!(manifest[2].type_name.ends_with("ChildOf"));
+    assert_eq!(RegistrationKind::Relation, manifest[2].kind);
+    assert_eq!(EXCLUSIVE, manifest[2].flags);
+}
+
+#[test]
+fn reload_all_rebinds_and_reports() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Pos(i32, i32);
+    struct Name(&'static str);
+    #[allow(dead_code)]
+    struct Gone(u32);
+    enum ChildOf {}
+
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_debug::<Pos>();
+    world.register_component::<Name>();
+    world.register_component::<Gone>();
+    world.register_relation_flags::<ChildOf>(EXCLUSIVE);
+
+    let parent = world.create().add(Name("parent")).entity;
+    let a = world.create().add(Pos(1, 2)).entity;
+    let b = world.create().add(Name("b")).entity;
+    world.add_relation::<ChildOf>(a, parent);
+
+    let mut registry = ReloadRegistry::new();
+    registry
+        .component_debug::<Pos>()
+        .component::<Name>()
+        .relation::<ChildOf>();
+
+    let report = unsafe { world.reload_all(&registry) };
+    assert!(!report.is_complete());
+    assert_eq!(3, report.reloaded.len());
+    assert_eq!(1, report.missing.len());
+    assert!(report.missing[0].ends_with("Gone"));
+    assert!(report.failed.is_empty());
+    assert!(report.debug_lost.is_empty());
+    assert!(world.registration_manifest()[0].debug);
+
+    // everything still works after the reload
+    assert_eq!(1, world.get_component::<Pos>(a).0);
+    assert_eq!("b", world.get_component::<Name>(b).0);
+    world.add_relation::<ChildOf>(a, b);
+    assert!(world.has_relation::<ChildOf>(a, b));
+    assert!(!world.has_relation::<ChildOf>(a, parent));
+    let debug = format!("{:?}", world.view_mut(a));
+    assert!(debug.contains("Pos(1, 2)"), "{debug}");
+    assert!(debug.contains("ChildOf<origin> to"), "{debug}");
+}
+
+#[test]
+fn reload_all_reports_lost_debug() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Pos(i32, i32);
+
+    let mut world = World::new();
+    world.register_debug::<Pos>();
+
+    let mut registry = ReloadRegistry::new();
+    registry.component::<Pos>();
+    let report = unsafe { world.reload_all(&registry) };
+    assert_eq!(1, report.debug_lost.len());
+    assert!(!world.registration_manifest()[0].debug);
+}
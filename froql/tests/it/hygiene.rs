@@ -0,0 +1,143 @@
+// nothing from froql is imported here, the macros have to use full paths
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+struct Health(i32);
+struct Name(&'static str);
+enum Likes {}
+enum Hates {}
+
+struct Game {
+    world: froql::world::World,
+    player: froql::entity_store::Entity,
+}
+
+struct Context<'a> {
+    game: &'a mut Game,
+}
+
+impl Context<'_> {
+    fn world_mut(&mut self) -> &mut froql::world::World {
+        &mut self.game.world
+    }
+}
+
+impl Game {
+    fn new() -> Self {
+        let mut world = froql::world::World::new();
+        world.register_relation::<Likes>();
+        world.register_relation::<Hates>();
+        world.register_relation::<InSquad>();
+        let player = world.create().add(Name("Player")).add(Health(10)).entity;
+        let npc = world.create().add(Name("Npc")).add(Health(3)).entity;
+        world.add_relation::<Likes>(npc, player);
+        world.add_relation::<Hates>(player, npc);
+        Game { world, player }
+    }
+
+    fn liked_by(&self) -> Vec<&'static str> {
+        froql::query!(self.world, Name(a), Likes(a, *{ self.player }))
+            .map(|(name,)| name.0)
+            .collect()
+    }
+
+    fn player_health(&self) -> i32 {
+        froql::query!(self.world, Health(*{ self.player }))
+            .map(|(health,)| health.0)
+            .sum()
+    }
+}
+
+#[test]
+fn world_expressions() {
+    let mut game = Game::new();
+    assert_eq!(vec!["Npc"], game.liked_by());
+    assert_eq!(10, game.player_health());
+
+    let player = game.player;
+    let mut ctx = Context { game: &mut game };
+    for (mut health,) in froql::query!(ctx.world_mut(), mut Health(*player)) {
+        health.0 += 5;
+    }
+    assert_eq!(2, froql::count!(ctx.world_mut(), Health));
+    assert!(froql::exists!(
+        ctx.world_mut(),
+        Hates(*player, b),
+        Likes(b, *player)
+    ));
+    assert_eq!(15, game.player_health());
+}
+
+#[test]
+fn full_paths_in_generated_code() {
+    let game = Game::new();
+    let world = &game.world;
+
+    let names: Vec<_> = froql::query!(world, Name, order_by(Health desc))
+        .map(|(name,)| name.0)
+        .collect();
+    assert_eq!(vec!["Player", "Npc"], names);
+
+    let total: Vec<_> = froql::query!(world, count(a), _ Health(a)).collect();
+    assert_eq!(vec![(2,)], total);
+
+    let mut pairs: Vec<_> = froql::query!(world, Name(a), Name(b), (Likes | Hates)(a, b))
+        .map(|(a, b)| (a.0, b.0))
+        .collect();
+    pairs.sort();
+    assert_eq!(vec![("Npc", "Player"), ("Player", "Npc")], pairs);
+
+    let mut count = 0;
+    for _ in froql::cached_query!(world, Name(a), InSquad(a, p)?, &p) {
+        count += 1;
+    }
+    assert_eq!(2, count);
+    assert_eq!(
+        1,
+        froql::count!(world, Name(a), where(Health(a) => |h| h.0 < 5))
+    );
+}
+
+enum InSquad {}
+
+// user code in invars and `where` closures is evaluated before the generated locals are bound
+#[test]
+fn user_names_in_expressions() {
+    let game = Game::new();
+    let world = &game.world;
+    let player = game.player;
+    let npc = froql::query!(world, &a, Hates(*player, a))
+        .map(|(a,)| a.entity)
+        .next()
+        .unwrap();
+
+    let groups = [npc];
+    let counts: Vec<_> = froql::query!(world, &p, count(c), Likes(c, p), Hates(p, *{ groups[0] }))
+        .map(|(p, count)| (p.entity, count))
+        .collect();
+    assert_eq!(vec![(player, 1)], counts);
+
+    let seen = npc;
+    let found: Vec<_> = froql::query!(world, &p, (Likes | Hates)(p, *seen))
+        .map(|(p,)| p.entity)
+        .collect();
+    assert_eq!(vec![player], found);
+
+    let rows = [player];
+    let names: Vec<_> = froql::query!(world, Name(a), Likes(a, *{ rows[0] }), order_by(Health(a)))
+        .map(|(name,)| name.0)
+        .collect();
+    assert_eq!(vec!["Npc"], names);
+
+    let bk = 5;
+    assert_eq!(
+        1,
+        froql::count!(world, Name(a), where(Health(a) => |h| h.0 < bk))
+    );
+    {
+        let world = 5;
+        assert_eq!(
+            1,
+            froql::count!(game.world, Name(a), where(Health(a) => |h| h.0 > world))
+        );
+    }
+}
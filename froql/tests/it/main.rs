@@ -1,5 +1,26 @@
+mod aggregate;
+mod cached_query;
+mod compile_fail;
+mod count;
+mod cross_join;
 mod deferred;
+mod dot;
+mod dump;
 mod entity_view;
+mod hot_reload;
+mod hygiene;
+mod optional_relation;
+mod or_terms;
+mod order_by;
+mod path;
+mod predicate;
 mod proc_query;
+mod query_builder;
+mod query_str;
+#[cfg(feature = "reference_model")]
+mod reference_model;
 mod relation;
+mod type_entity;
+mod type_paths;
+mod validate;
 mod world;
@@ -0,0 +1,124 @@
+use froql::{count, query, world::World};
+
+struct Name(&'static str);
+struct Leader;
+enum InSquad {}
+enum Likes {}
+
+fn names<'a>(
+    rows: impl Iterator<Item = (&'a str, Option<&'a str>)>,
+) -> Vec<(&'a str, Option<&'a str>)> {
+    let mut rows: Vec<_> = rows.collect();
+    rows.sort();
+    rows
+}
+
+#[test]
+fn optional_relation_target() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_component::<Leader>();
+    world.register_relation::<InSquad>();
+    let alpha = world.create().add(Name("Alpha")).add(Leader).entity;
+    let beta = world.create().add(Name("Beta")).add(Leader).entity;
+    world.create().add(Name("Ann")).relate_to::<InSquad>(alpha);
+    world.create().add(Name("Bob"));
+    // in two squads at once
+    world
+        .create()
+        .add(Name("Cid"))
+        .relate_to::<InSquad>(alpha)
+        .relate_to::<InSquad>(beta);
+
+    let result = names(
+        query!(world, Name(a), !Leader(a), InSquad(a, p)?, Name(p)?)
+            .map(|(a, p)| (a.0, p.map(|p| p.0))),
+    );
+    assert_eq!(
+        vec![
+            ("Ann", Some("Alpha")),
+            ("Bob", None),
+            ("Cid", Some("Alpha")),
+            ("Cid", Some("Beta")),
+        ],
+        result
+    );
+    assert_eq!(4, count!(world, Name(a), !Leader(a), InSquad(a, p)?));
+
+    let leaders: Vec<_> = query!(world, &a, !Leader(a), InSquad(a, p)?, &p)
+        .filter_map(|(_, p)| p.map(|p| p.entity))
+        .collect();
+    assert_eq!(3, leaders.len());
+    assert!(leaders.iter().all(|it| *it == alpha || *it == beta));
+}
+
+#[test]
+fn optional_relation_origin() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_component::<Leader>();
+    world.register_relation::<InSquad>();
+    let alpha = world.create().add(Name("Alpha")).add(Leader).entity;
+    world.create().add(Name("Beta")).add(Leader);
+    world.create().add(Name("Ann")).relate_to::<InSquad>(alpha);
+    world.create().add(Name("Bob")).relate_to::<InSquad>(alpha);
+
+    // the optional variable is the origin of the relation here
+    let result = names(
+        query!(world, Name(p), _ Leader(p), InSquad(m, p)?, Name(m)?)
+            .map(|(p, m)| (p.0, m.map(|m| m.0))),
+    );
+    assert_eq!(
+        vec![
+            ("Alpha", Some("Ann")),
+            ("Alpha", Some("Bob")),
+            ("Beta", None)
+        ],
+        result
+    );
+}
+
+#[test]
+fn optional_relation_with_other_joins() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_component::<Leader>();
+    world.register_relation::<InSquad>();
+    world.register_relation::<Likes>();
+    let alpha = world.create().add(Name("Alpha")).add(Leader).entity;
+    let ann = world
+        .create()
+        .add(Name("Ann"))
+        .relate_to::<InSquad>(alpha)
+        .entity;
+    let bob = world.create().add(Name("Bob")).entity;
+    world.add_relation::<Likes>(ann, bob);
+    world.add_relation::<Likes>(bob, ann);
+    world.add_relation::<Likes>(bob, alpha);
+
+    let result = names(
+        query!(
+            world,
+            Name(a),
+            Name(b),
+            Likes(a, b),
+            InSquad(a, p)?,
+            Name(p)?
+        )
+        .map(|(a, _, p)| (a.0, p.map(|p| p.0))),
+    );
+    assert_eq!(
+        vec![("Ann", Some("Alpha")), ("Bob", None), ("Bob", None)],
+        result
+    );
+
+    // the old side may be an invar
+    let result: Vec<_> = query!(world, InSquad(*ann, p)?, Name(p)?)
+        .map(|(p,)| p.map(|p| p.0))
+        .collect();
+    assert_eq!(vec![Some("Alpha")], result);
+    let result: Vec<_> = query!(world, InSquad(*bob, p)?, Name(p)?)
+        .map(|(p,)| p.map(|p| p.0))
+        .collect();
+    assert_eq!(vec![None], result);
+}
@@ -0,0 +1,168 @@
+use froql::{cached_query, count, entity_store::Entity, exists, query, world::World};
+
+#[derive(Clone, PartialEq, PartialOrd)]
+struct Name(&'static str);
+struct Burning;
+struct Poisoned;
+struct Frozen;
+enum Owns {}
+enum Borrows {}
+
+#[test]
+fn or_components() {
+    let mut world = World::new();
+    world.register_component::<Burning>();
+    world.register_component::<Poisoned>();
+    world.register_component::<Frozen>();
+    let a = world.create().add(Name("a")).add(Burning).entity;
+    let b = world.create().add(Name("b")).add(Poisoned).entity;
+    let c = world
+        .create()
+        .add(Name("c"))
+        .add(Burning)
+        .add(Poisoned)
+        .entity;
+    world.create().add(Name("d")).add(Frozen);
+
+    let mut result: Vec<(&str, usize)> = query!(world, Name, &(Poisoned | Burning))
+        .map(|(name, branch)| (name.0, branch))
+        .collect();
+    result.sort();
+    // the first matching branch is returned
+    assert_eq!(vec![("a", 1), ("b", 0), ("c", 0)], result);
+
+    assert_eq!(3, count!(world, (Burning | Poisoned)));
+    assert_eq!(
+        1,
+        count!(world, (Burning | Poisoned), (Frozen | Poisoned), !Burning)
+    );
+    assert!(!exists!(world, (Burning | Poisoned), Frozen));
+
+    // invar
+    assert!(exists!(world, (Frozen | Burning)(*a)));
+    assert!(!exists!(world, (Frozen | Burning)(*b)));
+    for e in [a, b, c] {
+        let expected = world.has_component::<Burning>(e) || world.has_component::<Poisoned>(e);
+        assert_eq!(
+            expected,
+            cached_query!(world, _ Name(*e), (Burning | Poisoned)(*e))
+                .next()
+                .is_some()
+        );
+    }
+}
+
+#[test]
+fn or_relations() {
+    let mut world = World::new();
+    world.register_relation::<Owns>();
+    world.register_relation::<Borrows>();
+    let alice = world.create().add(Name("Alice")).entity;
+    let bob = world.create().add(Name("Bob")).entity;
+    let book = world.create().add(Name("Book")).entity;
+    let car = world.create().add(Name("Car")).entity;
+    let pen = world.create().add(Name("Pen")).entity;
+    world.add_relation::<Owns>(alice, book);
+    world.add_relation::<Borrows>(bob, book);
+    world.add_relation::<Owns>(bob, car);
+    world.add_relation::<Borrows>(bob, car);
+    world.add_relation::<Borrows>(alice, pen);
+
+    let mut result: Vec<(&str, &str, usize)> =
+        query!(world, Name(a), Name(b), &(Owns | Borrows)(a, b))
+            .map(|(a, b, branch)| (a.0, b.0, branch))
+            .collect();
+    result.sort();
+    // Bob owns and borrows the car, but the pair is only returned once
+    assert_eq!(
+        vec![
+            ("Alice", "Book", 0),
+            ("Alice", "Pen", 1),
+            ("Bob", "Book", 1),
+            ("Bob", "Car", 0),
+        ],
+        result
+    );
+
+    let mut things: Vec<Entity> = query!(world, &thing, (Owns | Borrows)(*bob, thing))
+        .map(|(thing,)| thing.entity)
+        .collect();
+    things.sort_by_key(|e| e.id.0);
+    assert_eq!(vec![book, car], things);
+    assert_eq!(4, count!(world, Name(a), (Owns | Borrows)(a, b)));
+    assert!(exists!(world, (Owns | Borrows)(*alice, *pen)));
+    assert!(!exists!(world, (Owns | Borrows)(*bob, *pen)));
+
+    // with an anyvar the OR only filters archetypes
+    assert_eq!(2, count!(world, (Owns | Borrows)(this, _)));
+    assert_eq!(3, count!(world, (Owns | Borrows)(_, this)));
+    let mut owners: Vec<(&str, usize)> = query!(world, Name, &(Owns | Borrows)(this, _))
+        .map(|(name, branch)| (name.0, branch))
+        .collect();
+    owners.sort();
+    assert_eq!(vec![("Alice", 0), ("Bob", 0)], owners);
+}
+
+#[test]
+fn or_combined() {
+    let mut world = World::new();
+    world.register_component::<Burning>();
+    world.register_component::<Poisoned>();
+    world.register_relation::<Owns>();
+    world.register_relation::<Borrows>();
+    let alice = world.create().add(Name("Alice")).entity;
+    let bob = world.create().add(Name("Bob")).add(Burning).entity;
+    let book = world.create().add(Name("Book")).add(Poisoned).entity;
+    let car = world.create().add(Name("Car")).entity;
+    world.add_relation::<Owns>(alice, book);
+    world.add_relation::<Borrows>(bob, book);
+    world.add_relation::<Owns>(bob, car);
+
+    let result: Vec<(&str, &str)> = query!(
+        world,
+        Name(a),
+        Name(b),
+        (Owns | Borrows)(a, b),
+        (Burning | Poisoned)(b),
+        order_by(Name(a))
+    )
+    .map(|(a, b)| (a.0, b.0))
+    .collect();
+    assert_eq!(vec![("Alice", "Book"), ("Bob", "Book")], result);
+
+    let mut counts: Vec<(Entity, usize)> = query!(world, &a, count(b), (Owns | Borrows)(a, b))
+        .map(|(a, count)| (a.entity, count))
+        .collect();
+    counts.sort_by_key(|(e, _)| e.id.0);
+    assert_eq!(vec![(alice, 1), (bob, 2)], counts);
+}
+
+#[test]
+fn or_relations_capture_once() {
+    let mut world = World::new();
+    world.register_relation::<Owns>();
+    world.register_relation::<Borrows>();
+    let bob = world.create().add(Name("Bob")).entity;
+    let book = world.create().add(Name("Book")).entity;
+    let car = world.create().add(Name("Car")).entity;
+    world.add_relation::<Borrows>(bob, book);
+    world.add_relation::<Owns>(bob, car);
+
+    // every branch of the OR uses the same invar and predicate
+    let calls = std::cell::Cell::new(0);
+    let owner = || {
+        calls.set(calls.get() + 1);
+        bob
+    };
+    let banned = String::from("Car");
+    let result: Vec<&str> = query!(
+        world,
+        Name(b),
+        (Owns | Borrows)(*{ owner() }, b),
+        where(Name(b) => move |name| name.0 != banned)
+    )
+    .map(|(b,)| b.0)
+    .collect();
+    assert_eq!(vec!["Book"], result);
+    assert_eq!(1, calls.get());
+}
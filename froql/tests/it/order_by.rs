@@ -0,0 +1,92 @@
+use froql::{cached_query, entity_store::Entity, query, world::World};
+
+struct Name(&'static str);
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+struct Initiative(i32);
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+struct Depth(f32);
+struct Stunned;
+enum Targets {}
+
+#[test]
+fn order_by_desc() {
+    let mut world = World::new();
+    world.register_component::<Stunned>();
+    world.create().add(Name("Goblin")).add(Initiative(3));
+    world
+        .create()
+        .add(Name("Orc"))
+        .add(Initiative(1))
+        .add(Stunned);
+    world.create().add(Name("Hero")).add(Initiative(7));
+    world.create().add(Name("Rat")).add(Initiative(3));
+
+    let names: Vec<&str> = query!(world, Name, order_by(Initiative desc))
+        .map(|(name,)| name.0)
+        .collect();
+    // Goblin and Rat have the same initiative, Goblin was created first
+    assert_eq!(vec!["Hero", "Goblin", "Rat", "Orc"], names);
+
+    let names: Vec<&str> = cached_query!(world, Name, Initiative, order_by(Initiative asc))
+        .map(|(name, _)| name.0)
+        .collect();
+    assert_eq!(vec!["Orc", "Goblin", "Rat", "Hero"], names);
+}
+
+#[test]
+fn order_by_stable_over_archetype_changes() {
+    let mut world = World::new();
+    world.register_component::<Stunned>();
+    world.create().add(Name("Goblin")).add(Initiative(3));
+    world
+        .create()
+        .add(Name("Orc"))
+        .add(Initiative(1))
+        .add(Stunned);
+    world.create().add(Name("Hero")).add(Initiative(7));
+    world.create().add(Name("Rat")).add(Initiative(3));
+
+    let before: Vec<Entity> = query!(world, &this, _ Name, order_by(Initiative))
+        .map(|(e,)| e.entity)
+        .collect();
+
+    // moves entities into other archetypes
+    let (first, last) = (before[0], before[3]);
+    world.remove_component::<Stunned>(first);
+    world.add_component(last, Stunned);
+
+    let after: Vec<Entity> = query!(world, &this, _ Name, order_by(Initiative))
+        .map(|(e,)| e.entity)
+        .collect();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn order_by_multiple_keys() {
+    let mut world = World::new();
+    world.register_relation::<Targets>();
+    let hero = world.create().add(Name("Hero")).entity;
+    let villain = world.create().add(Name("Villain")).entity;
+    for (init, depth, target) in [(2, 0.5, hero), (2, 0.1, villain), (1, 0.9, hero)] {
+        world
+            .create()
+            .add(Initiative(init))
+            .add(Depth(depth))
+            .relate_to::<Targets>(target);
+    }
+
+    // mutable access happens while iterating, not while sorting
+    let mut order = Vec::new();
+    for (mut init, target) in query!(
+        world,
+        mut Initiative(a),
+        Name(b),
+        Targets(a, b),
+        order_by(Initiative(a) desc),
+        order_by(Depth(a))
+    ) {
+        init.0 *= 10;
+        order.push((init.0, target.0));
+    }
+    assert_eq!(vec![(20, "Villain"), (20, "Hero"), (10, "Hero")], order);
+}
@@ -0,0 +1,184 @@
+use froql::{component::TRANSITIVE, count, entity_store::Entity, exists, query, world::World};
+
+struct Name(&'static str);
+enum ChildOf {}
+
+/// root <- a <- b <- c
+///   ^
+///   '---- d
+fn tree() -> (World, Entity) {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation::<ChildOf>();
+    let root = world.create().add(Name("root")).entity;
+    let a = world
+        .create()
+        .add(Name("a"))
+        .relate_to::<ChildOf>(root)
+        .entity;
+    let b = world.create().add(Name("b")).relate_to::<ChildOf>(a).entity;
+    world.create().add(Name("c")).relate_to::<ChildOf>(b);
+    world.create().add(Name("d")).relate_to::<ChildOf>(root);
+    (world, root)
+}
+
+fn sorted<'a>(names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut names: Vec<&str> = names.collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn path_operators() {
+    let (world, root) = tree();
+
+    let descendants = sorted(query!(world, Name(x), ChildOf + (x, *root)).map(|(n,)| n.0));
+    assert_eq!(vec!["a", "b", "c", "d"], descendants);
+
+    // zero hops lead back to the entity itself, even without the relation component
+    let with_self = sorted(query!(world, Name(x), ChildOf * (x, *root)).map(|(n,)| n.0));
+    assert_eq!(vec!["a", "b", "c", "d", "root"], with_self);
+
+    let grandchildren = sorted(query!(world, Name(x), ChildOf{2}(x, *root)).map(|(n,)| n.0));
+    assert_eq!(vec!["b"], grandchildren);
+
+    let near = sorted(query!(world, Name(x), ChildOf{1, 2}(x, *root)).map(|(n,)| n.0));
+    assert_eq!(vec!["a", "b", "d"], near);
+
+    let deep = sorted(query!(world, Name(x), ChildOf{2,}(x, *root)).map(|(n,)| n.0));
+    assert_eq!(vec!["b", "c"], deep);
+
+    assert_eq!(4, count!(world, ChildOf + (x, *root)));
+    assert!(exists!(world, ChildOf{3}(x, *root)));
+    assert!(!exists!(world, ChildOf{4,}(x, *root)));
+}
+
+#[test]
+fn path_join() {
+    let (world, _) = tree();
+
+    // every ancestor of every entity, starting from either side
+    let mut pairs: Vec<(&str, &str)> = query!(world, Name(x), Name(y), ChildOf + (x, y))
+        .map(|(x, y)| (x.0, y.0))
+        .collect();
+    pairs.sort();
+    assert_eq!(
+        vec![
+            ("a", "root"),
+            ("b", "a"),
+            ("b", "root"),
+            ("c", "a"),
+            ("c", "b"),
+            ("c", "root"),
+            ("d", "root"),
+        ],
+        pairs
+    );
+    let mut ancestors: Vec<(&str, &str)> = query!(world, Name(y), ChildOf * (x, y), Name(x))
+        .map(|(y, x)| (x.0, y.0))
+        .filter(|(x, _)| *x == "c")
+        .collect();
+    ancestors.sort();
+    assert_eq!(
+        vec![("c", "a"), ("c", "b"), ("c", "c"), ("c", "root")],
+        ancestors
+    );
+
+    // the path is checked when both sides are already bound
+    let mut grandparents: Vec<(&str, &str)> =
+        query!(world, Name(x), Name(g), ChildOf(x, p), ChildOf(p, g), ChildOf{2}(x, g))
+            .map(|(x, g)| (x.0, g.0))
+            .collect();
+    grandparents.sort();
+    assert_eq!(vec![("b", "root"), ("c", "a")], grandparents);
+}
+
+#[test]
+fn path_hops() {
+    let (world, root) = tree();
+
+    let mut depths: Vec<(&str, usize)> =
+        query!(world, Name(x), ChildOf * (x, *root), hops(x, root))
+            .map(|(name, hops)| (name.0, hops))
+            .collect();
+    depths.sort();
+    assert_eq!(
+        vec![("a", 1), ("b", 2), ("c", 3), ("d", 1), ("root", 0)],
+        depths
+    );
+
+    // the order of the variables doesn't matter
+    let mut depths: Vec<(usize, &str)> =
+        query!(world, hops(y, x), ChildOf + (x, y), Name(x), Name(y))
+            .filter(|(_, _, y)| y.0 == "a")
+            .map(|(hops, x, _)| (hops, x.0))
+            .collect();
+    depths.sort();
+    assert_eq!(vec![(1, "b"), (2, "c")], depths);
+}
+
+#[test]
+fn path_cycle() {
+    enum Follows {}
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation::<Follows>();
+    let a = world.create().add(Name("a")).entity;
+    let b = world
+        .create()
+        .add(Name("b"))
+        .relate_from::<Follows>(a)
+        .entity;
+    let c = world
+        .create()
+        .add(Name("c"))
+        .relate_from::<Follows>(b)
+        .entity;
+    world.add_relation::<Follows>(c, a);
+    world.create().add(Name("d")).relate_to::<Follows>(a);
+
+    // everything in the cycle reaches itself, the shortest way around takes 3 hops
+    let mut cycle: Vec<(&str, &str, usize)> =
+        query!(world, Name(x), Name(y), Follows + (x, y), hops(x, y))
+            .filter(|(x, y, _)| x.0 == y.0)
+            .map(|(x, y, hops)| (x.0, y.0, hops))
+            .collect();
+    cycle.sort();
+    assert_eq!(vec![("a", "a", 3), ("b", "b", 3), ("c", "c", 3)], cycle);
+
+    // the entity itself is 0 hops away, even though the cycle leads back to it
+    let mut around: Vec<(&str, usize)> = query!(world, Name(x), Follows * (x, *a), hops(x, a))
+        .map(|(x, hops)| (x.0, hops))
+        .collect();
+    around.sort();
+    assert_eq!(vec![("a", 0), ("b", 2), ("c", 1), ("d", 1)], around);
+
+    let reached = sorted(query!(world, Name(x), Follows + (*a, x)).map(|(n,)| n.0));
+    assert_eq!(vec!["a", "b", "c"], reached);
+    let reached = sorted(query!(world, Name(x), Follows{4,5}(*a, x)).map(|(n,)| n.0));
+    assert_eq!(vec!["b", "c"], reached);
+}
+
+#[test]
+fn path_transitive_bounds() {
+    enum Contains {}
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation_flags::<Contains>(TRANSITIVE);
+    let house = world.create().add(Name("house")).entity;
+    let room = world
+        .create()
+        .add(Name("room"))
+        .relate_from::<Contains>(house)
+        .entity;
+    world
+        .create()
+        .add(Name("box"))
+        .relate_from::<Contains>(room);
+
+    // the relation is transitive, but the bounds of the term are used
+    let direct = sorted(query!(world, Name(x), Contains{1}(*house, x)).map(|(n,)| n.0));
+    assert_eq!(vec!["room"], direct);
+    let all = sorted(query!(world, Name(x), Contains(*house, x)).map(|(n,)| n.0));
+    assert_eq!(vec!["box", "room"], all);
+}
@@ -0,0 +1,83 @@
+use froql::{count, exists, query, world::World};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+struct Health(i32);
+struct Name(&'static str);
+enum Attacks {}
+
+fn is_low(health: &Health) -> bool {
+    health.0 < 10
+}
+
+#[test]
+fn predicate_single_var() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Name>();
+    world.create().add(Name("Ann")).add(Health(5));
+    world.create().add(Name("Bob")).add(Health(50));
+    world.create().add(Name("Cid")).add(Health(8));
+    world.create().add(Health(1));
+
+    let mut result: Vec<&str> = query!(world, Name(a), where(Health(a) => |h| h.0 < 10))
+        .map(|(name,)| name.0)
+        .collect();
+    result.sort();
+    assert_eq!(vec!["Ann", "Cid"], result);
+
+    // the component can still be in the result, even mutably
+    for (mut health,) in query!(world, mut Health(a), where(Health(a) => is_low)) {
+        health.0 += 100;
+    }
+    let mut result: Vec<i32> = query!(world, Health(a)).map(|(h,)| h.0).collect();
+    result.sort();
+    assert_eq!(vec![50, 101, 105, 108], result);
+
+    assert_eq!(1, count!(world, where(Health(a) => |h| h.0 == 50)));
+    assert!(!exists!(world, where(Health(a) => is_low)));
+}
+
+#[test]
+fn predicate_joined_vars() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_component::<Name>();
+    world.register_relation::<Attacks>();
+    let ann = world.create().add(Name("Ann")).add(Health(5)).entity;
+    let bob = world.create().add(Name("Bob")).add(Health(50)).entity;
+    let cid = world.create().add(Name("Cid")).add(Health(20)).entity;
+    world.add_relation::<Attacks>(ann, bob);
+    world.add_relation::<Attacks>(bob, ann);
+    world.add_relation::<Attacks>(cid, ann);
+    world.add_relation::<Attacks>(cid, bob);
+
+    // strong attackers against weak targets
+    let mut result: Vec<(&str, &str)> = query!(
+        world,
+        Name(a),
+        Name(b),
+        Attacks(a, b),
+        where(Health(a) => |h| h.0 >= 20),
+        where(Health(b) => is_low)
+    )
+    .map(|(a, b)| (a.0, b.0))
+    .collect();
+    result.sort();
+    assert_eq!(vec![("Bob", "Ann"), ("Cid", "Ann")], result);
+
+    // predicates on invars
+    assert_eq!(
+        2,
+        count!(world, Attacks(*cid, b), where(Health(*cid) => |h| h.0 == 20))
+    );
+    assert_eq!(
+        0,
+        count!(world, Attacks(*ann, b), where(Health(*ann) => |h| h.0 == 20))
+    );
+    let threshold = 30;
+    let result: Vec<&str> =
+        query!(world, Name(b), Attacks(*cid, b), where(Health(b) => |h| h.0 > threshold))
+            .map(|(b,)| b.0)
+            .collect();
+    assert_eq!(vec!["Bob"], result);
+}
@@ -491,6 +491,28 @@ fn proc_query_relation_constraint_invar() {
     assert_eq!(1, counter);
 }
 
+#[test]
+fn proc_query_relation_two_invars() {
+    enum Rel {}
+    let mut world = World::new();
+    world.register_relation::<Rel>();
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let both = world
+        .create()
+        .relate_to::<Rel>(a)
+        .relate_to::<Rel>(b)
+        .entity;
+    world.create().relate_to::<Rel>(a);
+    world.create().relate_to::<Rel>(b);
+
+    // the second relation constrains x, it must not replace the one x is joined over
+    let found: Vec<Entity> = query!(world, &x, Rel(x, *a), Rel(x, *b))
+        .map(|(x,)| *x)
+        .collect();
+    assert_eq!(vec![both], found);
+}
+
 #[test]
 fn proc_query_relation_transitive() {
     enum Rel {}
@@ -687,3 +709,29 @@ fn proc_query_singleton() {
     assert_eq!(6, world.singleton::<Accum>().0);
     assert_eq!(counter, 3);
 }
+
+#[test]
+fn proc_query_relation_fan_out() {
+    enum Likes {}
+    enum Hates {}
+    struct Pos(i32);
+
+    let mut world = World::new();
+    world.register_relation::<Likes>();
+    world.register_relation::<Hates>();
+    let a = world.create().add(Pos(0)).entity;
+    for i in 1..3 {
+        world.create().add(Pos(i)).relate_from::<Likes>(a);
+    }
+    for i in 3..5 {
+        world.create().add(Pos(i)).relate_from::<Hates>(a);
+    }
+
+    // the second join follows a relation of a variable that was bound two steps before
+    let mut result: Vec<(i32, i32)> =
+        query!(world, _ Pos(a), Pos(b), Pos(c), Likes(a, b), Hates(a, c))
+            .map(|(b, c)| (b.0, c.0))
+            .collect();
+    result.sort();
+    assert_eq!(vec![(1, 3), (1, 4), (2, 3), (2, 4)], result);
+}
@@ -0,0 +1,293 @@
+use std::{any::TypeId, cell::RefCell};
+
+use froql::{
+    component::TRANSITIVE,
+    entity_store::Entity,
+    query,
+    query_builder::{QueryBuilder, QueryError, Var},
+    relation::Relation,
+    world::World,
+};
+
+struct Health(i32);
+struct Tag;
+enum Attack {}
+enum Inside {}
+
+fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+    entities.sort_by_key(|e| e.id.0);
+    entities
+}
+
+#[test]
+fn query_builder_components() {
+    let mut world = World::new();
+    let health = world.register_component::<Health>();
+    world.create().add(Health(1));
+    world.create().add(Health(2)).add(Tag);
+    world.create().add(Tag);
+
+    let mut builder = QueryBuilder::new();
+    let this = builder.var();
+    builder.component(this, health);
+    builder.uncomponent(this, TypeId::of::<RefCell<Tag>>());
+    let query = builder.build(&world).unwrap();
+    let rows: Vec<_> = query.iter(&world).collect();
+    assert_eq!(1, rows.len());
+    let hp = unsafe { &*(rows[0].column(0) as *const RefCell<Health>) };
+    assert_eq!(1, hp.borrow().0);
+
+    // mutation through the column pointer
+    for row in query.iter(&world) {
+        let hp = unsafe { &*(row.column(0) as *const RefCell<Health>) };
+        hp.borrow_mut().0 += 10;
+    }
+    for (hp,) in query!(world, Health, !Tag) {
+        assert_eq!(11, hp.0);
+    }
+}
+
+#[test]
+fn query_builder_matches_macro() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    world.register_relation::<Attack>();
+    let units: Vec<Entity> = (0..5)
+        .map(|i| world.create().add(Health(i)).entity)
+        .collect();
+    world.add_relation::<Attack>(units[0], units[1]);
+    world.add_relation::<Attack>(units[0], units[2]);
+    world.add_relation::<Attack>(units[2], units[2]);
+    world.add_relation::<Attack>(units[3], units[1]);
+
+    let expected: Vec<(Entity, Entity)> =
+        query!(world, &a, &b, _ Health(a), _ Health(b), Attack(a, b), a != b)
+            .map(|(a, b)| (a.entity, b.entity))
+            .collect();
+
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    let b = builder.var();
+    builder
+        .component(a, TypeId::of::<RefCell<Health>>())
+        .component(b, TypeId::of::<RefCell<Health>>())
+        .relation(TypeId::of::<Relation<Attack>>(), a, b)
+        .unequal(a, b);
+    let query = builder.build(&world).unwrap();
+    let actual: Vec<(Entity, Entity)> = query
+        .iter(&world)
+        .map(|row| (row.entity(a), row.entity(b)))
+        .collect();
+    assert_eq!(3, actual.len());
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn query_builder_anyvar_and_unrelation() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let attack = world.register_relation::<Attack>();
+    let a = world.create().add(Health(0)).entity;
+    let b = world.create().add(Health(1)).entity;
+    let c = world.create().add(Health(2)).entity;
+    world.add_relation::<Attack>(a, b);
+    world.add_relation::<Attack>(b, c);
+
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    builder.relation(attack, Var::ANY, x);
+    let query = builder.build(&world).unwrap();
+    let actual = sorted(query.iter(&world).map(|row| row.entity(x)).collect());
+    assert_eq!(vec![b, c], actual);
+
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    builder
+        .component(x, TypeId::of::<RefCell<Health>>())
+        .unrelation(attack, x, Var::ANY);
+    let query = builder.build(&world).unwrap();
+    let actual: Vec<Entity> = query.iter(&world).map(|row| row.entity(x)).collect();
+    assert_eq!(vec![c], actual);
+
+    // a attacks b, but nobody attacks a
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    let y = builder.var();
+    builder
+        .relation(attack, x, y)
+        .unrelation(attack, Var::ANY, x);
+    let query = builder.build(&world).unwrap();
+    let actual: Vec<(Entity, Entity)> = query
+        .iter(&world)
+        .map(|row| (row.entity(x), row.entity(y)))
+        .collect();
+    assert_eq!(vec![(a, b)], actual);
+
+    // nobody attacks back
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    let y = builder.var();
+    builder
+        .relation(attack, x, y)
+        .component(y, TypeId::of::<RefCell<Health>>())
+        .unrelation(attack, y, x);
+    let query = builder.build(&world).unwrap();
+    let actual: Vec<(Entity, Entity)> = query
+        .iter(&world)
+        .map(|row| (row.entity(x), row.entity(y)))
+        .collect();
+    assert_eq!(2, actual.len());
+}
+
+#[test]
+fn query_builder_invar_transitive() {
+    let mut world = World::new();
+    world.register_component::<Tag>();
+    world.register_relation_flags::<Inside>(TRANSITIVE);
+    let house = world.create().entity;
+    let room = world.create().entity;
+    let chest = world.create().add(Tag).entity;
+    let coin = world.create().add(Tag).entity;
+    world.add_relation::<Inside>(room, house);
+    world.add_relation::<Inside>(chest, room);
+    world.add_relation::<Inside>(coin, chest);
+
+    let expected = sorted(
+        query!(world, &this, _ Tag(this), Inside(this, *house))
+            .map(|(this,)| this.entity)
+            .collect(),
+    );
+
+    let mut builder = QueryBuilder::new();
+    let this = builder.var();
+    let container = builder.invar(house);
+    builder
+        .component(this, TypeId::of::<RefCell<Tag>>())
+        .relation(TypeId::of::<Relation<Inside>>(), this, container);
+    let query = builder.build(&world).unwrap();
+    let actual = sorted(query.iter(&world).map(|row| row.entity(this)).collect());
+    assert_eq!(vec![chest, coin], actual);
+    assert_eq!(expected, actual);
+
+    // the query reflects later changes to the world
+    world.destroy(coin);
+    let actual: Vec<Entity> = query.iter(&world).map(|row| row.entity(this)).collect();
+    assert_eq!(vec![chest], actual);
+
+    // a dead invar produces no rows
+    world.destroy(house);
+    assert_eq!(0, query.iter(&world).count());
+}
+
+#[test]
+fn query_builder_two_invars() {
+    let mut world = World::new();
+    world.register_relation::<Attack>();
+    let a = world.create().entity;
+    let b = world.create().entity;
+    let both = world
+        .create()
+        .relate_to::<Attack>(a)
+        .relate_to::<Attack>(b)
+        .entity;
+    world.create().relate_to::<Attack>(a);
+    world.create().relate_to::<Attack>(b);
+
+    // x is joined over one invar and checked against the other
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    let (inv_a, inv_b) = (builder.invar(a), builder.invar(b));
+    builder
+        .relation(TypeId::of::<Relation<Attack>>(), x, inv_a)
+        .relation(TypeId::of::<Relation<Attack>>(), x, inv_b);
+    let query = builder.build(&world).unwrap();
+    let actual: Vec<Entity> = query.iter(&world).map(|row| row.entity(x)).collect();
+    assert_eq!(vec![both], actual);
+}
+
+#[test]
+fn query_builder_fan_out() {
+    let mut world = World::new();
+    let attack = world.register_relation::<Attack>();
+    let a = world.create().entity;
+    let b = world.create().entity;
+    let c = world.create().entity;
+    world.add_relation::<Attack>(a, b);
+    world.add_relation::<Attack>(a, c);
+
+    // both joins start from x, so the second helper has to be rewound
+    let mut builder = QueryBuilder::new();
+    let x = builder.var();
+    let y = builder.var();
+    let z = builder.var();
+    builder.relation(attack, x, y).relation(attack, x, z);
+    let query = builder.build(&world).unwrap();
+    let mut actual: Vec<(Entity, Entity)> = query
+        .iter(&world)
+        .map(|row| (row.entity(y), row.entity(z)))
+        .collect();
+    actual.sort_by_key(|(y, z)| (y.id.0, z.id.0));
+    assert_eq!(vec![(b, b), (b, c), (c, b), (c, c)], actual);
+}
+
+#[test]
+fn query_builder_errors() {
+    let mut world = World::new();
+    world.register_component::<Health>();
+    let attack = world.register_relation::<Attack>();
+
+    let builder = QueryBuilder::new();
+    assert_eq!(Some(QueryError::NoVariables), builder.build(&world).err());
+
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    builder.component(a, TypeId::of::<RefCell<Tag>>());
+    assert_eq!(
+        Some(QueryError::UnknownType(TypeId::of::<RefCell<Tag>>())),
+        builder.build(&world).err()
+    );
+
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    builder.component(a, attack);
+    assert!(matches!(
+        builder.build(&world),
+        Err(QueryError::KindMismatch(_))
+    ));
+
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    let b = builder.var();
+    builder
+        .component(a, TypeId::of::<RefCell<Health>>())
+        .component(b, TypeId::of::<RefCell<Health>>());
+    assert_eq!(Some(QueryError::CrossJoin(a)), builder.build(&world).err());
+
+    let mut builder = QueryBuilder::new();
+    builder.component(Var::ANY, TypeId::of::<RefCell<Health>>());
+    builder.var();
+    assert_eq!(
+        Some(QueryError::UnknownVariable(Var::ANY)),
+        builder.build(&world).err()
+    );
+
+    let mut builder = QueryBuilder::new();
+    builder.relation(attack, Var::ANY, Var::ANY);
+    assert_eq!(
+        Some(QueryError::AnyOnBothSides),
+        builder.build(&world).err()
+    );
+
+    // a variable without terms would match every entity, including internal ones
+    let mut builder = QueryBuilder::new();
+    let a = builder.var();
+    let b = builder.var();
+    builder
+        .component(a, TypeId::of::<RefCell<Health>>())
+        .uncomponent(b, TypeId::of::<RefCell<Health>>())
+        .cross_join();
+    assert_eq!(
+        Some(QueryError::UnconstrainedVariable(b)),
+        builder.build(&world).err()
+    );
+}
@@ -0,0 +1,160 @@
+use froql::{query_str::QueryStrError, world::World};
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Name(&'static str);
+struct Dead;
+enum IsA {}
+
+#[test]
+fn query_str_simple() {
+    let mut world = World::new();
+    world.register_debug::<Name>();
+    world.register_component::<Dead>();
+    world.register_relation::<IsA>();
+    let animal = world.create().add(Name("Animal")).entity;
+    let cat = world
+        .create()
+        .add(Name("Cat"))
+        .relate_to::<IsA>(animal)
+        .entity;
+    world.create().add(Name("Tom")).relate_to::<IsA>(cat);
+    world
+        .create()
+        .add(Name("Garfield"))
+        .add(Dead)
+        .relate_to::<IsA>(cat);
+
+    let result = world.query_str("Name(a), IsA(a, b), !Dead(a)").unwrap();
+    assert_eq!(vec!["a", "b"], result.variables);
+    assert_eq!(vec!["Name(a)"], result.columns);
+    let mut names: Vec<&str> = result
+        .rows
+        .iter()
+        .map(|row| row.components[0].as_str())
+        .collect();
+    names.sort();
+    insta::assert_debug_snapshot!(names, @r#"
+    [
+        "Name(\"Cat\")",
+        "Name(\"Tom\")",
+    ]
+    "#);
+}
+
+#[test]
+fn query_str_terms() {
+    let mut world = World::new();
+    world.register_debug::<Name>();
+    world.register_component::<Dead>();
+    world.register_relation::<IsA>();
+    let animal = world.create().add(Name("Animal")).entity;
+    let cat = world
+        .create()
+        .add(Name("Cat"))
+        .relate_to::<IsA>(animal)
+        .entity;
+    world.create().add(Name("Tom")).relate_to::<IsA>(cat);
+    world
+        .create()
+        .add(Name("Garfield"))
+        .add(Dead)
+        .relate_to::<IsA>(cat);
+
+    // default variable, full path, no output, anyvar, unequal
+    let path = std::any::type_name::<Name>();
+    let query =
+        format!("{path}, _ Name(other), IsA(this, c), IsA(other, c), IsA(c, _), this != other");
+    let result = world.query_str(&query).unwrap();
+    assert_eq!(vec!["this", "other", "c"], result.variables);
+    assert_eq!(vec![format!("{path}(this)")], result.columns);
+    // Tom and Garfield are both cats
+    assert_eq!(2, result.rows.len());
+
+    // components without debug formatter are shown by name
+    let result = world.query_str("Dead, &this").unwrap();
+    assert_eq!(vec!["Dead { .. }"], result.rows[0].components);
+}
+
+#[test]
+fn query_str_errors() {
+    let mut world = World::new();
+    world.register_debug::<Name>();
+    world.register_component::<Dead>();
+    world.register_relation::<IsA>();
+
+    let err = |query: &str| world.query_str(query).unwrap_err();
+
+    assert_eq!(
+        QueryStrError {
+            start: 9,
+            end: 17,
+            message: "Component 'Alive' is not registered.".to_string()
+        },
+        err("Name(a), Alive(a)")
+    );
+    assert_eq!(
+        "Relation 'Name' is not registered.",
+        err("Name(a, b)").message
+    );
+    let e = err("Name(a), IsA(a, *b)");
+    assert_eq!((9, 19), (e.start, e.end));
+    let e = err("Name(a), Name(b c)");
+    assert_eq!((9, 18), (e.start, e.end));
+    let e = err("Name(a), Name(b))");
+    assert_eq!((16, 17), (e.start, e.end));
+    // errors of the QueryBuilder point at the first use of the variable
+    let e = err("Name(a), Name(b)");
+    assert_eq!(
+        "Variable `a` is not connected to the rest of the query by a relation. Cross joins are not supported, add `cross` to the query.",
+        e.message
+    );
+    assert_eq!((0, 7), (e.start, e.end));
+    assert_eq!(
+        "Path terms are not supported in text queries.",
+        err("Name(a), IsA+(a, b)").message
+    );
+    assert_eq!(
+        "Variable `a` needs a Component or Relation term that is not negated.",
+        err("&a").message
+    );
+    let e = err("Name(a), !Dead(b), cross");
+    assert_eq!(
+        "Variable `b` needs a Component or Relation term that is not negated.",
+        e.message
+    );
+    assert_eq!((9, 17), (e.start, e.end));
+    assert_eq!(
+        "IsA(_, _) does not constrain any variable, at least one side has to be a variable.",
+        err("IsA(_, _)").message
+    );
+}
+
+#[test]
+fn query_str_type_entity() {
+    let mut world = World::new();
+    world.register_debug::<Name>();
+    world.register_component::<Dead>();
+    world.register_relation::<IsA>();
+
+    assert_eq!(
+        0,
+        world
+            .query_str("Name(a), IsA(a, @Dead)")
+            .unwrap()
+            .rows
+            .len()
+    );
+
+    let dead = world.type_entity::<Dead>();
+    world.create().add(Name("Zombie")).relate_to::<IsA>(dead);
+    let result = world.query_str("Name(a), IsA(a, @Dead)").unwrap();
+    assert_eq!(vec!["a", "@Dead"], result.variables);
+    assert_eq!(vec![r#"Name("Zombie")"#], result.rows[0].components);
+    assert_eq!(dead, result.rows[0].entities[1]);
+
+    assert_eq!(
+        "Component 'Alive' is not registered.",
+        world.query_str("IsA(a, @Alive)").unwrap_err().message
+    );
+}
@@ -0,0 +1,10 @@
+use froql::reference_model::driver::run;
+
+#[test]
+fn reference_model_random_operations() {
+    for seed in 0..20 {
+        if let Err(mismatch) = run(seed, 200) {
+            panic!("{mismatch:#?}");
+        }
+    }
+}
@@ -1,7 +1,12 @@
 use froql::{
-    component::{CASCADING_DESTRUCT, EXCLUSIVE, SYMMETRIC, TRANSITIVE},
+    component::{
+        ACYCLIC, CASCADING_DESTRUCT, CleanupPolicy, EXCLUSIVE, EXCLUSIVE_TARGET, ONE_TO_ONE,
+        PANIC_ON_ORIGIN_DESTRUCT, PANIC_ON_TARGET_DESTRUCT, SYMMETRIC, TARGET_CASCADING_DESTRUCT,
+        TRANSITIVE,
+    },
     entity_store::Entity,
-    world::World,
+    traversal::TraversalOrder,
+    world::{AddRelationError, Displaced, World},
 };
 
 #[test]
@@ -74,6 +79,22 @@ fn relation_entity_destroy() {
     assert!(!world.has_relation::<Rel>(a, b));
 }
 
+#[test]
+fn relation_destroy_self_related() {
+    enum Rel {}
+
+    let mut world = World::new();
+    world.register_relation::<Rel>();
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.add_relation::<Rel>(a, a);
+    world.add_relation::<Rel>(b, a);
+    world.destroy(a);
+    assert!(!world.is_alive(a));
+    assert_eq!(0, world.relation_targets::<Rel>(b).count());
+    assert_eq!(Ok(()), world.validate());
+}
+
 #[test]
 fn relation_exlusive() {
     enum Rel {}
@@ -91,6 +112,115 @@ fn relation_exlusive() {
     world.add_relation::<Rel>(a, c);
     assert!(world.has_relation::<Rel>(a, c));
     assert!(!world.has_relation::<Rel>(a, b));
+    // the old target must not remember the origin
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn relation_exclusive_target() {
+    enum Equips {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<Equips>(EXCLUSIVE_TARGET);
+    let left = world.create_entity();
+    let right = world.create_entity();
+    let sword = world.create_entity();
+    let shield = world.create_entity();
+    world.add_relation::<Equips>(left, sword);
+    world.add_relation::<Equips>(left, shield);
+    world.add_relation::<Equips>(right, sword);
+    assert!(world.has_relation::<Equips>(right, sword));
+    assert!(!world.has_relation::<Equips>(left, sword));
+    // the origin side is not exclusive
+    assert!(world.has_relation::<Equips>(left, shield));
+    assert_eq!(1, world.relation_origin_count::<Equips>(sword));
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn relation_one_to_one() {
+    enum Possesses {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<Possesses>(ONE_TO_ONE);
+    let player = world.create_entity();
+    let ghost = world.create_entity();
+    let body = world.create_entity();
+    let other_body = world.create_entity();
+    world.add_relation::<Possesses>(player, body);
+    world.add_relation::<Possesses>(ghost, other_body);
+
+    // the ghost leaves its old body and takes the body of the player
+    assert_eq!(
+        Ok(Displaced {
+            old_target: Some(other_body),
+            old_origin: Some(player),
+        }),
+        world.try_add_relation::<Possesses>(ghost, body)
+    );
+    assert_eq!(vec![(ghost, body)], world.relation_pairs::<Possesses>());
+    assert_eq!(0, world.relation_target_count::<Possesses>(player));
+    assert_eq!(0, world.relation_origin_count::<Possesses>(other_body));
+
+    assert_eq!(
+        Ok(Displaced::default()),
+        world.try_add_relation::<Possesses>(player, other_body)
+    );
+    assert_eq!(
+        Ok(Displaced {
+            old_target: Some(other_body),
+            old_origin: Some(ghost),
+        }),
+        world.try_add_relation::<Possesses>(player, body)
+    );
+    // relating the same pair again displaces nothing
+    assert_eq!(
+        Ok(Displaced::default()),
+        world.try_add_relation::<Possesses>(player, body)
+    );
+    let mut pairs = world.relation_pairs::<Possesses>();
+    pairs.sort_by_key(|(a, _)| a.id.0);
+    assert_eq!(vec![(player, body)], pairs);
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn relation_exclusive_symmetric() {
+    enum MarriedTo {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<MarriedTo>(SYMMETRIC | EXCLUSIVE);
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let c = world.create_entity();
+    let d = world.create_entity();
+    world.add_relation::<MarriedTo>(a, b);
+    world.add_relation::<MarriedTo>(c, d);
+
+    // a marries c, both old pairs are broken up on both sides
+    world.add_relation::<MarriedTo>(a, c);
+    assert!(world.has_relation::<MarriedTo>(a, c));
+    assert!(world.has_relation::<MarriedTo>(c, a));
+    assert!(!world.has_relation::<MarriedTo>(b, a));
+    assert!(!world.has_relation::<MarriedTo>(d, c));
+    assert_eq!(0, world.relation_target_count::<MarriedTo>(b));
+    assert_eq!(0, world.relation_target_count::<MarriedTo>(d));
+    assert_eq!(Ok(()), world.validate());
+
+    // the same happens when the target is the one with the old partner
+    world.add_relation::<MarriedTo>(b, d);
+    world.add_relation::<MarriedTo>(d, a);
+    assert!(world.has_relation::<MarriedTo>(a, d));
+    assert!(!world.has_relation::<MarriedTo>(c, a));
+    assert!(!world.has_relation::<MarriedTo>(b, d));
+    assert_eq!(0, world.relation_target_count::<MarriedTo>(b));
+    assert_eq!(0, world.relation_target_count::<MarriedTo>(c));
+    assert_eq!(Ok(()), world.validate());
+
+    // pairing an existing pair again changes nothing
+    world.add_relation::<MarriedTo>(a, d);
+    assert_eq!(1, world.relation_target_count::<MarriedTo>(a));
+    assert_eq!(1, world.relation_target_count::<MarriedTo>(d));
 }
 
 #[test]
@@ -139,6 +269,139 @@ fn relation_cascading() {
     assert!(!world.is_alive(b));
 }
 
+#[test]
+fn relation_cascading_reaches_entity_twice() {
+    enum Rel {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<Rel>(CASCADING_DESTRUCT);
+    let a = world.create_entity();
+    let b = world.create().relate_from::<Rel>(a).entity;
+    // c is destroyed by the cascade of b, before the cascade of a gets to it
+    let c = world
+        .create()
+        .relate_from::<Rel>(a)
+        .relate_from::<Rel>(b)
+        .entity;
+
+    world.destroy(a);
+    assert!(!world.is_alive(b));
+    assert!(!world.is_alive(c));
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn relation_target_cascading() {
+    enum AppliesTo {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<AppliesTo>(TARGET_CASCADING_DESTRUCT);
+    let unit = world.create_entity();
+    let other_unit = world.create_entity();
+    let buff = world.create().relate_to::<AppliesTo>(unit).entity;
+    let shared_buff = world
+        .create()
+        .relate_to::<AppliesTo>(unit)
+        .relate_to::<AppliesTo>(other_unit)
+        .entity;
+    let other_buff = world.create().relate_to::<AppliesTo>(other_unit).entity;
+
+    // destroying an origin doesn't cascade
+    world.destroy(other_buff);
+    assert!(world.is_alive(other_unit));
+
+    world.destroy(unit);
+    assert!(!world.is_alive(buff));
+    assert!(!world.is_alive(shared_buff));
+    assert!(world.is_alive(other_unit));
+    assert_eq!(0, world.relation_origin_count::<AppliesTo>(other_unit));
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn relation_cleanup_policy() {
+    enum Rel {}
+
+    let mut world = World::new();
+    let cid = world.register_relation::<Rel>();
+    assert_eq!(CleanupPolicy::Remove, cid.cleanup_policy());
+    assert_eq!(CleanupPolicy::Remove, cid.flip_target().cleanup_policy());
+
+    enum Both {}
+    world.register_relation_flags::<Both>(CASCADING_DESTRUCT | PANIC_ON_TARGET_DESTRUCT);
+    // already registered, so this only returns the id
+    let cid = world.register_relation::<Both>();
+    assert_eq!(CleanupPolicy::Destroy, cid.cleanup_policy());
+    assert_eq!(CleanupPolicy::Panic, cid.flip_target().cleanup_policy());
+}
+
+#[test]
+#[should_panic(expected = "it is the target of a InstanceOf relation")]
+fn relation_panic_on_target_destruct() {
+    enum InstanceOf {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<InstanceOf>(PANIC_ON_TARGET_DESTRUCT);
+    let prefab = world.create_entity();
+    let item = world.create().relate_to::<InstanceOf>(prefab).entity;
+
+    // origins can be destroyed
+    world.destroy(item);
+    world.create().relate_to::<InstanceOf>(prefab);
+    world.destroy(prefab);
+}
+
+#[test]
+fn relation_panic_on_origin_destruct() {
+    enum Owns {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<Owns>(PANIC_ON_ORIGIN_DESTRUCT);
+    let owner = world.create_entity();
+    let thing = world.create_entity();
+    world.add_relation::<Owns>(owner, thing);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.destroy(owner)));
+    assert!(result.is_err());
+    // nothing was changed before the panic
+    assert!(world.is_alive(owner));
+    assert!(world.has_relation::<Owns>(owner, thing));
+    assert_eq!(Ok(()), world.validate());
+
+    world.remove_relation::<Owns>(owner, thing);
+    world.destroy(owner);
+    assert!(!world.is_alive(owner));
+}
+
+#[test]
+fn relation_panic_on_destruct_in_cascade() {
+    enum Owns {}
+    enum InstanceOf {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<Owns>(CASCADING_DESTRUCT);
+    world.register_relation_flags::<InstanceOf>(PANIC_ON_TARGET_DESTRUCT);
+    let owner = world.create_entity();
+    let thing = world.create().relate_from::<Owns>(owner).entity;
+    let prefab = world.create().relate_from::<Owns>(owner).entity;
+    let item = world.create().relate_to::<InstanceOf>(prefab).entity;
+
+    // the prefab is only reached after the thing, it still panics before anything is changed
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.destroy(owner)));
+    assert!(result.is_err());
+    assert!(world.is_alive(owner));
+    assert!(world.is_alive(thing));
+    assert!(world.has_relation::<Owns>(owner, thing));
+    assert_eq!(Ok(()), world.validate());
+
+    // the item is destroyed before the prefab
+    world.add_relation::<Owns>(thing, item);
+    world.destroy(owner);
+    assert!(!world.is_alive(prefab));
+    assert!(!world.is_alive(item));
+    assert_eq!(Ok(()), world.validate());
+}
+
 #[test]
 fn relation_transitive() {
     enum Rel {}
@@ -176,3 +439,172 @@ fn relation_transitive_circle() {
     assert!(world.has_relation::<Rel>(a, c));
     assert!(world.has_relation::<Rel>(a, d));
 }
+
+#[test]
+fn relation_acyclic() {
+    enum ChildOf {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<ChildOf>(ACYCLIC | EXCLUSIVE);
+    let root = world.create_entity();
+    let a = world.create().relate_to::<ChildOf>(root).entity;
+    let b = world.create().relate_to::<ChildOf>(a).entity;
+
+    assert_eq!(
+        Err(AddRelationError::Cycle { from: root, to: b }),
+        world.try_add_relation::<ChildOf>(root, b)
+    );
+    assert_eq!(
+        Err(AddRelationError::Cycle { from: a, to: a }),
+        world.try_add_relation::<ChildOf>(a, a)
+    );
+    assert!(!world.has_relation::<ChildOf>(root, b));
+    assert!(world.has_relation::<ChildOf>(b, a));
+
+    // moving b to the root is fine, adding an existing relation too
+    let moved = Displaced {
+        old_target: Some(a),
+        old_origin: None,
+    };
+    assert_eq!(Ok(moved), world.try_add_relation::<ChildOf>(b, root));
+    assert_eq!(
+        Ok(Displaced::default()),
+        world.try_add_relation::<ChildOf>(b, root)
+    );
+    // now a is not an ancestor of b anymore
+    let moved = Displaced {
+        old_target: Some(root),
+        old_origin: None,
+    };
+    assert_eq!(Ok(moved), world.try_add_relation::<ChildOf>(a, b));
+}
+
+#[test]
+#[should_panic(expected = "would close a cycle in an ACYCLIC relation")]
+fn relation_acyclic_panics() {
+    enum ChildOf {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<ChildOf>(ACYCLIC);
+    let a = world.create_entity();
+    let b = world.create().relate_to::<ChildOf>(a).entity;
+    world.add_relation::<ChildOf>(a, b);
+}
+
+#[test]
+fn relation_acyclic_deferred() {
+    enum ChildOf {}
+
+    let mut world = World::new();
+    world.register_relation_flags::<ChildOf>(ACYCLIC);
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.view_deferred(a).relate_to::<ChildOf>(b);
+    world.view_deferred(b).relate_to::<ChildOf>(a);
+    let c = world.create_deferred().relate_to::<ChildOf>(b).entity;
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.process()));
+    let err = result.unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("would close a cycle"), "{msg}");
+
+    // the other operations were executed anyway
+    assert!(world.has_relation::<ChildOf>(a, b));
+    assert!(!world.has_relation::<ChildOf>(b, a));
+    assert!(world.has_relation::<ChildOf>(c, b));
+}
+
+#[test]
+fn relation_traversal() {
+    enum Rel {}
+
+    // a -> b -> c -> d, a -> d, d -> b
+    let mut world = World::new();
+    world.register_relation::<Rel>();
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let c = world.create_entity();
+    let d = world.create_entity();
+    world.add_relation::<Rel>(a, b);
+    world.add_relation::<Rel>(b, c);
+    world.add_relation::<Rel>(c, d);
+    world.add_relation::<Rel>(a, d);
+    world.add_relation::<Rel>(d, b);
+
+    let bfs: Vec<Entity> = world
+        .relation_targets_transitive::<Rel>(a, TraversalOrder::BreadthFirst, None)
+        .collect();
+    assert_eq!(vec![b, d, c], bfs);
+    let mut dfs: Vec<Entity> = world
+        .relation_targets_transitive::<Rel>(a, TraversalOrder::DepthFirst, None)
+        .collect();
+    assert_eq!(3, dfs.len());
+    dfs.sort_by_key(|e| e.id.0);
+    assert_eq!(vec![b, c, d], dfs);
+
+    // the cycle leads back to the start
+    let from_b: Vec<Entity> = world
+        .relation_targets_transitive::<Rel>(b, TraversalOrder::BreadthFirst, None)
+        .collect();
+    assert_eq!(vec![c, d, b], from_b);
+
+    // d is one hop away from a, even if depth first finds it over c first
+    for order in [TraversalOrder::BreadthFirst, TraversalOrder::DepthFirst] {
+        let mut near: Vec<Entity> = world
+            .relation_targets_transitive::<Rel>(a, order, Some(1))
+            .collect();
+        near.sort_by_key(|e| e.id.0);
+        assert_eq!(vec![b, d], near);
+        let mut two: Vec<Entity> = world
+            .relation_targets_transitive::<Rel>(a, order, Some(2))
+            .collect();
+        two.sort_by_key(|e| e.id.0);
+        assert_eq!(vec![b, c, d], two);
+    }
+
+    let mut origins: Vec<Entity> = world
+        .relation_origins_transitive::<Rel>(c, TraversalOrder::default(), None)
+        .collect();
+    origins.sort_by_key(|e| e.id.0);
+    assert_eq!(vec![a, b, c, d], origins);
+    let origins: Vec<Entity> = world
+        .relation_origins_transitive::<Rel>(a, TraversalOrder::default(), None)
+        .collect();
+    assert!(origins.is_empty());
+
+    world.destroy(c);
+    let origins: Vec<Entity> = world
+        .relation_origins_transitive::<Rel>(c, TraversalOrder::default(), None)
+        .collect();
+    assert!(origins.is_empty());
+}
+
+#[test]
+fn relation_counts_and_pairs() {
+    enum Rel {}
+
+    let mut world = World::new();
+    world.register_relation::<Rel>();
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let c = world.create_entity();
+    world.add_relation::<Rel>(a, b);
+    world.add_relation::<Rel>(a, c);
+    world.add_relation::<Rel>(b, c);
+
+    assert_eq!(2, world.relation_target_count::<Rel>(a));
+    assert_eq!(0, world.relation_target_count::<Rel>(c));
+    assert_eq!(2, world.relation_origin_count::<Rel>(c));
+    assert_eq!(0, world.relation_origin_count::<Rel>(a));
+
+    let mut pairs: Vec<(Entity, Entity)> = world.relation_pairs_iter::<Rel>().collect();
+    pairs.sort_by_key(|(a, b)| (a.id.0, b.id.0));
+    let mut eager = world.relation_pairs::<Rel>();
+    eager.sort_by_key(|(a, b)| (a.id.0, b.id.0));
+    assert_eq!(vec![(a, b), (a, c), (b, c)], pairs);
+    assert_eq!(eager, pairs);
+
+    world.destroy(a);
+    assert_eq!(0, world.relation_target_count::<Rel>(a));
+    assert_eq!(1, world.relation_origin_count::<Rel>(c));
+}
@@ -0,0 +1,110 @@
+use froql::{count, exists, query, world::World};
+
+struct Name(&'static str);
+struct Apples;
+struct Pears;
+enum Eats {}
+
+mod state {
+    pub struct Idle;
+    pub struct Fighting;
+}
+enum State {}
+
+#[test]
+fn type_entity_stable() {
+    let mut world = World::new();
+    assert_eq!(None, world.get_type_entity::<Apples>());
+    let apples = world.type_entity::<Apples>();
+    assert_eq!(apples, world.type_entity::<Apples>());
+    assert_eq!(Some(apples), world.get_type_entity::<Apples>());
+    assert_ne!(apples, world.type_entity::<Pears>());
+
+    // a destroyed type entity is replaced
+    world.destroy(apples);
+    assert_eq!(None, world.get_type_entity::<Apples>());
+    let apples_again = world.type_entity::<Apples>();
+    assert_ne!(apples, apples_again);
+    assert!(world.is_alive(apples_again));
+}
+
+#[test]
+fn type_entity_query() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation::<Eats>();
+    let apples = world.type_entity::<Apples>();
+    let pears = world.type_entity::<Pears>();
+    world
+        .create()
+        .add(Name("anna"))
+        .relate_to::<Eats>(apples)
+        .relate_to::<Eats>(pears);
+    world.create().add(Name("otto")).relate_to::<Eats>(apples);
+    world.create().add(Name("emil")).relate_to::<Eats>(pears);
+
+    let mut names: Vec<&str> = query!(world, Name, Eats(this, @Apples))
+        .map(|(name,)| name.0)
+        .collect();
+    names.sort();
+    assert_eq!(vec!["anna", "otto"], names);
+
+    let names: Vec<&str> = query!(world, Name, Eats(this, @Apples), Eats(this, @Pears))
+        .map(|(name,)| name.0)
+        .collect();
+    assert_eq!(vec!["anna"], names);
+
+    let names: Vec<&str> = query!(world, Name, Eats(this, @Pears), !Eats(this, @Apples))
+        .map(|(name,)| name.0)
+        .collect();
+    assert_eq!(vec!["emil"], names);
+
+    // type entities can have components too
+    world.add_component(apples, Name("apples"));
+    let mut eaten: Vec<(&str, &str)> =
+        query!(world, Name(a), Name(food), Eats(a, food), Name(@Apples))
+            .map(|(a, food, _)| (a.0, food.0))
+            .collect();
+    eaten.sort();
+    assert_eq!(vec![("anna", "apples"), ("otto", "apples")], eaten);
+}
+
+#[test]
+fn type_entity_states() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation_flags::<State>(froql::component::EXCLUSIVE);
+    let idle = world.type_entity::<state::Idle>();
+    let fighting = world.type_entity::<state::Fighting>();
+    let a = world
+        .create()
+        .add(Name("a"))
+        .relate_to::<State>(idle)
+        .entity;
+    world.create().add(Name("b")).relate_to::<State>(idle);
+
+    assert_eq!(2, count!(world, State(this, @state::Idle)));
+    world.add_relation::<State>(a, fighting);
+    assert_eq!(1, count!(world, State(this, @state::Idle)));
+    let fighters: Vec<&str> = query!(world, Name, State(this, @state::Fighting))
+        .map(|(name,)| name.0)
+        .collect();
+    assert_eq!(vec!["a"], fighters);
+}
+
+#[test]
+fn type_entity_missing() {
+    let mut world = World::new();
+    world.register_component::<Name>();
+    world.register_relation::<Eats>();
+    world.create().add(Name("anna"));
+
+    // there is no type entity for apples yet, so the query has no rows at all,
+    // just like a query with a dead entity in it
+    assert!(!exists!(world, Name, Eats(this, @Apples)));
+    assert_eq!(0, query!(world, Name, Eats(this, @Apples)).count());
+    assert_eq!(0, query!(world, Name, !Eats(this, @Apples)).count());
+
+    world.type_entity::<Apples>();
+    assert_eq!(1, query!(world, Name, !Eats(this, @Apples)).count());
+}
@@ -0,0 +1,110 @@
+use froql::{query, world::World};
+use std::marker::PhantomData;
+
+mod physics {
+    #[derive(Debug, PartialEq)]
+    pub struct Velocity(pub i32);
+    pub enum Pulls {}
+}
+
+struct Handle<T>(u32, PhantomData<T>);
+struct Texture;
+struct Sound;
+enum AttachedTo<T> {
+    _Marker(PhantomData<T>),
+}
+struct Palette([u8; 3]);
+struct Pair<A, B>(A, B);
+
+mod nested {
+    use froql::{query, world::World};
+
+    pub fn count_velocities(world: &World) -> usize {
+        query!(world, super::physics::Velocity).count()
+    }
+}
+
+#[test]
+fn type_paths() {
+    let mut world = World::new();
+    let a = world
+        .create()
+        .add(physics::Velocity(3))
+        .add(Handle::<Texture>(1, PhantomData))
+        .add(Handle::<Sound>(2, PhantomData))
+        .add(Palette([1, 2, 3]))
+        .entity;
+    let b = world
+        .create()
+        .add(physics::Velocity(5))
+        .add(Handle::<Texture>(7, PhantomData))
+        .add(Pair(1_u8, 2_i32))
+        .entity;
+    world.add_relation::<physics::Pulls>(a, b);
+    world.add_relation::<AttachedTo<Sound>>(b, a);
+
+    let result: Vec<i32> = query!(
+        world,
+        crate::type_paths::physics::Velocity(a),
+        self::physics::Pulls(a, b)
+    )
+    .map(|(v,)| v.0)
+    .collect();
+    assert_eq!(vec![3], result);
+
+    let mut result: Vec<u32> = query!(world, Handle<Texture>).map(|(h,)| h.0).collect();
+    result.sort();
+    assert_eq!(vec![1, 7], result);
+
+    let result: Vec<(u32, Option<u32>)> = query!(
+        world,
+        Handle<Texture>(a),
+        Handle<Sound>(b)?,
+        AttachedTo<Sound>(a, b),
+    )
+    .map(|(t, s)| (t.0, s.map(|s| s.0)))
+    .collect();
+    assert_eq!(vec![(7, Some(2))], result);
+
+    for (mut v,) in query!(world, mut physics::Velocity, !Handle<Sound>) {
+        v.0 += 10;
+    }
+    let mut result: Vec<i32> = query!(world, physics::Velocity).map(|(v,)| v.0).collect();
+    result.sort();
+    assert_eq!(vec![3, 15], result);
+
+    let result: Vec<[u8; 3]> = query!(world, Palette, _ Handle<Sound>)
+        .map(|(p,)| p.0)
+        .collect();
+    assert_eq!(vec![[1, 2, 3]], result);
+    let result: Vec<(u8, i32)> = query!(world, Pair<u8, i32>)
+        .map(|(p,)| (p.0, p.1))
+        .collect();
+    assert_eq!(vec![(1, 2)], result);
+
+    assert_eq!(2, nested::count_velocities(&world));
+
+    // text queries take the full path of generic types
+    let result = world
+        .query_str("it::type_paths::Handle<it::type_paths::Texture>(a), Velocity(a)")
+        .unwrap();
+    assert_eq!(2, result.rows.len());
+}
+
+// types from macro_rules arrive as a single invisible group
+macro_rules! count_single {
+    ($value:expr, $ty:ty) => {{
+        let mut world = World::new();
+        world.create().add($value);
+        query!(world, $ty).count()
+    }};
+}
+
+#[test]
+fn type_from_macro_rules() {
+    assert_eq!(
+        1,
+        count_single!(Handle::<Texture>(1, PhantomData), Handle<Texture>)
+    );
+    assert_eq!(1, count_single!(physics::Velocity(1), physics::Velocity));
+}
@@ -0,0 +1,90 @@
+use froql::{
+    archetype::ArchetypeRow, component::EXCLUSIVE, entity_store::EntityId,
+    relation_vec::RelationVec, validate::IntegrityError, world::World,
+};
+
+#[allow(dead_code)]
+struct Pos(i32, i32);
+struct Marker;
+enum ChildOf {}
+enum Likes {}
+
+#[test]
+fn validate_after_operations() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    world.register_component::<Marker>();
+    world.register_relation_flags::<ChildOf>(EXCLUSIVE);
+    world.register_relation::<Likes>();
+
+    let parent = world.create().add(Pos(0, 0)).entity;
+    let mut children = Vec::new();
+    for i in 0..10 {
+        let child = world
+            .create()
+            .add(Pos(i, i))
+            .relate_to::<ChildOf>(parent)
+            .entity;
+        children.push(child);
+    }
+    for pair in children.windows(2) {
+        world.add_relation::<Likes>(pair[0], pair[1]);
+    }
+    world.add_component(children[3], Marker);
+    world.destroy(children[5]);
+    world.remove_relation::<Likes>(children[0], children[1]);
+    world.remove_component::<Pos>(children[2]);
+    world.add_relation::<ChildOf>(children[7], children[8]);
+    world.view_deferred(children[9]).destroy();
+    world.create_deferred().add(Pos(3, 3));
+    world.process();
+
+    assert_eq!(Ok(()), world.validate());
+}
+
+#[test]
+fn validate_entity_location() {
+    let mut world = World::new();
+    world.register_component::<Pos>();
+    let e = world.create().add(Pos(1, 2)).entity;
+    let (aid, _) = world.bookkeeping.entities.get_archetype(e);
+    world
+        .bookkeeping
+        .entities
+        .set_archetype(e, aid, ArchetypeRow(5));
+
+    assert_eq!(
+        Err(vec![IntegrityError::EntityLocation {
+            entity: e.id,
+            expected: (aid, ArchetypeRow(5)),
+            found: None,
+        }]),
+        world.validate()
+    );
+}
+
+#[test]
+fn validate_relation_mirror() {
+    let mut world = World::new();
+    world.register_relation::<Likes>();
+    let a = world.create().entity;
+    let b = world.create().entity;
+    let c = world.create().entity;
+    world.add_relation::<Likes>(a, b);
+    world.add_relation::<Likes>(c, b);
+
+    // add a partner to the origin side only
+    let cid = world.register_relation::<Likes>();
+    let ptr = world.bookkeeping.get_component(a, cid) as *mut RelationVec;
+    unsafe { (*ptr).push(c.id.0) };
+
+    let errors = world.validate().unwrap_err();
+    assert_eq!(
+        vec![IntegrityError::MissingMirror {
+            entity: a.id,
+            relation: "Likes".to_string(),
+            partner: EntityId(c.id.0),
+        }],
+        errors
+    );
+}
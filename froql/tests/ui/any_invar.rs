@@ -0,0 +1,10 @@
+use froql::{count, world::World};
+
+struct Name(&'static str);
+enum Likes {}
+
+fn main() {
+    let world = World::new();
+    count!(world, Likes(*_, a));
+    count!(world, Name(*_));
+}
@@ -0,0 +1,9 @@
+use froql::{count, world::World};
+
+enum Likes {}
+
+fn main() {
+    let world = World::new();
+    count!(world, Likes(_, _));
+    count!(world, Likes(a, b), !Likes(_, _));
+}
@@ -0,0 +1,11 @@
+use froql::{count, query, world::World};
+
+struct Name(&'static str);
+struct Pos(i32);
+
+fn main() {
+    let world = World::new();
+    for _ in query!(world, cross, Name(a), Pos(b)?) {}
+    count!(world, cross, Name(a), &b);
+    count!(world, cross, Name(a), !Pos(b), &b);
+}
@@ -0,0 +1,9 @@
+use froql::{count, world::World};
+
+enum Likes {}
+
+fn main() {
+    let world = World::new();
+    count!(world, Likes(a, c), Likes(b, c), a == b);
+    count!(world, Likes(a, c), Likes(b, c), a = *b);
+}
@@ -0,0 +1,10 @@
+use froql::{count, world::World};
+
+struct Name(&'static str);
+
+fn main() {
+    let world = World::new();
+    count!(world, Name(a b));
+    count!(world, & Name(a));
+    count!(world, <T as Trait>(a));
+}
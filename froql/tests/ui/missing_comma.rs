@@ -0,0 +1,10 @@
+use froql::{query, world::World};
+
+struct Name(&'static str);
+
+fn main() {
+    let world = World::new();
+    for (name,) in query!(world Name(a)) {
+        println!("{}", name.0);
+    }
+}
@@ -0,0 +1,9 @@
+use froql::query;
+
+struct Name(&'static str);
+
+fn main() {
+    for (name,) in query!(, Name(a)) {
+        println!("{}", name.0);
+    }
+}
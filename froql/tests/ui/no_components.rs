@@ -0,0 +1,9 @@
+use froql::{count, world::World};
+
+struct Time(f32);
+
+fn main() {
+    let world = World::new();
+    count!(world, &a);
+    count!(world, $ Time);
+}
@@ -0,0 +1,12 @@
+use froql::{count, query, world::World};
+
+struct Name(&'static str);
+enum ChildOf {}
+
+fn main() {
+    let world = World::new();
+    count!(world, Name(a), ChildOf+(a, _));
+    count!(world, Name(a), ChildOf*(a, a));
+    count!(world, Name(a), ChildOf{3, 1}(a, b));
+    for _ in query!(world, Name(a), ChildOf(a, b), hops(a, b)) {}
+}
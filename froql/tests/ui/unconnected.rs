@@ -0,0 +1,10 @@
+use froql::{count, world::World};
+
+struct Name(&'static str);
+enum Likes {}
+
+fn main() {
+    let world = World::new();
+    count!(world, Name(a), Likes(a, b), Name(c));
+    count!(world, Name(a), a != b);
+}
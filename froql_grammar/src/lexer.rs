@@ -0,0 +1,173 @@
+use crate::token::{FallbackSpan, ParseError, Token};
+
+/// Character range in the input string, `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// zero-width at the start of the input
+impl FallbackSpan for Span {
+    fn fallback() -> Self {
+        Span::new(0, 0)
+    }
+}
+
+/// Splits a query string into tokens.
+///
+/// Paths like `my_mod::Name` become a single identifier,
+/// since they can't be split into anything the grammar understands.
+pub fn lex(input: &str) -> Result<Vec<Token<Span>>, ParseError<Span>> {
+    let chars: Vec<char> = input.chars().collect();
+    // the outermost level is the result, every open parenthesis pushes a level
+    let mut stack: Vec<(usize, Vec<Token<Span>>)> = vec![(0, Vec::new())];
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            loop {
+                while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                    pos += 1;
+                }
+                let is_path = chars.get(pos) == Some(&':')
+                    && chars.get(pos + 1) == Some(&':')
+                    && chars
+                        .get(pos + 2)
+                        .is_some_and(|c| c.is_alphabetic() || *c == '_');
+                if !is_path {
+                    break;
+                }
+                pos += 2;
+            }
+            let ident = chars[start..pos].iter().collect();
+            let span = Span::new(start, pos);
+            stack.last_mut().unwrap().1.push(Token::Ident(ident, span));
+        } else if c == '(' {
+            stack.push((pos, Vec::new()));
+            pos += 1;
+        } else if c == ')' {
+            if stack.len() == 1 {
+                return Err(unexpected(pos, "Unmatched ')'"));
+            }
+            let (start, tokens) = stack.pop().unwrap();
+            let span = Span::new(start, pos + 1);
+            stack.last_mut().unwrap().1.push(Token::Group(tokens, span));
+            pos += 1;
+        } else if "*&!=?$,|<>:-+@".contains(c) {
+            let span = Span::new(pos, pos + 1);
+            stack.last_mut().unwrap().1.push(Token::Punct(c, span));
+            pos += 1;
+        } else {
+            return Err(unexpected(pos, &format!("Unexpected character '{c}'")));
+        }
+    }
+    if stack.len() > 1 {
+        let (start, _) = stack.pop().unwrap();
+        return Err(unexpected(start, "Unclosed '('"));
+    }
+    Ok(stack.pop().unwrap().1)
+}
+
+fn unexpected(pos: usize, message: &str) -> ParseError<Span> {
+    let span = Span::new(pos, pos + 1);
+    ParseError {
+        start: span,
+        end: span,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lex_relation() {
+        let tokens = lex("IsA(a, *b)").unwrap();
+        insta::assert_debug_snapshot!(tokens, @r#"
+        [
+            Ident(
+                "IsA",
+                Span {
+                    start: 0,
+                    end: 3,
+                },
+            ),
+            Group(
+                [
+                    Ident(
+                        "a",
+                        Span {
+                            start: 4,
+                            end: 5,
+                        },
+                    ),
+                    Punct(
+                        ',',
+                        Span {
+                            start: 5,
+                            end: 6,
+                        },
+                    ),
+                    Punct(
+                        '*',
+                        Span {
+                            start: 7,
+                            end: 8,
+                        },
+                    ),
+                    Ident(
+                        "b",
+                        Span {
+                            start: 8,
+                            end: 9,
+                        },
+                    ),
+                ],
+                Span {
+                    start: 3,
+                    end: 10,
+                },
+            ),
+        ]
+        "#);
+    }
+
+    #[test]
+    fn lex_path_and_unequal() {
+        let tokens = lex("a::Name(x), x != y").unwrap();
+        let kinds: Vec<String> = tokens
+            .iter()
+            .map(|t| match t {
+                Token::Ident(name, _) => name.clone(),
+                Token::Punct(c, _) => c.to_string(),
+                Token::Group(..) => "()".to_string(),
+                Token::Other(other, _) => other.clone(),
+            })
+            .collect();
+        assert_eq!(vec!["a::Name", "()", ",", "x", "!", "=", "y"], kinds);
+    }
+
+    #[test]
+    fn lex_errors() {
+        let err = lex("Name(a").unwrap_err();
+        assert_eq!(
+            (Span::new(4, 5), "Unclosed '('"),
+            (err.start, &*err.message)
+        );
+        let err = lex("Name(a))").unwrap_err();
+        assert_eq!(Span::new(7, 8), err.start);
+        let err = lex("Näme % 1").unwrap_err();
+        assert_eq!(Span::new(5, 6), err.start);
+    }
+}
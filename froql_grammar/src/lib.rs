@@ -0,0 +1,20 @@
+//! The term grammar of froql queries.
+//!
+//! The `query!` macro parses `proc_macro` tokens, `World::query_str` parses strings.
+//! Both convert their input into `Token`s and share the parser in this crate.
+//! The span type is generic so that each side can report errors in its own coordinates.
+#![allow(clippy::needless_return)]
+
+#[cfg(feature = "proc_macro")]
+extern crate proc_macro;
+
+mod lexer;
+mod parser;
+mod token;
+
+pub use lexer::{Span, lex};
+pub use parser::{
+    AggregateKind, OrVars, Order, RelationVarKind, Term, VarKind, parse_term, split_terms,
+    transform_anyvars,
+};
+pub use token::{FallbackSpan, ParseError, Token};
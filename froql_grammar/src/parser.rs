@@ -0,0 +1,1439 @@
+use std::fmt::Debug;
+
+use crate::token::{FallbackSpan, ParseError, Token};
+
+macro_rules! error {
+    ($arr:expr, $($arg:tt)*) => {
+        return Err(ParseError::slice($arr, format!($($arg)*)))
+    };
+}
+
+macro_rules! error_single {
+    ($tt:expr, $($arg:tt)*) => {
+        return Err(ParseError::single($tt, format!($($arg)*)))
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarKind {
+    /// variable that just exists in the query
+    Var(String),
+    /// variable that is coming into the query from an outside scope
+    /// marked with a * before its name in the query! syntax
+    InVar(String),
+}
+use VarKind as VK;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelationVarKind {
+    /// variable that just exists in the query
+    Var(String),
+    /// variable that is coming into the query from an outside scope
+    /// marked with a * before its name in the query! syntax
+    InVar(String),
+    /// just a _
+    AnyVar,
+}
+use RelationVarKind as RVK;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateKind {
+    /// number of distinct entities
+    Count,
+    /// sum of a component over distinct entities
+    Sum,
+    /// smallest value of a component
+    Min,
+    /// largest value of a component
+    Max,
+}
+
+impl AggregateKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+}
+
+/// Variables of an OR term
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrVars {
+    /// `(CompA | CompB)(var)`
+    Component(VarKind),
+    /// `(RelA | RelB)(a, b)`
+    Relation(RelationVarKind, RelationVarKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    /// smallest first
+    Asc,
+    /// largest first
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Type, Variable
+    ComponentVar(String, VarKind),
+    /// Type, Variable
+    MutComponentVar(String, VarKind),
+    /// Type, Variable
+    /// but this Component won't be in the output tuple
+    NoOutComponentVar(String, VarKind),
+    /// Type, Variable, Variable
+    Relation(String, RelationVarKind, RelationVarKind),
+    /// VariableName
+    OutVar(String),
+    /// VarA, VarB
+    ConstraintUnequal(VarKind, VarKind),
+    /// Type, VariableName
+    Uncomponent(String, String),
+    /// Type, Variable, Variable
+    Unrelation(String, RelationVarKind, RelationVarKind),
+    /// Type, VariableName
+    OptionalComponent(String, String),
+    /// Type, VariableName
+    OptionalMutComponent(String, String),
+    /// Type
+    Singleton(String),
+    /// Type
+    SingletonMut(String),
+    /// Kind, Type, VariableName
+    /// Type is None for `count(var)`
+    Aggregate(AggregateKind, Option<String>, String),
+    /// Type, VariableName, Order
+    OrderBy(String, String, Order),
+    /// Types of the branches, Variables, output the index of the matching branch
+    Or(Vec<String>, OrVars, bool),
+    /// Type, Variable, Variable
+    /// one of the variables is only bound if there is a matching relation
+    OptionalRelation(String, RelationVarKind, RelationVarKind),
+    /// Type, Variable
+    /// `where(Comp(var) => closure)`, the closure is not part of the grammar
+    Where(String, VarKind),
+    /// `cross`, variables may be unconnected and are joined with every other match
+    CrossJoin,
+    /// Type, Variable, Variable, min hops, max hops
+    /// `Rel+(a, b)`, `Rel*(a, b)` or `Rel{1,3}(a, b)`, max is None if unbounded
+    Path(String, RelationVarKind, RelationVarKind, u32, Option<u32>),
+    /// VariableName, VariableName
+    /// `hops(a, b)`, number of hops of the path term between the two variables
+    Hops(String, String),
+}
+
+/// Splits the tokens at top level commas, skipping empty terms.
+/// Commas between the generic arguments of a type don't split.
+pub fn split_terms<S>(tokens: &[Token<S>]) -> impl Iterator<Item = &[Token<S>]> {
+    let mut depth = 0_usize;
+    let mut last_minus = false;
+    tokens
+        .split(move |t| {
+            match t {
+                Token::Punct('<', _) => depth += 1,
+                Token::Punct('>', _) if !last_minus => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            last_minus = matches!(t, Token::Punct('-', _));
+            depth == 0 && matches!(t, Token::Punct(',', _))
+        })
+        .filter(|term| !term.is_empty())
+}
+
+pub fn parse_term<S: FallbackSpan + Debug>(tokens: &[Token<S>]) -> Result<Term, ParseError<S>> {
+    use Token as T;
+    let tokens = &type_entities(invar_expressions(merge_type_paths(tokens)?))?[..];
+    check_equality(tokens)?;
+    if let [T::Ident(name, _), T::Group(group, _)] = tokens
+        && let Some(kind) = AggregateKind::from_name(name)
+    {
+        return parse_aggregate(kind, tokens, group);
+    }
+    if let [T::Ident(name, _), T::Group(group, _)] = tokens
+        && name == "order_by"
+    {
+        return parse_order_by(tokens, group);
+    }
+    if let [T::Ident(name, _), T::Group(group, _)] = tokens
+        && name == "where"
+    {
+        return parse_where(tokens, group);
+    }
+    if let [T::Ident(name, _), T::Group(group, _)] = tokens
+        && name == "hops"
+    {
+        return match &group[..] {
+            [T::Ident(a, _), T::Punct(',', _), T::Ident(b, _)] => {
+                Ok(Term::Hops(a.to_string(), b.to_string()))
+            }
+            _ => error!(tokens, "Expected hops(a, b)"),
+        };
+    }
+    if let [
+        T::Ident(ty, _),
+        op @ (T::Punct('+' | '*', _) | T::Other(..)),
+        vars @ T::Group(..),
+    ] = tokens
+    {
+        return parse_path(ty, op, vars);
+    }
+    match tokens {
+        [T::Group(..), ..] => return parse_or(tokens, false),
+        [T::Punct('&', _), rest @ ..] if matches!(rest.first(), Some(T::Group(..))) => {
+            return parse_or(rest, true);
+        }
+        _ => (),
+    }
+    if tokens.len() == 1 {
+        return match &tokens[0] {
+            T::Ident(ty, _) if ty == "cross" => Ok(Term::CrossJoin),
+            T::Ident(ty, _) => Ok(Term::ComponentVar(
+                ty.to_string(),
+                VK::Var("this".to_string()),
+            )),
+            _ => error!(
+                tokens,
+                "Expected Component, got `{}`",
+                render_tokens(tokens)
+            ),
+        };
+    }
+    if tokens.len() == 2 {
+        return match (&tokens[0], &tokens[1]) {
+            (T::Ident(mutability, _), T::Ident(ty, _)) => match mutability.as_str() {
+                "mut" => {
+                    return Ok(Term::MutComponentVar(
+                        ty.to_string(),
+                        VK::Var("this".to_string()),
+                    ));
+                }
+                "_" => {
+                    return Ok(Term::NoOutComponentVar(
+                        ty.to_string(),
+                        VK::Var("this".to_string()),
+                    ));
+                }
+                _ => {
+                    error_single!(&tokens[0], "Expected mut or _");
+                }
+            },
+            (T::Ident(ty, _), T::Group(group, _)) => {
+                let ty = ty.to_string();
+                match &group[..] {
+                    [T::Ident(var, _)] => Ok(Term::ComponentVar(ty, VK::Var(var.to_string()))),
+                    [star_t @ T::Punct(star, _), T::Ident(var, _)] => {
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        Ok(Term::ComponentVar(ty, VK::InVar(var.to_string())))
+                    }
+                    [
+                        T::Ident(rel_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        T::Ident(rel_b, _),
+                    ] => {
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        Ok(Term::Relation(
+                            ty,
+                            RVK::Var(rel_a.to_string()),
+                            RVK::Var(rel_b.to_string()),
+                        ))
+                    }
+                    [
+                        star_t @ T::Punct(star, _),
+                        T::Ident(rel_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        T::Ident(rel_b, _),
+                    ] => {
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        Ok(Term::Relation(
+                            ty,
+                            RVK::InVar(rel_a.to_string()),
+                            RVK::Var(rel_b.to_string()),
+                        ))
+                    }
+                    [
+                        T::Ident(rel_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        star_t @ T::Punct(star, _),
+                        T::Ident(rel_b, _),
+                    ] => {
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        Ok(Term::Relation(
+                            ty,
+                            RVK::Var(rel_a.to_string()),
+                            RVK::InVar(rel_b.to_string()),
+                        ))
+                    }
+                    [
+                        star_t @ T::Punct(star, _),
+                        T::Ident(rel_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        star2_t @ T::Punct(star2, _),
+                        T::Ident(rel_b, _),
+                    ] => {
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        if *star2 != '*' {
+                            error_single!(star2_t, "Expected '*'");
+                        }
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        Ok(Term::Relation(
+                            ty,
+                            RVK::InVar(rel_a.to_string()),
+                            RVK::InVar(rel_b.to_string()),
+                        ))
+                    }
+                    _ => error!(
+                        tokens,
+                        "expected Component(var) or Relation(a,b), got `{}`",
+                        render_tokens(tokens)
+                    ),
+                }
+            }
+            (T::Punct(punct, _), T::Ident(ident, _)) => match punct {
+                '&' => return Ok(Term::OutVar(ident.to_string())),
+                '!' => return Ok(Term::Uncomponent(ident.to_string(), "this".to_string())),
+                '$' => return Ok(Term::Singleton(ident.to_string())),
+                _ => error_single!(&tokens[0], "Expected & or ! or $"),
+            },
+            // Example: CompA?
+            (T::Ident(ty, _), question_t @ T::Punct(question, _)) => {
+                if *question != '?' {
+                    error_single!(question_t, "Expected '?'");
+                }
+                return Ok(Term::OptionalComponent(ty.to_string(), "this".to_string()));
+            }
+            _ => {
+                error!(
+                    tokens,
+                    "expected mut Component or Component(var) or Relation(a,b) or &var or Component? or $ Component, got `{}`",
+                    render_tokens(tokens)
+                );
+            }
+        };
+    }
+    if tokens.len() == 3 {
+        match (&tokens[0], &tokens[1], &tokens[2]) {
+            (T::Ident(mut_or_, _), T::Ident(ty, _), T::Group(group, _)) => match &group[..] {
+                [T::Ident(var, _)] => match mut_or_.as_str() {
+                    "mut" => {
+                        return Ok(Term::MutComponentVar(
+                            ty.to_string(),
+                            VK::Var(var.to_string()),
+                        ));
+                    }
+                    "_" => {
+                        return Ok(Term::NoOutComponentVar(
+                            ty.to_string(),
+                            VK::Var(var.to_string()),
+                        ));
+                    }
+                    _ => {
+                        error_single!(&tokens[0], "Expected mut or _");
+                    }
+                },
+                [star_t @ T::Punct(star, _), T::Ident(var, _)] => {
+                    if *star != '*' {
+                        error_single!(star_t, "Expected '*'");
+                    }
+                    match mut_or_.as_str() {
+                        "mut" => {
+                            return Ok(Term::MutComponentVar(
+                                ty.to_string(),
+                                VK::InVar(var.to_string()),
+                            ));
+                        }
+                        "_" => {
+                            return Ok(Term::NoOutComponentVar(
+                                ty.to_string(),
+                                VK::InVar(var.to_string()),
+                            ));
+                        }
+                        _ => {
+                            error_single!(&tokens[0], "Expected mut or _");
+                        }
+                    }
+                }
+                _ => error!(
+                    tokens,
+                    "expected <mut|_> Component(var), got `{}`",
+                    render_tokens(tokens)
+                ),
+            },
+            // mut Comp?
+            (mut_t @ T::Ident(mut_, _), T::Ident(ty, _), question_t @ T::Punct(question, _)) => {
+                if mut_ != "mut" {
+                    error_single!(mut_t, "Expected mut");
+                }
+                if *question != '?' {
+                    error_single!(question_t, "Expected '?'");
+                }
+                return Ok(Term::OptionalMutComponent(
+                    ty.to_string(),
+                    "this".to_string(),
+                ));
+            }
+            (bang_t @ T::Punct(bang, _), T::Ident(ty, _), T::Group(group, _)) => {
+                if *bang == '&' {
+                    let var = match &group[..] {
+                        [T::Ident(var, _)] => var.as_str(),
+                        _ => "a",
+                    };
+                    error!(
+                        tokens,
+                        "`&` only takes a variable, did you mean `&{var}` or `!{ty}({var})`?"
+                    );
+                }
+                if *bang != '!' {
+                    error_single!(bang_t, "Expected !");
+                }
+
+                match &group[..] {
+                    [T::Ident(var, _)] => {
+                        return Ok(Term::Uncomponent(ty.to_string(), var.to_string()));
+                    }
+                    [
+                        T::Ident(var_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        T::Ident(var_b, _),
+                    ] => {
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        return Ok(Term::Unrelation(
+                            ty.to_string(),
+                            RVK::Var(var_a.to_string()),
+                            RVK::Var(var_b.to_string()),
+                        ));
+                    }
+                    [
+                        star_t @ T::Punct(star, _),
+                        T::Ident(var_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        T::Ident(var_b, _),
+                    ] => {
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        return Ok(Term::Unrelation(
+                            ty.to_string(),
+                            RVK::InVar(var_a.to_string()),
+                            RVK::Var(var_b.to_string()),
+                        ));
+                    }
+                    [
+                        T::Ident(var_a, _),
+                        comma_t @ T::Punct(comma, _),
+                        star_t @ T::Punct(star, _),
+                        T::Ident(var_b, _),
+                    ] => {
+                        if *comma != ',' {
+                            error_single!(comma_t, "Expected ','");
+                        }
+                        if *star != '*' {
+                            error_single!(star_t, "Expected '*'");
+                        }
+                        return Ok(Term::Unrelation(
+                            ty.to_string(),
+                            RVK::Var(var_a.to_string()),
+                            RVK::InVar(var_b.to_string()),
+                        ));
+                    }
+                    _ => error!(
+                        tokens,
+                        "expected !Component(var) or !Rel(a,b), got `{}`",
+                        render_tokens(tokens)
+                    ),
+                }
+            }
+            (T::Ident(ty, _), T::Group(group, _), question_t @ T::Punct(question, _)) => {
+                if *question != '?' {
+                    error_single!(question_t, "Expected ?");
+                }
+                match &group[..] {
+                    [T::Ident(ident, _)] => {
+                        return Ok(Term::OptionalComponent(ty.to_string(), ident.to_string()));
+                    }
+                    _ => match parse_term(&tokens[..2]) {
+                        Ok(Term::Relation(ty, a, b)) => {
+                            return Ok(Term::OptionalRelation(ty, a, b));
+                        }
+                        _ => error!(
+                            tokens,
+                            "expected Component(var)? or Relation(a, b)?, got `{}`",
+                            render_tokens(tokens)
+                        ),
+                    },
+                }
+            }
+            (mut_t @ T::Ident(mut_, _), dollar_t @ T::Punct(dollar, _), T::Ident(ty, _)) => {
+                if mut_ != "mut" {
+                    error_single!(mut_t, "Expected mut");
+                }
+                if *dollar != '$' {
+                    error_single!(dollar_t, "Expected '$'");
+                }
+                return Ok(Term::SingletonMut(ty.to_string()));
+            }
+            _ => {
+                error!(
+                    tokens,
+                    "expected <mut|_|!|mut $> Component<(var)|?|>, got `{}`",
+                    render_tokens(tokens)
+                )
+            }
+        };
+    }
+
+    if tokens.len() == 4 {
+        match (&tokens[0], &tokens[1], &tokens[2], &tokens[3]) {
+            // var_a != var_b
+            (T::Ident(id_a, _), T::Punct(bang, _), T::Punct(equal, _), T::Ident(id_b, _)) => {
+                let var_a = id_a.to_string();
+                let var_b = id_b.to_string();
+                match (bang, equal) {
+                    ('!', '=') => (),
+                    _ => error!(&tokens[3..4], "Expected var_a != var_b"),
+                }
+                return Ok(Term::ConstraintUnequal(VK::Var(var_a), VK::Var(var_b)));
+            }
+            // mut Comp(var)?
+            (
+                mut_t @ T::Ident(mut_, _),
+                T::Ident(ty, _),
+                group_t @ T::Group(group, _),
+                question_t @ T::Punct(question, _),
+            ) => {
+                if mut_ != "mut" {
+                    error_single!(mut_t, "Expected mut");
+                }
+                if *question != '?' {
+                    error_single!(question_t, "Expected '?'");
+                }
+
+                match &group[..] {
+                    [T::Ident(var, _)] => {
+                        return Ok(Term::OptionalMutComponent(ty.to_string(), var.to_string()));
+                    }
+                    _ => {
+                        error_single!(group_t, "Expected (var)");
+                    }
+                }
+            }
+            _ => {
+                error!(
+                    tokens,
+                    "Expected var_a != var_b, got `{}`",
+                    render_tokens(tokens)
+                );
+            }
+        }
+    }
+    if tokens.len() == 5 {
+        match (&tokens[0], &tokens[1], &tokens[2], &tokens[3], &tokens[4]) {
+            (
+                T::Ident(id_a, _),
+                T::Punct(bang, _),
+                T::Punct(equal, _),
+                star_t @ T::Punct(star, _),
+                T::Ident(id_b, _),
+            ) => {
+                let var_a = id_a.to_string();
+                let var_b = id_b.to_string();
+                match (bang, equal) {
+                    ('!', '=') => (),
+                    _ => error!(&tokens[3..4], "Expected var_a != var_b"),
+                }
+                if *star != '*' {
+                    error_single!(star_t, "Expected '*'");
+                }
+                return Ok(Term::ConstraintUnequal(VK::Var(var_a), VK::InVar(var_b)));
+            }
+            (
+                star_t @ T::Punct(star, _),
+                T::Ident(id_a, _),
+                T::Punct(bang, _),
+                T::Punct(equal, _),
+                T::Ident(id_b, _),
+            ) => {
+                let var_a = id_a.to_string();
+                let var_b = id_b.to_string();
+                match (bang, equal) {
+                    ('!', '=') => (),
+                    _ => error!(&tokens[3..4], "Expected var_a != var_b"),
+                }
+                if *star != '*' {
+                    error_single!(star_t, "Expected '*'");
+                }
+                return Ok(Term::ConstraintUnequal(VK::InVar(var_a), VK::Var(var_b)));
+            }
+            _ => error!(
+                tokens,
+                "Expected a != *b or *a != b, got `{}`",
+                render_tokens(tokens)
+            ),
+        }
+    }
+    if tokens.len() == 6 {
+        match (
+            &tokens[0], &tokens[1], &tokens[2], &tokens[3], &tokens[4], &tokens[5],
+        ) {
+            (
+                star_t @ T::Punct(star, _),
+                T::Ident(id_a, _),
+                T::Punct(bang, _),
+                T::Punct(equal, _),
+                star2_t @ T::Punct(star2, _),
+                T::Ident(id_b, _),
+            ) => {
+                let var_a = id_a.to_string();
+                let var_b = id_b.to_string();
+                match (bang, equal) {
+                    ('!', '=') => (),
+                    _ => error!(&tokens[3..4], "Expected var_a != var_b"),
+                }
+                if *star != '*' {
+                    error_single!(star_t, "Expected '*'");
+                }
+                if *star2 != '*' {
+                    error_single!(star2_t, "Expected '*'");
+                }
+                return Ok(Term::ConstraintUnequal(VK::InVar(var_a), VK::InVar(var_b)));
+            }
+            _ => error!(tokens, "Expected *a != *b, got `{}`", render_tokens(tokens)),
+        }
+    }
+
+    error!(tokens, "Can't parse term `{}`", render_tokens(tokens));
+}
+
+/// Merges type paths like `physics::Velocity`, `::std::num::Wrapping<u8>`
+/// or `Handle<Texture>` into a single identifier,
+/// so that the rest of the parser only has to handle single identifiers as types.
+fn merge_type_paths<S: FallbackSpan + Debug>(
+    tokens: &[Token<S>],
+) -> Result<Vec<Token<S>>, ParseError<S>> {
+    use Token as T;
+    let is_colons = |index: usize| {
+        matches!(
+            tokens.get(index..index + 2),
+            Some([T::Punct(':', _), T::Punct(':', _)])
+        )
+    };
+    let mut result = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let starts_path = match &tokens[pos] {
+            T::Ident(name, _) if name != "mut" && name != "_" => {
+                is_colons(pos + 1) || matches!(tokens.get(pos + 1), Some(T::Punct('<', _)))
+            }
+            _ => is_colons(pos),
+        };
+        // `<T as Trait>::Type` in place of a type
+        let type_position = match pos.checked_sub(1).map(|it| &tokens[it]) {
+            None | Some(T::Punct('!', _)) => true,
+            Some(T::Ident(prefix, _)) => prefix == "mut" || prefix == "_",
+            Some(_) => false,
+        };
+        if type_position && matches!(tokens[pos], T::Punct('<', _)) {
+            error!(
+                &tokens[pos..],
+                "Qualified paths like `<T as Trait>::Type` can't be used as types in queries, add a type alias for the type and use that instead."
+            );
+        }
+        if !starts_path {
+            result.push(tokens[pos].clone());
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        if matches!(tokens[pos], T::Ident(..)) {
+            pos += 1;
+        }
+        loop {
+            if is_colons(pos) {
+                match tokens.get(pos + 2) {
+                    Some(T::Ident(..)) => pos += 3,
+                    // turbofish
+                    Some(T::Punct('<', _)) => pos += 2,
+                    _ => error!(
+                        &tokens[start..(pos + 2).min(tokens.len())],
+                        "Expected identifier after ::"
+                    ),
+                }
+            } else if let Some(T::Punct('<', _)) = tokens.get(pos) {
+                pos = generic_args_end(tokens, start, pos)?;
+            } else {
+                break;
+            }
+        }
+        let path = &tokens[start..pos];
+        result.push(T::Ident(render_tokens(path), path[0].span()));
+    }
+    Ok(result)
+}
+
+/// `a == b` and `a = b` are common typos of `a != b`
+fn check_equality<S: FallbackSpan + Debug>(tokens: &[Token<S>]) -> Result<(), ParseError<S>> {
+    let Some(start) = tokens
+        .iter()
+        .position(|t| matches!(t, Token::Punct('=', _)))
+    else {
+        return Ok(());
+    };
+    if start == 0 || matches!(tokens[start - 1], Token::Punct('!', _)) {
+        return Ok(());
+    }
+    let end = start
+        + tokens[start..]
+            .iter()
+            .take_while(|t| matches!(t, Token::Punct('=', _)))
+            .count();
+    let left = render_tokens(&tokens[..start]);
+    let right = render_tokens(&tokens[end..]);
+    error!(
+        &tokens[start..end],
+        "Variables can only be constrained to be unequal, did you mean `{left} != {right}`?"
+    );
+}
+
+/// Turns the block of `*{expression}` into an identifier,
+/// so that invars can be given as expressions like `*{self.player}`.
+fn invar_expressions<S: Copy>(tokens: Vec<Token<S>>) -> Vec<Token<S>> {
+    let mut after_star = false;
+    let mut result = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let token = match token {
+            Token::Group(group, span) => Token::Group(invar_expressions(group), span),
+            Token::Other(text, span) if after_star && text.starts_with('{') => {
+                Token::Ident(text, span)
+            }
+            token => token,
+        };
+        after_star = matches!(token, Token::Punct('*', _));
+        result.push(token);
+    }
+    result
+}
+
+/// Turns `@Type` into the invar `*@Type`,
+/// which stands for the entity of the component type, see `World::type_entity`.
+fn type_entities<S: FallbackSpan + Debug>(
+    tokens: Vec<Token<S>>,
+) -> Result<Vec<Token<S>>, ParseError<S>> {
+    use Token as T;
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut iter = tokens.into_iter();
+    while let Some(token) = iter.next() {
+        match token {
+            T::Group(group, span) => {
+                // type paths in groups are only merged where the group is parsed
+                let group = if group.iter().any(|t| matches!(t, T::Punct('@', _))) {
+                    merge_type_paths(&group)?
+                } else {
+                    group
+                };
+                result.push(T::Group(type_entities(group)?, span));
+            }
+            at @ T::Punct('@', _) => match iter.next() {
+                Some(T::Ident(ty, span)) if ty != "_" => {
+                    result.push(T::Punct('*', at.span()));
+                    result.push(T::Ident(format!("@{ty}"), span));
+                }
+                _ => error_single!(&at, "Expected a type after '@', like `@Apples`"),
+            },
+            token => result.push(token),
+        }
+    }
+    Ok(result)
+}
+
+/// index after the `>` that closes the `<` at `open`
+fn generic_args_end<S: FallbackSpan + Debug>(
+    tokens: &[Token<S>],
+    start: usize,
+    open: usize,
+) -> Result<usize, ParseError<S>> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Punct('<', _) => depth += 1,
+            // `->` of function types doesn't close anything
+            Token::Punct('>', _) if !matches!(tokens[index - 1], Token::Punct('-', _)) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(index + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    error!(&tokens[start..], "Unclosed '<' in type");
+}
+
+/// turns tokens back into Rust source, for types and error messages
+fn render_tokens<S>(tokens: &[Token<S>]) -> String {
+    let mut result = String::new();
+    let mut last_is_word = false;
+    for token in tokens {
+        let is_word = matches!(token, Token::Ident(..) | Token::Other(..));
+        if last_is_word && is_word {
+            result.push(' ');
+        }
+        match token {
+            Token::Ident(text, _) | Token::Other(text, _) => result.push_str(text),
+            Token::Punct(',', _) => result.push_str(", "),
+            Token::Punct(c, _) => result.push(*c),
+            Token::Group(group, _) => {
+                result.push('(');
+                result.push_str(&render_tokens(group));
+                result.push(')');
+            }
+        }
+        last_is_word = is_word;
+    }
+    result
+}
+
+/// `count(var)`, `count(Comp(var))` or `sum|min|max(Comp(var))`
+fn parse_aggregate<S: FallbackSpan + Debug>(
+    kind: AggregateKind,
+    tokens: &[Token<S>],
+    group: &[Token<S>],
+) -> Result<Term, ParseError<S>> {
+    if let (AggregateKind::Count, [Token::Ident(var, _)]) = (kind, group) {
+        return Ok(Term::Aggregate(kind, None, var.to_string()));
+    }
+    if group.is_empty() {
+        error!(tokens, "Expected a Component inside of the aggregate");
+    }
+    match parse_term(group)? {
+        Term::ComponentVar(ty, VK::Var(var)) => Ok(Term::Aggregate(kind, Some(ty), var)),
+        _ => error!(
+            group,
+            "Expected Component or Component(var) inside of the aggregate"
+        ),
+    }
+}
+
+/// `(CompA | CompB)`, `(CompA | CompB)(var)` or `(RelA | RelB)(a, b)`
+fn parse_or<S: FallbackSpan + Debug>(
+    tokens: &[Token<S>],
+    output: bool,
+) -> Result<Term, ParseError<S>> {
+    let (branches, vars) = match tokens {
+        [Token::Group(branches, _)] => (branches, None),
+        [Token::Group(branches, _), vars @ Token::Group(..)] => (branches, Some(vars)),
+        _ => error!(tokens, "Expected (A | B) or (A | B)(vars)"),
+    };
+    let mut types = Vec::new();
+    for (index, branch) in merge_type_paths(branches)?.iter().enumerate() {
+        match (index % 2, branch) {
+            (0, Token::Ident(ty, _)) => types.push(ty.to_string()),
+            (1, Token::Punct('|', _)) => (),
+            _ => error_single!(branch, "Expected (A | B)"),
+        }
+    }
+    if types.len() < 2 || branches.len() % 2 == 0 {
+        error!(
+            tokens,
+            "An OR term needs to be written as (A | B) with at least two branches"
+        );
+    }
+    let Some(vars) = vars else {
+        let this = VK::Var("this".to_string());
+        return Ok(Term::Or(types, OrVars::Component(this), output));
+    };
+    // the variables are parsed the same way as for a single component or relation
+    let single = [
+        Token::Ident(types[0].clone(), tokens[0].span()),
+        vars.clone(),
+    ];
+    let vars = match parse_term(&single)? {
+        Term::ComponentVar(_, var) => OrVars::Component(var),
+        Term::Relation(_, a, b) => OrVars::Relation(a, b),
+        _ => error_single!(vars, "Expected (var) or (a, b)"),
+    };
+    Ok(Term::Or(types, vars, output))
+}
+
+/// `Rel+(a, b)`, `Rel*(a, b)`, `Rel{2}(a, b)`, `Rel{1,3}(a, b)` or `Rel{2,}(a, b)`
+fn parse_path<S: FallbackSpan + Debug>(
+    ty: &str,
+    op: &Token<S>,
+    vars: &Token<S>,
+) -> Result<Term, ParseError<S>> {
+    let (min, max) = match op {
+        Token::Punct('+', _) => (1, None),
+        Token::Punct('*', _) => (0, None),
+        Token::Other(text, _) => match parse_hop_bounds(text) {
+            Some((_, Some(0))) => error_single!(op, "A path needs at least one hop"),
+            Some((min, Some(max))) if min > max => {
+                error_single!(op, "The smallest number of hops is larger than the largest")
+            }
+            Some(bounds) => bounds,
+            None => error_single!(op, "Expected hops like {{2}}, {{1,3}} or {{2,}}"),
+        },
+        _ => error_single!(op, "Expected + or * or {{min,max}}"),
+    };
+    // the variables are parsed the same way as for a single relation
+    let single = [Token::Ident(ty.to_string(), op.span()), vars.clone()];
+    match parse_term(&single)? {
+        Term::Relation(ty, a, b) => Ok(Term::Path(ty, a, b, min, max)),
+        _ => error_single!(vars, "Expected (a, b)"),
+    }
+}
+
+/// `{2}`, `{1,3}` or `{2,}`
+fn parse_hop_bounds(text: &str) -> Option<(u32, Option<u32>)> {
+    let inner = text.strip_prefix('{')?.strip_suffix('}')?;
+    match inner.split_once(',') {
+        None => {
+            let hops = inner.trim().parse().ok()?;
+            Some((hops, Some(hops)))
+        }
+        Some((min, max)) if max.trim().is_empty() => Some((min.trim().parse().ok()?, None)),
+        Some((min, max)) => Some((min.trim().parse().ok()?, Some(max.trim().parse().ok()?))),
+    }
+}
+
+/// `order_by(Comp(var) asc|desc)`, the order is optional and defaults to `asc`
+fn parse_order_by<S: FallbackSpan + Debug>(
+    tokens: &[Token<S>],
+    group: &[Token<S>],
+) -> Result<Term, ParseError<S>> {
+    let (order, key) = match group {
+        [key @ .., Token::Ident(order, _)] if !key.is_empty() && order == "asc" => {
+            (Order::Asc, key)
+        }
+        [key @ .., Token::Ident(order, _)] if !key.is_empty() && order == "desc" => {
+            (Order::Desc, key)
+        }
+        [] => error!(tokens, "Expected a Component inside of order_by"),
+        key => (Order::Asc, key),
+    };
+    match parse_term(key)? {
+        Term::ComponentVar(ty, VK::Var(var)) => Ok(Term::OrderBy(ty, var, order)),
+        _ => error!(key, "Expected order_by(Component(var) asc|desc)"),
+    }
+}
+
+/// `where(Comp(var) => closure)`, everything after `=>` is left to the caller
+fn parse_where<S: FallbackSpan + Debug>(
+    tokens: &[Token<S>],
+    group: &[Token<S>],
+) -> Result<Term, ParseError<S>> {
+    let arrow = group
+        .windows(2)
+        .position(|it| matches!(it, [Token::Punct('=', _), Token::Punct('>', _)]));
+    let Some(arrow) = arrow.filter(|arrow| *arrow > 0 && arrow + 2 < group.len()) else {
+        error!(
+            tokens,
+            "Expected where(Component(var) => |value| predicate)"
+        );
+    };
+    match parse_term(&group[..arrow])? {
+        Term::ComponentVar(ty, var) => Ok(Term::Where(ty, var)),
+        _ => error!(
+            &group[..arrow],
+            "Expected where(Component(var) => |value| predicate)"
+        ),
+    }
+}
+
+/// the parser treats the identifier _ of anyvars as normal variable names
+/// this function changes those to the proper anyvar enum variant
+pub fn transform_anyvars(input: Term) -> Result<Term, String> {
+    match input {
+        Term::Relation(ty, var_a, var_b) => Ok(Term::Relation(
+            ty,
+            transform_var(var_a)?,
+            transform_var(var_b)?,
+        )),
+        Term::Unrelation(ty, var_a, var_b) => Ok(Term::Unrelation(
+            ty,
+            transform_var(var_a)?,
+            transform_var(var_b)?,
+        )),
+        Term::OptionalRelation(ty, var_a, var_b) => Ok(Term::OptionalRelation(
+            ty,
+            transform_var(var_a)?,
+            transform_var(var_b)?,
+        )),
+        Term::Path(ty, var_a, var_b, min, max) => Ok(Term::Path(
+            ty,
+            transform_var(var_a)?,
+            transform_var(var_b)?,
+            min,
+            max,
+        )),
+        Term::Or(types, OrVars::Relation(var_a, var_b), output) => Ok(Term::Or(
+            types,
+            OrVars::Relation(transform_var(var_a)?, transform_var(var_b)?),
+            output,
+        )),
+        Term::ComponentVar(_, VK::InVar(ref var))
+        | Term::MutComponentVar(_, VK::InVar(ref var))
+        | Term::NoOutComponentVar(_, VK::InVar(ref var))
+        | Term::Where(_, VK::InVar(ref var))
+        | Term::Or(_, OrVars::Component(VK::InVar(ref var)), _)
+        | Term::ConstraintUnequal(VK::InVar(ref var), _)
+        | Term::ConstraintUnequal(_, VK::InVar(ref var))
+            if var == "_" =>
+        {
+            Err(ANY_INVAR_ERROR.to_string())
+        }
+        no_transform => Ok(no_transform),
+    }
+}
+
+const ANY_INVAR_ERROR: &str = "An Invar may not also be an AnyVar!\n*_ is not allowed!";
+
+fn transform_var(input: RVK) -> Result<RVK, String> {
+    match input {
+        RVK::Var(ref var) => {
+            if var == "_" {
+                Ok(RVK::AnyVar)
+            } else {
+                Ok(input)
+            }
+        }
+        RVK::InVar(ref var) => {
+            if var == "_" {
+                return Err(ANY_INVAR_ERROR.to_string());
+            }
+            Ok(input)
+        }
+        RVK::AnyVar => Ok(input),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexer::{Span, lex};
+
+    fn parse(input: &str) -> Result<Term, String> {
+        let tokens = lex(input).unwrap();
+        parse_term(&tokens)
+            .map_err(|err| err.message)
+            .and_then(transform_anyvars)
+    }
+
+    #[test]
+    fn parse_terms() {
+        let terms: Vec<Term> = [
+            "Name",
+            "mut Health(a)",
+            "_ Name(*e)",
+            "IsA(a, *b)",
+            "!Dead",
+            "!IsA(_, a)",
+            "a != *b",
+            "Name(a)?",
+            "mut $ Config",
+            "count(c)",
+            "sum(Weight(c))",
+            "order_by(Initiative desc)",
+            "(Burning | Poisoned)",
+            "&(Owns | Borrows)(*a, _)",
+            "cross",
+            "ChildOf(a, p)?",
+            "where(Health(*e) => |h| is_low(h))",
+        ]
+        .iter()
+        .map(|it| parse(it).unwrap())
+        .collect();
+        insta::assert_debug_snapshot!(terms, @r#"
+        [
+            ComponentVar(
+                "Name",
+                Var(
+                    "this",
+                ),
+            ),
+            MutComponentVar(
+                "Health",
+                Var(
+                    "a",
+                ),
+            ),
+            NoOutComponentVar(
+                "Name",
+                InVar(
+                    "e",
+                ),
+            ),
+            Relation(
+                "IsA",
+                Var(
+                    "a",
+                ),
+                InVar(
+                    "b",
+                ),
+            ),
+            Uncomponent(
+                "Dead",
+                "this",
+            ),
+            Unrelation(
+                "IsA",
+                AnyVar,
+                Var(
+                    "a",
+                ),
+            ),
+            ConstraintUnequal(
+                Var(
+                    "a",
+                ),
+                InVar(
+                    "b",
+                ),
+            ),
+            OptionalComponent(
+                "Name",
+                "a",
+            ),
+            SingletonMut(
+                "Config",
+            ),
+            Aggregate(
+                Count,
+                None,
+                "c",
+            ),
+            Aggregate(
+                Sum,
+                Some(
+                    "Weight",
+                ),
+                "c",
+            ),
+            OrderBy(
+                "Initiative",
+                "this",
+                Desc,
+            ),
+            Or(
+                [
+                    "Burning",
+                    "Poisoned",
+                ],
+                Component(
+                    Var(
+                        "this",
+                    ),
+                ),
+                false,
+            ),
+            Or(
+                [
+                    "Owns",
+                    "Borrows",
+                ],
+                Relation(
+                    InVar(
+                        "a",
+                    ),
+                    AnyVar,
+                ),
+                true,
+            ),
+            CrossJoin,
+            OptionalRelation(
+                "ChildOf",
+                Var(
+                    "a",
+                ),
+                Var(
+                    "p",
+                ),
+            ),
+            Where(
+                "Health",
+                InVar(
+                    "e",
+                ),
+            ),
+        ]
+        "#);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let tokens = lex("Name(a b)").unwrap();
+        let err = parse_term(&tokens).unwrap_err();
+        assert_eq!(0, err.start.start);
+        assert_eq!(9, err.end.end);
+
+        let tokens = lex("& Name(a)").unwrap();
+        let err = parse_term(&tokens).unwrap_err();
+        assert_eq!(
+            "`&` only takes a variable, did you mean `&a` or `!Name(a)`?",
+            err.message
+        );
+
+        let tokens = lex("<T as Trait>::Health(a)").unwrap();
+        let err = parse_term(&tokens).unwrap_err();
+        assert!(err.message.starts_with("Qualified paths like"));
+        assert!(parse("mut <T as Trait>::Health(a)").is_err());
+
+        assert!(parse("IsA(*_, a)").is_err());
+        assert!(parse("sum()").is_err());
+        assert!(parse("max(Weight(*c))").is_err());
+        assert!(parse("order_by(mut Initiative)").is_err());
+        assert!(parse("(Burning)").is_err());
+        assert!(parse("(Burning | Poisoned |)").is_err());
+        assert!(parse("(Burning, Poisoned)(a)").is_err());
+        assert!(parse("ChildOf(a b)?").is_err());
+        assert!(parse("where(Health(a))").is_err());
+        assert!(parse("where(Health(a) =>)").is_err());
+        assert!(parse("where(mut Health(a) => |h| h)").is_err());
+        assert!(parse("Handle<Texture(a)").is_err());
+        assert!(parse("physics::(a)").is_err());
+        assert_eq!(
+            Err(
+                "Variables can only be constrained to be unequal, did you mean `a != *b`?"
+                    .to_string()
+            ),
+            parse("a == *b")
+        );
+        assert!(parse("a = b").is_err());
+        assert!(parse("Name(*_)").is_err());
+        assert!(parse("a != *_").is_err());
+
+        // the error covers the whole path
+        let tokens = lex("mut Handle<Texture Sprite(a)").unwrap();
+        let err = parse_term(&tokens).unwrap_err();
+        assert_eq!("Unclosed '<' in type", err.message);
+        assert_eq!(4, err.start.start);
+        assert_eq!(28, err.end.end);
+
+        // without tokens the error is zero-width at the start
+        let err = parse_term::<Span>(&[]).unwrap_err();
+        assert_eq!(Span { start: 0, end: 0 }, err.start);
+        assert_eq!(Span { start: 0, end: 0 }, err.end);
+    }
+
+    #[test]
+    fn parse_paths() {
+        let path = |min, max| {
+            Term::Path(
+                "ChildOf".to_string(),
+                RVK::Var("a".to_string()),
+                RVK::InVar("b".to_string()),
+                min,
+                max,
+            )
+        };
+        assert_eq!(Ok(path(1, None)), parse("ChildOf+(a, *b)"));
+        assert_eq!(Ok(path(0, None)), parse("ChildOf*(a, *b)"));
+        assert_eq!(
+            Ok(Term::Hops("a".to_string(), "b".to_string())),
+            parse("hops(a, b)")
+        );
+        assert!(parse("ChildOf+(a)").is_err());
+        assert!(parse("hops(a)").is_err());
+
+        // the lexer has no braces, the macro passes them in as `Other`
+        let bounds = |text: &str| {
+            let mut tokens = lex("ChildOf+(a, *b)").unwrap();
+            tokens[1] = Token::Other(text.to_string(), tokens[1].span());
+            parse_term(&tokens).map_err(|err| err.message)
+        };
+        assert_eq!(Ok(path(1, Some(3))), bounds("{ 1, 3 }"));
+        assert_eq!(Ok(path(2, Some(2))), bounds("{2}"));
+        assert_eq!(Ok(path(2, None)), bounds("{2,}"));
+        assert_eq!(
+            Err("A path needs at least one hop".to_string()),
+            bounds("{0}")
+        );
+        assert!(bounds("{3, 1}").is_err());
+        assert!(bounds("{a}").is_err());
+        assert!(bounds("[1]").is_err());
+    }
+
+    #[test]
+    fn parse_type_entities() {
+        let invar = |name: &str| RVK::InVar(name.to_string());
+        assert_eq!(
+            Ok(Term::Relation(
+                "Eats".to_string(),
+                RVK::Var("this".to_string()),
+                invar("@Apples")
+            )),
+            parse("Eats(this, @Apples)")
+        );
+        assert_eq!(
+            Ok(Term::Relation(
+                "Likes".to_string(),
+                invar("@food::Apples"),
+                invar("@Handle<Sword>")
+            )),
+            parse("Likes(@food::Apples, @Handle<Sword>)")
+        );
+        assert_eq!(
+            Ok(Term::ComponentVar(
+                "Description".to_string(),
+                VK::InVar("@Apples".to_string())
+            )),
+            parse("Description(@Apples)")
+        );
+        assert!(parse("Eats(this, @)").is_err());
+        assert!(parse("Eats(this, @_)").is_err());
+        assert!(parse("Eats(this, @*a)").is_err());
+    }
+
+    #[test]
+    fn parse_invar_expressions() {
+        // the lexer has no blocks, the macro passes them in as `Other`
+        let span = Span { start: 0, end: 0 };
+        let block = || Token::Other("{ self.player }".to_string(), span);
+        let tokens = vec![
+            Token::Ident("Likes".to_string(), span),
+            Token::Group(
+                vec![
+                    Token::Ident("a".to_string(), span),
+                    Token::Punct(',', span),
+                    Token::Punct('*', span),
+                    block(),
+                ],
+                span,
+            ),
+        ];
+        assert_eq!(
+            Term::Relation(
+                "Likes".to_string(),
+                RVK::Var("a".to_string()),
+                RVK::InVar("{ self.player }".to_string())
+            ),
+            parse_term(&tokens).unwrap()
+        );
+        // without the star a block is no variable
+        let tokens = vec![
+            Token::Ident("Name".to_string(), span),
+            Token::Group(vec![block()], span),
+        ];
+        assert!(parse_term(&tokens).is_err());
+    }
+
+    #[test]
+    fn parse_type_paths() {
+        let terms: Vec<Term> = [
+            "physics::Velocity(a)",
+            "mut crate::physics::Velocity",
+            "!super::Dead(a)",
+            "$ ::std::num::Wrapping<u8>",
+            "Handle<Texture>(a)?",
+            "assets::Handle<assets::Texture>",
+            "Cache<HashMap<Key, Value>>(a)",
+            "Vec::<Health>",
+            "ChildOf<Scene>(a, b)",
+            "(Handle<Texture> | Handle<Sound>)(a)",
+            "order_by(Wrapping<i32>(a) desc)",
+        ]
+        .into_iter()
+        .map(|it| parse(it).unwrap())
+        .collect();
+        insta::assert_debug_snapshot!(terms, @r#"
+        [
+            ComponentVar(
+                "physics::Velocity",
+                Var(
+                    "a",
+                ),
+            ),
+            MutComponentVar(
+                "crate::physics::Velocity",
+                Var(
+                    "this",
+                ),
+            ),
+            Uncomponent(
+                "super::Dead",
+                "a",
+            ),
+            Singleton(
+                "::std::num::Wrapping<u8>",
+            ),
+            OptionalComponent(
+                "Handle<Texture>",
+                "a",
+            ),
+            ComponentVar(
+                "assets::Handle<assets::Texture>",
+                Var(
+                    "this",
+                ),
+            ),
+            ComponentVar(
+                "Cache<HashMap<Key, Value>>",
+                Var(
+                    "a",
+                ),
+            ),
+            ComponentVar(
+                "Vec::<Health>",
+                Var(
+                    "this",
+                ),
+            ),
+            Relation(
+                "ChildOf<Scene>",
+                Var(
+                    "a",
+                ),
+                Var(
+                    "b",
+                ),
+            ),
+            Or(
+                [
+                    "Handle<Texture>",
+                    "Handle<Sound>",
+                ],
+                Component(
+                    Var(
+                        "a",
+                    ),
+                ),
+                false,
+            ),
+            OrderBy(
+                "Wrapping<i32>",
+                "a",
+                Desc,
+            ),
+        ]
+        "#);
+
+        let tokens = lex("Name(a), Cache<HashMap<Key, Value>>(a), Boxed<dyn Fn() -> u8>").unwrap();
+        assert_eq!(3, split_terms(&tokens).count());
+    }
+}
@@ -0,0 +1,72 @@
+/// A token of the query grammar.
+///
+/// Mirrors the shape of `proc_macro::TokenTree`, but only the parts the grammar needs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<S> {
+    /// identifier, including `_`
+    Ident(String, S),
+    /// single punctuation character
+    Punct(char, S),
+    /// tokens in parentheses
+    Group(Vec<Token<S>>, S),
+    /// anything else, for example a literal, the parser rejects it
+    Other(String, S),
+}
+
+impl<S: Copy> Token<S> {
+    pub fn span(&self) -> S {
+        match self {
+            Token::Ident(_, span)
+            | Token::Punct(_, span)
+            | Token::Group(_, span)
+            | Token::Other(_, span) => *span,
+        }
+    }
+}
+
+/// Error with the spans of the first and the last offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError<S> {
+    pub start: S,
+    pub end: S,
+    pub message: String,
+}
+
+/// Span type of the tokens, with a span for errors that have no token to point at.
+pub trait FallbackSpan: Copy {
+    fn fallback() -> Self;
+}
+
+/// points at the whole macro invocation
+#[cfg(feature = "proc_macro")]
+impl FallbackSpan for proc_macro::Span {
+    fn fallback() -> Self {
+        proc_macro::Span::call_site()
+    }
+}
+
+impl<S: FallbackSpan> ParseError<S> {
+    /// An empty slice results in an error at the fallback span.
+    pub fn slice(tokens: &[Token<S>], message: String) -> Self {
+        let (start, end) = match tokens {
+            [] => (S::fallback(), S::fallback()),
+            [first, .., last] => (first.span(), last.span()),
+            [single] => (single.span(), single.span()),
+        };
+        Self {
+            start,
+            end,
+            message,
+        }
+    }
+}
+
+impl<S: Copy> ParseError<S> {
+    pub fn single(token: &Token<S>, message: String) -> Self {
+        Self {
+            start: token.span(),
+            end: token.span(),
+            message,
+        }
+    }
+}
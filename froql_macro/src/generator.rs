@@ -9,24 +9,29 @@ use join_order::InitVar;
 use join_order::JoinKind;
 use join_order::JoinOrderComputer;
 use join_order::NewJoin;
+use join_order::NewOptionalJoin;
 
 mod join_order;
 mod nodes;
 
 use crate::ANYVAR;
 use crate::Unrelation;
-use crate::{Accessor, Component, Relation};
+use crate::{Accessor, Component, OptRelation, Or, Path, Relation};
+use froql_grammar::AggregateKind;
+use froql_grammar::Order;
 pub use join_order::Checks;
 use nodes::GeneratorNode;
 use nodes::archetype_start::ArchetypeStart;
 use nodes::invar_start::InvarInfo;
 use nodes::invar_start::InvarStart;
+use nodes::optional_join::OptionalJoin;
 use nodes::relation_helper::RelationHelperInfo;
 use nodes::relation_helper::UnrelationHelperInfo;
 use nodes::relation_join::RelationJoin;
+use nodes::types::Predicate;
 use nodes::yield_result::YieldResult;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Generator {
     pub vars: Vec<isize>,
     pub prefills: HashMap<isize, String>,
@@ -38,24 +43,89 @@ pub struct Generator {
     pub unequals: Vec<(isize, isize)>,
     pub accessors: Vec<Accessor>,
     pub unrelations: Vec<Unrelation>,
+    /// keep component ids and archetype sets in a cache at the call site
+    pub cached: bool,
+    pub output: Output,
+    /// Type, variable, order
+    pub order_by: Vec<(String, isize, Order)>,
+    pub ors: Vec<Or>,
+    /// allow variables that are not connected by relations
+    pub cross_join: bool,
+    pub opt_relations: Vec<OptRelation>,
+    /// Type, variable, closure source
+    pub predicates: Vec<(String, isize, String)>,
+    pub paths: Vec<Path>,
+    /// invars and predicates are already bound by an enclosing block
+    pub captured: bool,
+}
+
+/// What the generated code evaluates to.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// an iterator over the result rows
+    #[default]
+    Rows,
+    /// number of matches as `usize`
+    Count,
+    /// `true` if there is at least one match
+    Exists,
 }
 
 impl Generator {
     pub fn generate(&self, world: &str) -> String {
+        if (self.accessors.iter()).any(|it| matches!(it, Accessor::Aggregate(..))) {
+            return self.generate_aggregated(world);
+        }
+        if !self.order_by.is_empty() {
+            return self.generate_ordered(world);
+        }
+        self.generate_rows(world, &self.accessors)
+    }
+
+    /// generates the query, yielding one tuple per row made from `accessors`
+    fn generate_rows(&self, world: &str, accessors: &[Accessor]) -> String {
+        if self.ors.iter().any(is_join_or) {
+            return self.generate_union(world, accessors);
+        }
         let mut result = String::new();
 
         result.push_str("{\n");
 
-        generate_invar_captures(&mut result, &self.prefills);
+        // user code is evaluated before any local is bound, so it can't see them
+        if !self.captured {
+            generate_invar_captures(&mut result, &self.prefills);
+            generate_predicates(&mut result, &self.predicates);
+        } else {
+            generate_predicate_clones(&mut result, &self.predicates);
+        }
 
         write!(
             &mut result,
             "
-let world: &World = &{world};
-let bk = &world.bookkeeping;
+use ::froql::query_helper::AsWorld as _;
+let __froql_world: &::froql::world::World = ({world}).as_world();
+let __froql_bk = &__froql_world.bookkeeping;
 "
         )
         .unwrap();
+        generate_type_entity_captures(&mut result, &self.prefills);
+        generate_or_arrays(&mut result, &self.ors);
+
+        if self.output != Output::Rows && self.archetypes_only() {
+            generate_archetype_sets(
+                &mut result,
+                &self.vars,
+                &self.prefills,
+                &self.components,
+                &self.relations,
+                &self.uncomponents,
+                &self.unrelations,
+                &self.ors,
+            );
+            generate_archetype_count(&mut result, self.output);
+            result.push_str("\n}");
+            return result;
+        }
 
         let mut infos = compute_var_infos(
             &self.vars,
@@ -63,16 +133,36 @@ let bk = &world.bookkeeping;
             &self.relations,
             &self.opt_components,
         );
+        for info in &mut infos {
+            info.check_archetype = !archetype_ors(&self.ors, info.index).is_empty();
+        }
+        for (nr, (ty, var, _)) in self.predicates.iter().enumerate() {
+            let info = &mut infos[*var as usize];
+            info.predicates.push(Predicate {
+                nr,
+                ty: ty.clone(),
+                var: *var,
+                column_index: info.components[ty],
+            });
+        }
         let join_order: Vec<JoinKind> = JoinOrderComputer::new(
             &self.relations,
             &mut infos,
             &self.prefills,
             &self.unequals,
             &self.unrelations,
+            &self.opt_relations,
+            &self.paths,
+            self.cross_join,
         )
         .compute_join_order();
 
-        generate_archetype_sets(
+        let generate_sets = if self.cached {
+            generate_cached_archetype_sets
+        } else {
+            generate_archetype_sets
+        };
+        generate_sets(
             &mut result,
             &self.vars,
             &self.prefills,
@@ -80,6 +170,7 @@ let bk = &world.bookkeeping;
             &self.relations,
             &self.uncomponents,
             &self.unrelations,
+            &self.ors,
         );
         generate_fsm_context(&mut result, &self.vars, &self.components, &self.relations);
 
@@ -89,12 +180,320 @@ let bk = &world.bookkeeping;
             join_order,
             &self.prefills,
             &infos,
-            &self.accessors,
+            accessors,
+        );
+        match self.output {
+            Output::Rows => {}
+            Output::Count => result.push_str(".count()"),
+            Output::Exists => result.push_str(".next().is_some()"),
+        }
+
+        result.push_str("\n}");
+        return result;
+    }
+
+    /// Runs the query with the outvars, the aggregated entities and values as result
+    /// and merges the rows into one row per distinct combination of outvars.
+    fn generate_aggregated(&self, world: &str) -> String {
+        let mut group_vars = Vec::new();
+        let mut aggregates = Vec::new();
+        for accessor in &self.accessors {
+            match accessor {
+                Accessor::OutVar(var) => group_vars.push(*var),
+                Accessor::Aggregate(kind, ty, var) => aggregates.push((*kind, ty.clone(), *var)),
+                _ => unreachable!("Internal: only outvars and aggregates in aggregating query"),
+            }
+        }
+        let mut row_accessors: Vec<Accessor> = group_vars
+            .iter()
+            .map(|var| Accessor::OutVar(*var))
+            .collect();
+        for (kind, ty, var) in &aggregates {
+            row_accessors.push(Accessor::OutVar(*var));
+            if *kind != AggregateKind::Count {
+                let ty = ty.clone().expect("Internal: aggregate needs a component");
+                row_accessors.push(Accessor::Component(ty, *var));
+            }
+        }
+        let key_len = group_vars.len();
+        let mut result = String::from("{\n");
+        let inner = self.generate_shared_captures(&mut result);
+        // the world expression is only evaluated once, by the outer block
+        let rows = inner.generate_rows("__froql_world", &row_accessors);
+        write!(
+            result,
+            "use ::froql::query_helper::AsWorld as _;
+let __froql_world: &::froql::world::World = ({world}).as_world();
+let mut __froql_group_indexes = ::std::collections::HashMap::new();
+let mut __froql_groups: ::std::vec::Vec<([::froql::entity_store::Entity; {key_len}], ("
+        )
+        .unwrap();
+        for (kind, _, _) in &aggregates {
+            match kind {
+                AggregateKind::Count => result.push_str("::froql::query_helper::Aggregate<()>, "),
+                _ => result.push_str("::froql::query_helper::Aggregate<_>, "),
+            }
+        }
+        result.push_str("))> = ::std::vec::Vec::new();\nfor (");
+        for i in 0..key_len {
+            write!(result, "group_{i}, ").unwrap();
+        }
+        for (i, (kind, _, _)) in aggregates.iter().enumerate() {
+            write!(result, "entity_{i}, ").unwrap();
+            if *kind != AggregateKind::Count {
+                write!(result, "value_{i}, ").unwrap();
+            }
+        }
+        write!(
+            result,
+            ") in {rows} {{
+    let key: [::froql::entity_store::Entity; {key_len}] = ["
+        )
+        .unwrap();
+        for i in 0..key_len {
+            write!(result, "group_{i}.entity, ").unwrap();
+        }
+        result.push_str(
+            "];
+    let index = *__froql_group_indexes.entry(key).or_insert_with(|| {
+        __froql_groups.push((key, ::std::default::Default::default()));
+        __froql_groups.len() - 1
+    });
+    let group = &mut __froql_groups[index].1;
+",
+        );
+        for (i, (kind, _, _)) in aggregates.iter().enumerate() {
+            let method = match kind {
+                AggregateKind::Count => {
+                    write!(result, "    group.{i}.insert(entity_{i}.entity);\n").unwrap();
+                    continue;
+                }
+                AggregateKind::Sum => "sum",
+                AggregateKind::Min => "min",
+                AggregateKind::Max => "max",
+            };
+            write!(
+                result,
+                "    group.{i}.{method}(entity_{i}.entity, || ::std::clone::Clone::clone(&*value_{i}));\n"
+            )
+            .unwrap();
+        }
+        let key = if key_len == 0 { "_" } else { "key" };
+        write!(
+            result,
+            "}}\n__froql_groups.into_iter().map(move |({key}, group)| ("
+        )
+        .unwrap();
+        let (mut group_index, mut aggregate_index) = (0, 0);
+        for accessor in &self.accessors {
+            match accessor {
+                Accessor::OutVar(_) => {
+                    write!(
+                        result,
+                        "\n    ::froql::entity_view_deferred::EntityViewDeferred::new(__froql_world, key[{group_index}]),"
+                    )
+                    .unwrap();
+                    group_index += 1;
+                }
+                Accessor::Aggregate(AggregateKind::Count, _, _) => {
+                    write!(result, "\n    group.{aggregate_index}.count(),").unwrap();
+                    aggregate_index += 1;
+                }
+                Accessor::Aggregate(..) => {
+                    write!(result, "\n    group.{aggregate_index}.into_value(),").unwrap();
+                    aggregate_index += 1;
+                }
+                _ => unreachable!(),
+            }
+        }
+        result.push_str("\n))\n}");
+        return result;
+    }
+
+    /// Collects the entities of every row together with the order keys and sorts them.
+    /// The result rows are then accessed by entity, when they are yielded.
+    fn generate_ordered(&self, world: &str) -> String {
+        let mut row_accessors: Vec<Accessor> =
+            self.vars.iter().map(|var| Accessor::OutVar(*var)).collect();
+        for (ty, var, _) in &self.order_by {
+            row_accessors.push(Accessor::Component(ty.clone(), *var));
+        }
+        let mut result = String::from("{\n");
+        let inner = self.generate_shared_captures(&mut result);
+        let rows = inner.generate_rows("__froql_world", &row_accessors);
+        write!(
+            result,
+            "use ::froql::query_helper::AsWorld as _;
+let __froql_world: &::froql::world::World = ({world}).as_world();\n"
+        )
+        .unwrap();
+        generate_branch_arrays(&mut result, &self.accessors, &self.ors);
+        write!(
+            result,
+            "let mut __froql_rows: ::std::vec::Vec<_> = {rows}
+.map(|("
+        )
+        .unwrap();
+        for var in &self.vars {
+            write!(result, "entity_{var}, ").unwrap();
+        }
+        for i in 0..self.order_by.len() {
+            write!(result, "key_{i}, ").unwrap();
+        }
+        result.push_str(")| ([");
+        for var in &self.vars {
+            write!(result, "entity_{var}.entity, ").unwrap();
+        }
+        result.push_str("], (");
+        for i in 0..self.order_by.len() {
+            write!(result, "::std::clone::Clone::clone(&*key_{i}), ").unwrap();
+        }
+        result.push_str(
+            ")))
+.collect();
+__froql_rows.sort_by(|(a_entities, a_keys), (b_entities, b_keys)| {
+    ",
         );
+        for (i, (_, _, order)) in self.order_by.iter().enumerate() {
+            let (a, b) = match order {
+                Order::Asc => ("a", "b"),
+                Order::Desc => ("b", "a"),
+            };
+            if i > 0 {
+                result.push_str("\n        .then_with(|| ");
+            }
+            write!(
+                result,
+                "::std::cmp::PartialOrd::partial_cmp(&{a}_keys.{i}, &{b}_keys.{i})
+            .unwrap_or(::std::cmp::Ordering::Equal)"
+            )
+            .unwrap();
+            if i > 0 {
+                result.push(')');
+            }
+        }
+        // same keys are ordered by entity id, so the order doesn't depend on archetypes
+        result.push_str(
+            "
+        .then_with(|| a_entities.map(|e| e.id.0).cmp(&b_entities.map(|e| e.id.0)))
+});
+",
+        );
+        result.push_str("__froql_rows.into_iter()");
+        generate_entity_accessors(&mut result, &self.accessors, &self.ors);
+        result.push_str("\n}");
+        return result;
+    }
+
+    /// OR relations between two variables are queried once per branch.
+    /// The rows of all branches are chained and every combination of entities is only returned once,
+    /// so the first matching branch wins.
+    fn generate_union(&self, world: &str, accessors: &[Accessor]) -> String {
+        let join_ors: Vec<&Or> = self.ors.iter().filter(|or| is_join_or(or)).collect();
+        // every combination of branches, the first branch of the first OR term comes first
+        let mut combinations: Vec<Vec<usize>> = vec![Vec::new()];
+        for (types, _, _, _) in &join_ors {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    (0..types.len()).map(move |branch| {
+                        let mut combination = combination.clone();
+                        combination.push(branch);
+                        combination
+                    })
+                })
+                .collect();
+        }
 
+        let mut result = String::from("{\n");
+        // every branch uses the same invars and predicates, so they are evaluated only once
+        let inner = self.generate_shared_captures(&mut result);
+        write!(
+            result,
+            "use ::froql::query_helper::AsWorld as _;
+let __froql_world: &::froql::world::World = ({world}).as_world();\n"
+        )
+        .unwrap();
+        generate_branch_arrays(&mut result, accessors, &self.ors);
+        result.push_str("let mut __froql_seen = ::std::collections::HashSet::new();\n");
+        match self.output {
+            Output::Exists => result.push_str("let mut __froql_rows = "),
+            _ => result.push_str("let __froql_rows = "),
+        }
+        let row_accessors: Vec<Accessor> =
+            self.vars.iter().map(|var| Accessor::OutVar(*var)).collect();
+        for (i, combination) in combinations.iter().enumerate() {
+            let mut sub = inner.clone();
+            sub.ors.retain(|or| !is_join_or(or));
+            sub.output = Output::Rows;
+            for ((types, a, b, _), branch) in join_ors.iter().zip(combination) {
+                sub.relations
+                    .push((types[*branch].clone(), *a, b.expect("Internal: join OR")));
+            }
+            let rows = sub.generate_rows("__froql_world", &row_accessors);
+            if i > 0 {
+                result.push_str("\n.chain(");
+            }
+            result.push_str(&rows);
+            result.push_str("\n.map(|(");
+            for var in &self.vars {
+                write!(result, "entity_{var}, ").unwrap();
+            }
+            result.push_str(")| ([");
+            for var in &self.vars {
+                write!(result, "entity_{var}.entity, ").unwrap();
+            }
+            result.push_str("], [");
+            for branch in combination {
+                write!(result, "{branch}usize, ").unwrap();
+            }
+            result.push_str("]))");
+            if i > 0 {
+                result.push(')');
+            }
+        }
+        result.push_str(
+            "\n.filter(move |(entities, _)| __froql_seen.insert(*entities));\n__froql_rows",
+        );
+        match self.output {
+            Output::Rows => generate_entity_accessors(&mut result, accessors, &self.ors),
+            Output::Count => result.push_str(".count()"),
+            Output::Exists => result.push_str(".next().is_some()"),
+        }
         result.push_str("\n}");
         return result;
     }
+
+    /// Binds the invars and predicates for queries that nest the generated rows in their own block.
+    /// Returns the generator for the nested rows, which reuses them.
+    fn generate_shared_captures(&self, result: &mut String) -> Generator {
+        let mut inner = self.clone();
+        if !self.captured {
+            generate_invar_captures(result, &self.prefills);
+            generate_shared_predicates(result, &self.predicates);
+            inner.captured = true;
+        }
+        inner
+    }
+
+    /// true if every entity in a matching archetype is a match,
+    /// so rows don't need to be visited one by one
+    fn archetypes_only(&self) -> bool {
+        self.vars.len() == 1
+            && self.predicates.is_empty()
+            && self.paths.is_empty()
+            && !self.ors.iter().any(is_join_or)
+            && self.prefills.is_empty()
+            && self.unequals.is_empty()
+            && self
+                .relations
+                .iter()
+                .all(|(_, a, b)| *a == ANYVAR || *b == ANYVAR)
+            && self
+                .unrelations
+                .iter()
+                .all(|(_, a, b, _)| *a == ANYVAR || *b == ANYVAR)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Default)]
@@ -122,6 +521,11 @@ pub struct VarInfo {
     /// if this var is set by a relation join, then this is the index of the RelationHelper
     /// for that join
     pub join_helper_index: Option<usize>,
+    /// OR terms filter the archetypes of this var
+    /// invars need to check that their archetype is in the archetype set
+    pub check_archetype: bool,
+    /// checked right after a row for this var is selected
+    pub predicates: Vec<Predicate>,
 }
 
 impl Debug for VarInfo {
@@ -161,30 +565,54 @@ pub(crate) fn generate_invar_captures(result: &mut String, prefills: &HashMap<is
     v.sort();
 
     for (index, name) in v {
+        // type entities need the world, see `generate_type_entity_captures`
+        if is_type_entity(name) {
+            continue;
+        }
         write!(
             result,
-            "let invar_{index}: ::froql::entity_store::Entity = (&{name}).into();
+            "let __froql_invar_{index}: ::froql::entity_store::Entity = (&{name}).into();
 "
         )
         .unwrap();
     }
 }
 
+/// `@Type` in a query, the entity of a component type
+pub(crate) fn is_type_entity(invar: &str) -> bool {
+    invar.starts_with('@')
+}
+
+/// looks up the entities of `@Type` invars, after the world is bound
+pub(crate) fn generate_type_entity_captures(
+    result: &mut String,
+    prefills: &HashMap<isize, String>,
+) {
+    let mut v = prefills.iter().collect::<Vec<_>>();
+    v.sort();
+
+    for (index, name) in v {
+        if let Some(ty) = name.strip_prefix('@') {
+            write!(
+                result,
+                "let __froql_invar_{index}: ::froql::entity_store::Entity = __froql_bk.type_entity_or_dead(::std::any::TypeId::of::<::std::cell::RefCell<{ty}>>());
+"
+            )
+            .unwrap();
+        }
+    }
+}
+
 pub fn compute_var_infos(
     vars: &[isize],
     components: &[Component],
     relations: &[Relation],
     opt_components: &[(String, isize, usize)],
 ) -> Vec<VarInfo> {
-    assert_ne!(
-        0,
-        components.len() + relations.len(),
-        "A query needs have at least one Component or Relation."
-    );
     assert_ne!(
         0,
         vars.len(),
-        "A query needs to have at least one Variable."
+        "Internal: a query needs to have at least one Variable."
     );
 
     let mut infos = Vec::new();
@@ -199,8 +627,10 @@ pub fn compute_var_infos(
             opt_components: Vec::new(),
             relation_helpers: Vec::new(),
             join_helper_index: None,
+            check_archetype: false,
             init_rank: None,
             unrelation_helpers: Vec::new(),
+            predicates: Vec::new(),
         };
         // component
         let mut dedup = HashSet::new();
@@ -250,6 +680,7 @@ pub fn compute_var_infos(
     return infos;
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn generate_archetype_sets(
     result: &mut String,
     vars: &[isize],
@@ -258,137 +689,394 @@ pub fn generate_archetype_sets(
     relations: &[Relation],
     uncomponents: &[Component],
     unrelations: &[Unrelation], // only care about unrelations with anyvars here
+    ors: &[Or],
 ) {
     assert_ne!(
         0,
-        components.len() + relations.len(),
-        "A query needs have at least one Component or Relation."
+        components.len() + relations.len() + ors.len(),
+        "Internal: a query needs have at least one Component or Relation."
     );
     assert_ne!(
         0,
         vars.len(),
-        "A query needs to have at least one Variable."
+        "Internal: a query needs to have at least one Variable."
     );
 
     for var in vars {
-        write!(result, "let components_{var} = [").unwrap();
-        // component
-        let mut dedup = HashSet::new();
-        for (ty, _) in components.iter().filter(|(_, id)| id == var) {
-            if dedup.contains(&ty) {
-                continue;
-            }
-            dedup.insert(ty);
-
-            write!(result, "\n    world.get_component_id::<{ty}>(),").unwrap();
-        }
+        generate_component_array(result, *var, components, relations);
+    }
 
-        // relation from
-        dedup.clear();
-        for (ty, _, _) in relations.iter().filter(|(_, id, _)| id == var) {
-            if dedup.contains(&ty) {
+    let has_uncomponents = !(uncomponents.is_empty() && unrelations.is_empty());
+    if has_uncomponents {
+        for var in vars {
+            if prefills.contains_key(var) {
                 continue;
             }
-            dedup.insert(ty);
 
+            generate_uncomponent_array(result, *var, uncomponents, unrelations);
+        }
+    }
+    result.push_str("let archetype_id_sets = [\n");
+    for var in vars {
+        let without = if has_uncomponents && !prefills.contains_key(var) {
+            format!("&uncomponents_{var}")
+        } else {
+            "&[]".to_string()
+        };
+        let any_of = archetype_ors(ors, *var);
+        if !any_of.is_empty() {
+            // also needed for invars, to check their archetype
+            let any_of = generate_any_of(&any_of);
             write!(
                 result,
-                "\n    bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()),"
+                "    __froql_bk.matching_archetypes_any(&components_{var}, {without}, {any_of}),\n"
             )
             .unwrap();
-        }
-
-        // relation to
-        dedup.clear();
-        for (ty, _, _) in relations.iter().filter(|(_, _, id)| id == var) {
-            if dedup.contains(&ty) {
-                continue;
-            }
-            dedup.insert(ty);
-
-            result.push_str("\n    ");
+        } else if prefills.contains_key(var) {
+            // don't need this for prefills
+            result.push_str("    Vec::<::froql::archetype::ArchetypeId>::new(),\n");
+        } else {
             write!(
                 result,
-                "bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()).flip_target(),"
+                "    __froql_bk.matching_archetypes(&components_{var}, {without}),\n"
             )
             .unwrap();
         }
-        result.push_str("\n];\n\n");
     }
+    result.push_str("];\n\n");
+}
 
-    if uncomponents.is_empty() && unrelations.is_empty() {
-        result.push_str("let archetype_id_sets = [\n");
-        for var in vars {
-            if prefills.contains_key(var) {
-                // don't need this for prefills
-                result.push_str("    Vec::<::froql::archetype::ArchetypeId>::new(),\n");
-            } else {
-                write!(
+/// OR terms between two variables need a join per branch,
+/// the other OR terms only filter archetypes
+pub fn is_join_or((_, var, other, _): &Or) -> bool {
+    matches!(other, Some(other) if *var != ANYVAR && *other != ANYVAR)
+}
+
+/// indexes of the OR terms that filter the archetypes of `var`
+fn archetype_ors(ors: &[Or], var: isize) -> Vec<usize> {
+    ors.iter()
+        .filter(|or| !is_join_or(or))
+        .filter(|(_, a, b, _)| *a == var || *b == Some(var))
+        .map(|(_, _, _, index)| *index)
+        .collect()
+}
+
+fn generate_any_of(any_of: &[usize]) -> String {
+    let groups: Vec<String> = any_of.iter().map(|i| format!("&or_{i}[..]")).collect();
+    format!("&[{}]", groups.join(", "))
+}
+
+/// one array with the component ids of the branches for every OR term that filters archetypes
+pub fn generate_or_arrays(result: &mut String, ors: &[Or]) {
+    for (types, var, other, index) in ors.iter().filter(|or| !is_join_or(or)) {
+        write!(result, "let or_{index} = [").unwrap();
+        for ty in types {
+            match other {
+                None => write!(result, "\n    __froql_world.get_component_id::<{ty}>(),"),
+                Some(_) if *var != ANYVAR => write!(
+                    result,
+                    "\n    __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()),"
+                ),
+                Some(_) => write!(
                     result,
-                    "    bk.matching_archetypes(&components_{var}, &[]),\n"
-                )
-                .unwrap();
+                    "\n    __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()).flip_target(),"
+                ),
             }
+            .unwrap();
         }
-        result.push_str("];\n\n");
-    } else {
-        for var in vars {
-            if prefills.contains_key(var) {
-                continue;
-            }
+        result.push_str("\n];\n");
+    }
+}
 
-            write!(result, "let uncomponents_{var} = [").unwrap();
+/// binds the closures of `where` terms, coerced to take a reference to their component
+pub fn generate_predicates(result: &mut String, predicates: &[(String, isize, String)]) {
+    for (nr, (ty, _, closure)) in predicates.iter().enumerate() {
+        writeln!(
+            result,
+            "let __froql_pred_{nr} = ::froql::query_helper::predicate::<{ty}>({closure});"
+        )
+        .unwrap();
+    }
+}
 
-            // component
-            for (ty, _) in uncomponents.iter().filter(|(_, id)| id == var) {
-                write!(result, "\n    world.get_component_id::<{ty}>(),").unwrap();
-            }
+/// like `generate_predicates`, but the closures can be shared by several row iterators
+pub fn generate_shared_predicates(result: &mut String, predicates: &[(String, isize, String)]) {
+    for (nr, (ty, _, closure)) in predicates.iter().enumerate() {
+        writeln!(
+            result,
+            "let __froql_pred_{nr} = ::std::rc::Rc::new(::froql::query_helper::predicate::<{ty}>({closure}));"
+        )
+        .unwrap();
+    }
+}
 
-            // unrelations from var to anyvar
-            for (ty, _, _, _) in unrelations
-                .iter()
-                .filter(|(_, id, any, _)| *any == ANYVAR && id == var)
-            {
-                write!(
-                    result,
-                    "\n    bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()),"
-                )
-                .unwrap();
-            }
+/// each row iterator takes its own handle to the predicates from `generate_shared_predicates`
+fn generate_predicate_clones(result: &mut String, predicates: &[(String, isize, String)]) {
+    for nr in 0..predicates.len() {
+        writeln!(
+            result,
+            "let __froql_pred_{nr} = ::std::rc::Rc::clone(&__froql_pred_{nr});"
+        )
+        .unwrap();
+    }
+}
 
-            // unrelations from anyvar to var
-            for (ty, _, _, _) in unrelations
-                .iter()
-                .filter(|(_, any, id, _)| *any == ANYVAR && id == var)
-            {
-                result.push_str("\n    ");
-                write!(
-                    result,
-                    "bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()).flip_target(),"
-                )
-                .unwrap();
-            }
+fn generate_component_array(
+    result: &mut String,
+    var: isize,
+    components: &[Component],
+    relations: &[Relation],
+) {
+    write!(result, "let components_{var} = [").unwrap();
+    // component
+    let mut dedup = HashSet::new();
+    for (ty, _) in components.iter().filter(|(_, id)| *id == var) {
+        if dedup.contains(&ty) {
+            continue;
+        }
+        dedup.insert(ty);
+
+        write!(result, "\n    __froql_world.get_component_id::<{ty}>(),").unwrap();
+    }
 
-            result.push_str("\n];\n\n");
+    // relation from
+    dedup.clear();
+    for (ty, _, _) in relations.iter().filter(|(_, id, _)| *id == var) {
+        if dedup.contains(&ty) {
+            continue;
         }
-        result.push_str("let archetype_id_sets = [\n");
-        for var in vars {
-            if prefills.contains_key(var) {
-                // don't need this for prefills
-                write!(
-                    result,
-                    "    Vec::<::froql::archetype::ArchetypeId>::new(),\n"
-                )
-                .unwrap();
-            } else {
-                write!(
+        dedup.insert(ty);
+
+        write!(
+            result,
+            "\n    __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()),"
+        )
+        .unwrap();
+    }
+
+    // relation to
+    dedup.clear();
+    for (ty, _, _) in relations.iter().filter(|(_, _, id)| *id == var) {
+        if dedup.contains(&ty) {
+            continue;
+        }
+        dedup.insert(ty);
+
+        result.push_str("\n    ");
+        write!(
+            result,
+            "__froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()).flip_target(),"
+        )
+        .unwrap();
+    }
+    result.push_str("\n];\n\n");
+}
+
+fn generate_uncomponent_array(
+    result: &mut String,
+    var: isize,
+    uncomponents: &[Component],
+    unrelations: &[Unrelation],
+) {
+    write!(result, "let uncomponents_{var} = [").unwrap();
+
+    // component
+    for (ty, _) in uncomponents.iter().filter(|(_, id)| *id == var) {
+        write!(result, "\n    __froql_world.get_component_id::<{ty}>(),").unwrap();
+    }
+
+    // unrelations from var to anyvar
+    for (ty, _, _, _) in unrelations
+        .iter()
+        .filter(|(_, id, any, _)| *any == ANYVAR && *id == var)
+    {
+        write!(
+            result,
+            "\n    __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()),"
+        )
+        .unwrap();
+    }
+
+    // unrelations from anyvar to var
+    for (ty, _, _, _) in unrelations
+        .iter()
+        .filter(|(_, any, id, _)| *any == ANYVAR && *id == var)
+    {
+        result.push_str("\n    ");
+        write!(
+            result,
+            "__froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()).flip_target(),"
+        )
+        .unwrap();
+    }
+
+    result.push_str("\n];\n\n");
+}
+
+/// like `generate_archetype_sets`, but the component arrays and archetype sets
+/// are kept in a thread local cache at the call site
+#[allow(clippy::too_many_arguments)]
+pub fn generate_cached_archetype_sets(
+    result: &mut String,
+    vars: &[isize],
+    prefills: &HashMap<isize, String>,
+    components: &[Component],
+    relations: &[Relation],
+    uncomponents: &[Component],
+    unrelations: &[Unrelation],
+    ors: &[Or],
+) {
+    result.push_str(
+        "
+thread_local! {
+    static QUERY_CACHE: ::std::cell::RefCell<::froql::query_cache::QueryCache> =
+        const { ::std::cell::RefCell::new(::froql::query_cache::QueryCache::new()) };
+}
+let (",
+    );
+    for var in vars {
+        write!(result, "components_{var}, ").unwrap();
+    }
+    result.push_str(
+        "archetype_id_sets) = QUERY_CACHE.with_borrow_mut(|cache| {
+cache.update(__froql_bk, &[",
+    );
+    // generic functions share the cache between instantiations
+    let mut types: Vec<&str> = Vec::new();
+    let all_types = (components.iter().map(|(ty, _)| ty))
+        .chain(relations.iter().map(|(ty, _, _)| ty))
+        .chain(uncomponents.iter().map(|(ty, _)| ty))
+        .chain(unrelations.iter().map(|(ty, _, _, _)| ty))
+        .chain(ors.iter().flat_map(|(tys, _, _, _)| tys));
+    for ty in all_types {
+        if !types.contains(&ty.as_str()) {
+            types.push(ty);
+        }
+    }
+    for ty in types {
+        write!(result, "\n    ::std::any::TypeId::of::<{ty}>(),").unwrap();
+    }
+    result.push_str("\n], |cache| {\n");
+    for var in vars {
+        generate_component_array(result, *var, components, relations);
+        let any_of = archetype_ors(ors, *var);
+        if prefills.contains_key(var) && any_of.is_empty() {
+            write!(result, "cache.add_invar(&components_{var});\n").unwrap();
+        } else if prefills.contains_key(var) {
+            // invars with OR terms check their archetype
+            let any_of = generate_any_of(&any_of);
+            write!(result, "cache.add_var(&components_{var}, &[], {any_of});\n").unwrap();
+        } else {
+            generate_uncomponent_array(result, *var, uncomponents, unrelations);
+            let any_of = generate_any_of(&any_of);
+            write!(
+                result,
+                "cache.add_var(&components_{var}, &uncomponents_{var}, {any_of});\n"
+            )
+            .unwrap();
+        }
+    }
+    result.push_str("});\n(\n");
+    for var in vars {
+        write!(result, "    cache.components({var}),\n").unwrap();
+    }
+    result.push_str("    [");
+    for var in vars {
+        write!(result, "cache.archetype_ids({var}), ").unwrap();
+    }
+    result.push_str("],\n)\n});\n\n");
+}
+
+/// the arrays of the OR terms that `generate_entity_accessors` needs for the matched branch
+fn generate_branch_arrays(result: &mut String, accessors: &[Accessor], ors: &[Or]) {
+    let needed: Vec<Or> = (ors.iter())
+        .filter(|or| !is_join_or(or))
+        .filter(|(_, _, _, index)| {
+            (accessors.iter()).any(|it| matches!(it, Accessor::Branch(i, _) if i == index))
+        })
+        .cloned()
+        .collect();
+    if !needed.is_empty() {
+        result.push_str("let __froql_bk = &__froql_world.bookkeeping;\n");
+        generate_or_arrays(result, &needed);
+    }
+}
+
+/// Maps rows of `(entities, branches)` to the result tuple, by looking up every component by entity.
+/// `entities` has one entity per variable, `branches` the matched branch of every OR relation
+/// between two variables.
+fn generate_entity_accessors(result: &mut String, accessors: &[Accessor], ors: &[Or]) {
+    let join_ors: Vec<usize> = (ors.iter())
+        .filter(|or| is_join_or(or))
+        .map(|(_, _, _, index)| *index)
+        .collect();
+    let uses_entities = accessors.iter().any(|it| match it {
+        Accessor::Singleton(_) | Accessor::SingletonMut(_) => false,
+        Accessor::Branch(index, _) => !join_ors.contains(index),
+        _ => true,
+    });
+    let uses_branches = (accessors.iter())
+        .any(|it| matches!(it, Accessor::Branch(index, _) if join_ors.contains(index)));
+    let entities = if uses_entities { "entities" } else { "_" };
+    let branches = if uses_branches { "branches" } else { "_" };
+    write!(result, ".map(move |({entities}, {branches})| (").unwrap();
+    for accessor in accessors {
+        match accessor {
+            Accessor::Component(ty, var) => write!(
+                result,
+                "\n    __froql_world.get_component::<{ty}>(entities[{var}]),"
+            ),
+            Accessor::ComponentMut(ty, var) => write!(
+                result,
+                "\n    __froql_world.get_component_mut::<{ty}>(entities[{var}]),"
+            ),
+            Accessor::OutVar(var) => write!(
+                result,
+                "\n    ::froql::entity_view_deferred::EntityViewDeferred::new(__froql_world, entities[{var}]),"
+            ),
+            Accessor::OptComponent(ty, var, _) => write!(
+                result,
+                "\n    __froql_world.get_component_opt::<{ty}>(entities[{var}]),"
+            ),
+            Accessor::OptMutComponent(ty, var, _) => write!(
+                result,
+                "\n    __froql_world.get_component_mut_opt::<{ty}>(entities[{var}]),"
+            ),
+            Accessor::Singleton(ty) => write!(result, "\n    __froql_world.singleton::<{ty}>(),"),
+            Accessor::SingletonMut(ty) => write!(result, "\n    __froql_world.singleton_mut::<{ty}>(),"),
+            Accessor::Branch(index, var) => match join_ors.iter().position(|it| it == index) {
+                Some(position) => write!(result, "\n    branches[{position}],"),
+                None => write!(
                     result,
-                    "    bk.matching_archetypes(&components_{var}, &uncomponents_{var}),\n"
-                )
-                .unwrap();
+                    "\n    ::froql::query_helper::first_branch(__froql_world, entities[{var}], &or_{index}),"
+                ),
+            },
+            Accessor::Aggregate(..) => unreachable!("Internal: aggregates are grouped before"),
+            Accessor::Hops(..) => unreachable!("Internal: hops are only known while traversing"),
+            Accessor::OptOutVar(_) => {
+                unreachable!("Internal: rows with optional relations are not collected")
             }
         }
-        result.push_str("];\n\n");
+        .unwrap();
+    }
+    result.push_str("\n))");
+}
+
+/// sums up the entities of the matching archetypes of a single variable query
+pub fn generate_archetype_count(result: &mut String, output: Output) {
+    match output {
+        Output::Count => result.push_str(
+            "archetype_id_sets[0]
+    .iter()
+    .map(|id| __froql_bk.archetypes[id.as_index()].entities.len())
+    .sum::<usize>()",
+        ),
+        Output::Exists => result.push_str(
+            "archetype_id_sets[0]
+    .iter()
+    .any(|id| !__froql_bk.archetypes[id.as_index()].entities.is_empty())",
+        ),
+        Output::Rows => unreachable!("rows are yielded by the query closure"),
     }
 }
 
@@ -405,7 +1093,7 @@ pub fn generate_fsm_context(
         "
 // result set
 const VAR_COUNT: usize = {var_count};
-let mut a_refs = [&bk.archetypes[0]; VAR_COUNT];
+let mut a_refs = [&__froql_bk.archetypes[0]; VAR_COUNT];
 let mut a_rows = [::froql::archetype::ArchetypeRow(u32::MAX); VAR_COUNT];
 
 // general context for statemachine
@@ -458,6 +1146,9 @@ pub fn generate_resumable_query_closure(
                             opt_components: info.opt_components.clone(),
                             relation_helpers: info.relation_helpers.clone(),
                             unrelation_helpers: info.unrelation_helpers.clone(),
+                            check_archetype: info.check_archetype,
+                            check_alive: is_type_entity(&prefills[&info.index]),
+                            predicates: info.predicates.clone(),
                         })
                         .collect(),
                 }
@@ -473,6 +1164,7 @@ pub fn generate_resumable_query_closure(
                     relation_helpers: first_info.relation_helpers.clone(),
                     unrelation_helpers: first_info.unrelation_helpers.clone(),
                     checks,
+                    predicates: first_info.predicates.clone(),
                 }
                 .generate(step_count, prepend, &mut append);
             }
@@ -496,6 +1188,17 @@ pub fn generate_resumable_query_closure(
                         .join_helper_index
                         .expect("Internal: RelationHelper needs to exist for Join"),
                     new_unrelation_helpers: new_info.unrelation_helpers.clone(),
+                    predicates: new_info.predicates.clone(),
+                }
+                .generate(step_count, prepend, &mut append);
+            }
+            JoinKind::OptionalJoin(NewOptionalJoin { new, helper_nr }) => {
+                let new_info = &infos[new as usize];
+                step_count = OptionalJoin {
+                    new,
+                    new_components: new_info.component_range.clone(),
+                    opt_components: new_info.opt_components.clone(),
+                    helper_nr,
                 }
                 .generate(step_count, prepend, &mut append);
             }
@@ -522,6 +1225,64 @@ mod test {
     use super::*;
     use crate::Accessor;
 
+    #[test]
+    fn test_generate_cached_archetype_sets() {
+        let components = vec![("Unit".into(), 0), ("Unit".into(), 1)];
+        let relations = vec![("Attack".into(), 1, 0)];
+        let uncomponents = vec![("Bird".into(), 0)];
+        let vars = vec![0, 1];
+        let mut result = String::new();
+        let mut prefills = HashMap::new();
+        prefills.insert(1, "player".to_string());
+
+        generate_cached_archetype_sets(
+            &mut result,
+            &vars,
+            &prefills,
+            &components,
+            &relations,
+            &uncomponents,
+            &[],
+            &[],
+        );
+
+        insta::assert_snapshot!(result, @r#"
+        thread_local! {
+            static QUERY_CACHE: ::std::cell::RefCell<::froql::query_cache::QueryCache> =
+                const { ::std::cell::RefCell::new(::froql::query_cache::QueryCache::new()) };
+        }
+        let (components_0, components_1, archetype_id_sets) = QUERY_CACHE.with_borrow_mut(|cache| {
+        cache.update(__froql_bk, &[
+            ::std::any::TypeId::of::<Unit>(),
+            ::std::any::TypeId::of::<Attack>(),
+            ::std::any::TypeId::of::<Bird>(),
+        ], |cache| {
+        let components_0 = [
+            __froql_world.get_component_id::<Unit>(),
+            __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()).flip_target(),
+        ];
+
+        let uncomponents_0 = [
+            __froql_world.get_component_id::<Bird>(),
+        ];
+
+        cache.add_var(&components_0, &uncomponents_0, &[]);
+        let components_1 = [
+            __froql_world.get_component_id::<Unit>(),
+            __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()),
+        ];
+
+        cache.add_invar(&components_1);
+        });
+        (
+            cache.components(0),
+            cache.components(1),
+            [cache.archetype_ids(0), cache.archetype_ids(1), ],
+        )
+        });
+        "#);
+    }
+
     #[test]
     fn test_generate_archetype_id_sets_relation() {
         let components = vec![("Unit".into(), 0), ("Health".into(), 0), ("Unit".into(), 1)];
@@ -540,34 +1301,35 @@ mod test {
             &relations,
             &uncomponents,
             &[],
+            &[],
         );
 
-        insta::assert_snapshot!(result, @r"
+        insta::assert_snapshot!(result, @r#"
         let components_0 = [
-            world.get_component_id::<Unit>(),
-            world.get_component_id::<Health>(),
-            bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()).flip_target(),
+            __froql_world.get_component_id::<Unit>(),
+            __froql_world.get_component_id::<Health>(),
+            __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()).flip_target(),
         ];
 
         let components_1 = [
-            world.get_component_id::<Unit>(),
-            bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()),
+            __froql_world.get_component_id::<Unit>(),
+            __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()),
         ];
 
         let uncomponents_0 = [
-            world.get_component_id::<Bird>(),
-            world.get_component_id::<Fish>(),
+            __froql_world.get_component_id::<Bird>(),
+            __froql_world.get_component_id::<Fish>(),
         ];
 
         let uncomponents_1 = [
-            world.get_component_id::<Bird>(),
+            __froql_world.get_component_id::<Bird>(),
         ];
 
         let archetype_id_sets = [
-            bk.matching_archetypes(&components_0, &uncomponents_0),
-            bk.matching_archetypes(&components_1, &uncomponents_1),
+            __froql_bk.matching_archetypes(&components_0, &uncomponents_0),
+            __froql_bk.matching_archetypes(&components_1, &uncomponents_1),
         ];
-        ");
+        "#);
 
         insta::assert_debug_snapshot!(infos, @r#"
         [
@@ -611,8 +1373,9 @@ mod test {
         ]
         "#);
 
-        let join_order = JoinOrderComputer::new(&relations, &mut infos, &prefills, &[], &[])
-            .compute_join_order();
+        let join_order =
+            JoinOrderComputer::new(&relations, &mut infos, &prefills, &[], &[], &[], &[], false)
+                .compute_join_order();
         insta::assert_debug_snapshot!(join_order, @r#"
         [
             InitVar(
@@ -637,8 +1400,17 @@ mod test {
         "#);
 
         let unequals = vec![(0, 1)];
-        let join_order = JoinOrderComputer::new(&relations, &mut infos, &prefills, &unequals, &[])
-            .compute_join_order();
+        let join_order = JoinOrderComputer::new(
+            &relations,
+            &mut infos,
+            &prefills,
+            &unequals,
+            &[],
+            &[],
+            &[],
+            false,
+        )
+        .compute_join_order();
         insta::assert_debug_snapshot!(join_order, @r#"
         [
             InitVar(
@@ -677,10 +1449,10 @@ mod test {
         insta::assert_snapshot!({
             generate_invar_captures(&mut result, &prefills);
             result
-        }, @r"
-        let invar_1: ::froql::entity_store::Entity = (&player).into();
-        let invar_2: ::froql::entity_store::Entity = (&somebody).into();
-        ");
+        }, @r#"
+        let __froql_invar_1: ::froql::entity_store::Entity = (&player).into();
+        let __froql_invar_2: ::froql::entity_store::Entity = (&somebody).into();
+        "#);
 
         let mut result = String::new();
         let mut prefills = HashMap::new();
@@ -688,7 +1460,7 @@ mod test {
         insta::assert_snapshot!({
             generate_invar_captures(&mut result, &prefills);
             result
-        }, @"let invar_1: ::froql::entity_store::Entity = (&player).into();");
+        }, @r#"let __froql_invar_1: ::froql::entity_store::Entity = (&player).into();"#);
 
         // empty
         let mut result = String::new();
@@ -700,30 +1472,66 @@ mod test {
             },
             ""
         );
+
+        // type entities are captured after the world
+        let mut prefills = HashMap::new();
+        prefills.insert(1, "player".to_string());
+        prefills.insert(2, "@Apples".to_string());
+        let mut result = String::new();
+        generate_invar_captures(&mut result, &prefills);
+        result.push_str("---\n");
+        generate_type_entity_captures(&mut result, &prefills);
+        insta::assert_snapshot!(result, @r#"
+        let __froql_invar_1: ::froql::entity_store::Entity = (&player).into();
+        ---
+        let __froql_invar_2: ::froql::entity_store::Entity = __froql_bk.type_entity_or_dead(::std::any::TypeId::of::<::std::cell::RefCell<Apples>>());
+        "#);
     }
 
     #[test]
-    fn test_generate_result_set() {
-        let components = vec![("Unit".into(), 0), ("Health".into(), 0), ("Unit".into(), 1)];
-        let relations = vec![("Attack".into(), 1, 0)];
-        let vars = vec![0, 1];
-        let mut result = String::new();
+    fn test_generate_count() {
+        let generator = Generator {
+            vars: vec![0],
+            components: vec![("Unit".into(), 0)],
+            relations: vec![("Attack".into(), 0, ANYVAR)],
+            output: Output::Count,
+            ..Default::default()
+        };
+        insta::assert_snapshot!(generator.generate("world"), @r#"
+        {
 
-        insta::assert_snapshot!({
-            generate_fsm_context(&mut result, &vars, &components, &relations);
-            result
-        }, @r#"
-        // result set
-        const VAR_COUNT: usize = 2;
-        let mut a_refs = [&bk.archetypes[0]; VAR_COUNT];
-        let mut a_rows = [::froql::archetype::ArchetypeRow(u32::MAX); VAR_COUNT];
-
-        // general context for statemachine
-        let mut current_step = 0;
-        let mut a_max_rows = [0; VAR_COUNT];
-        let mut a_next_indexes = [usize::MAX; VAR_COUNT];
-        let mut col_indexes = [usize::MAX; 5];
+        use ::froql::query_helper::AsWorld as _;
+        let __froql_world: &::froql::world::World = (world).as_world();
+        let __froql_bk = &__froql_world.bookkeeping;
+        let components_0 = [
+            __froql_world.get_component_id::<Unit>(),
+            __froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<Attack>>()),
+        ];
+
+        let archetype_id_sets = [
+            __froql_bk.matching_archetypes(&components_0, &[]),
+        ];
+
+        archetype_id_sets[0]
+            .iter()
+            .map(|id| __froql_bk.archetypes[id.as_index()].entities.len())
+            .sum::<usize>()
+        }
         "#);
+
+        // two variables need to visit every row
+        let generator = Generator {
+            vars: vec![0, 1],
+            components: vec![("Unit".into(), 0)],
+            relations: vec![("Attack".into(), 0, 1)],
+            output: Output::Exists,
+            ..Default::default()
+        };
+        assert!(
+            generator
+                .generate("world")
+                .ends_with(".next().is_some()\n}")
+        );
     }
 
     #[test]
@@ -1,9 +1,9 @@
 use std::collections::HashMap;
 
 use crate::{
-    ANYVAR, Relation, Unrelation,
+    ANYVAR, OptRelation, Path, Relation, Unrelation,
     generator::nodes::{
-        relation_helper::{RelationHelperInfo, UnrelationHelperInfo},
+        relation_helper::{PathInfo, RelationHelperInfo, UnrelationHelperInfo},
         types::{RelationConstraint, UnrelationConstraint},
     },
 };
@@ -15,6 +15,7 @@ pub enum JoinKind {
     InitInvars(InitInvars),
     InitVar(InitVar),
     InnerJoin(NewJoin),
+    OptionalJoin(NewOptionalJoin),
 }
 
 #[derive(Debug)]
@@ -53,25 +54,42 @@ pub struct NewJoin {
     pub unrel_constraints: Vec<UnrelationConstraint>,
 }
 
+#[derive(Debug)]
+pub struct NewOptionalJoin {
+    pub new: isize,
+    /// nr of the optional UnrelationHelperInfo of the old var
+    pub helper_nr: usize,
+}
+
 pub struct JoinOrderComputer<'a> {
     infos: &'a mut [VarInfo],
     prefills: &'a HashMap<isize, String>,
     relations_left: Vec<Relation>,
+    /// path terms are joined like relations, by a RelationHelper that traverses
+    paths_left: Vec<Path>,
     unequals: Vec<(isize, isize)>,
     unrelations_left: Vec<Unrelation>,
+    /// joined after every other variable, nothing else depends on them
+    opt_relations: &'a [OptRelation],
     available: Vec<isize>,
     result: Vec<JoinKind>,
     init_rank: u32,
     relation_helper_nr: usize,
+    /// unconnected variables get their own archetype scan instead of panicking
+    cross_join: bool,
 }
 
 impl<'a> JoinOrderComputer<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         relations: &'a [Relation],
         infos: &'a mut [VarInfo],
         prefills: &'a HashMap<isize, String>,
         unequals: &'a [(isize, isize)],
         unrelations: &'a [Unrelation],
+        opt_relations: &'a [OptRelation],
+        paths: &'a [Path],
+        cross_join: bool,
     ) -> Self {
         let work_left: Vec<Relation> = relations
             .iter()
@@ -87,7 +105,9 @@ impl<'a> JoinOrderComputer<'a> {
             .collect();
         Self {
             relations_left: work_left,
+            paths_left: Vec::from(paths),
             unrelations_left,
+            opt_relations,
             infos,
             prefills,
             unequals: Vec::from(unequals),
@@ -95,6 +115,7 @@ impl<'a> JoinOrderComputer<'a> {
             result: Vec::new(),
             init_rank: 0,
             relation_helper_nr: 0,
+            cross_join,
         }
     }
 
@@ -115,6 +136,7 @@ impl<'a> JoinOrderComputer<'a> {
             let invar_rel_constraints = newly_available_constraints(
                 &self.available,
                 &mut self.relations_left,
+                &mut self.paths_left,
                 self.infos,
                 &mut self.relation_helper_nr,
             );
@@ -132,10 +154,11 @@ impl<'a> JoinOrderComputer<'a> {
 
         // compute join
         let mut join_count = 0;
-        while !self.compute_inner_joins() || self.available.len() != self.infos.len() {
+        let required_count = self.infos.len() - self.opt_relations.len();
+        while !self.compute_inner_joins() || self.available.len() != required_count {
             join_count += 1;
-            if join_count > 1 {
-                panic!("Cross joins are not supported. Use nested queries instead.");
+            if join_count > 1 && !self.cross_join {
+                panic!("Internal: cross joins are rejected before the join order is computed.");
             }
 
             // I think its a decent metric to use the most constrained variable first
@@ -143,6 +166,7 @@ impl<'a> JoinOrderComputer<'a> {
                 .infos
                 .iter_mut()
                 .filter(|it| !self.available.contains(&it.index))
+                .filter(|it| !self.opt_relations.iter().any(|opt| opt.3 == it.index))
                 .max_by_key(|it| it.component_range.len())
                 .expect("Internal: first join init unwrap");
             first.init_rank = Some(self.init_rank);
@@ -164,15 +188,36 @@ impl<'a> JoinOrderComputer<'a> {
             self.result.push(JoinKind::InitVar(InitVar { var, checks }));
         }
 
+        for (nr, (ty, from, to, new)) in self.opt_relations.iter().enumerate() {
+            let old = if from == new { *to } else { *from };
+            self.infos[old as usize]
+                .unrelation_helpers
+                .push(UnrelationHelperInfo {
+                    ty: ty.clone(),
+                    flip_target: old == *to,
+                    old_var: old,
+                    new_var: *new,
+                    nr,
+                    optional: true,
+                });
+            self.infos[*new as usize].init_rank = Some(self.init_rank);
+            self.init_rank += 1;
+            self.available.push(*new);
+            self.result.push(JoinKind::OptionalJoin(NewOptionalJoin {
+                new: *new,
+                helper_nr: nr,
+            }));
+        }
+
         assert_eq!(
             self.available.len(),
             self.infos.len(),
-            "Not all variables were joined."
+            "Internal: not all variables were joined."
         );
         assert!(self.unequals.is_empty());
         assert!(
             self.unrelations_left.is_empty(),
-            "Not all unrelations were inserted.\n{:#?}\n{:#?}",
+            "Internal: not all unrelations were inserted.\n{:#?}\n{:#?}",
             self.unrelations_left,
             self.result,
         );
@@ -191,6 +236,7 @@ impl<'a> JoinOrderComputer<'a> {
             rel_constraints: newly_available_constraints(
                 &self.available,
                 &mut self.relations_left,
+                &mut self.paths_left,
                 self.infos,
                 &mut self.relation_helper_nr,
             ),
@@ -218,34 +264,70 @@ impl<'a> JoinOrderComputer<'a> {
 
     /// returns true when done
     fn compute_inner_joins(&mut self) -> bool {
-        while !self.relations_left.is_empty() {
+        while !self.relations_left.is_empty() || !self.paths_left.is_empty() {
             // find next variable for joining and remove it from working list
             // always handle constraints first, because it may let us skip work
             // when we are executing the query at runtime
+            let is_next = |from: isize, to: isize| {
+                self.available
+                    .iter()
+                    .any(|avail| *avail == from || *avail == to)
+            };
             let next_join = {
-                let pos = self.relations_left.iter().position(|rel| {
-                    self.available
-                        .iter()
-                        .any(|avail| *avail == rel.1 || *avail == rel.2)
-                });
-                pos.map(|pos| self.relations_left.remove(pos))
+                let pos = self
+                    .relations_left
+                    .iter()
+                    .position(|rel| is_next(rel.1, rel.2));
+                let path_pos = self
+                    .paths_left
+                    .iter()
+                    .position(|path| is_next(path.1, path.2));
+                match (pos, path_pos) {
+                    (Some(pos), _) => {
+                        let (ty, from, to) = self.relations_left.remove(pos);
+                        Some((ty, from, to, None))
+                    }
+                    (None, Some(pos)) => {
+                        let (ty, from, to, min, max) = self.paths_left.remove(pos);
+                        Some((ty, from, to, Some((min, max))))
+                    }
+                    (None, None) => None,
+                }
             };
-            if let Some(join) = next_join {
-                let reversed = self.available.iter().any(|avail| *avail == join.2);
-                let old_var = if reversed { join.2 } else { join.1 };
-                let new_var = if reversed { join.1 } else { join.2 };
+            if let Some((ty, from, to, hops)) = next_join {
+                let reversed = self.available.contains(&to);
+                let old_var = if reversed { to } else { from };
+                let new_var = if reversed { from } else { to };
                 let old_info = &mut self.infos[old_var as usize];
                 assert_eq!(old_var, old_info.index);
-                let column_index = old_info.related_with[&(join.0, new_var)];
-                let cid_index = column_index - old_info.component_range.start;
-
-                old_info.relation_helpers.push(RelationHelperInfo {
-                    column_index,
-                    old_var,
-                    new_var,
-                    nr: self.relation_helper_nr,
-                    cid_index,
-                });
+                let helper = match hops {
+                    Some((min, max)) => RelationHelperInfo {
+                        column_index: 0,
+                        old_var,
+                        new_var,
+                        nr: self.relation_helper_nr,
+                        cid_index: 0,
+                        path: Some(PathInfo {
+                            ty,
+                            flip_target: reversed,
+                            min,
+                            max,
+                        }),
+                    },
+                    None => {
+                        let column_index = old_info.related_with[&(ty, new_var)];
+                        let cid_index = column_index - old_info.component_range.start;
+                        RelationHelperInfo {
+                            column_index,
+                            old_var,
+                            new_var,
+                            nr: self.relation_helper_nr,
+                            cid_index,
+                            path: None,
+                        }
+                    }
+                };
+                old_info.relation_helpers.push(helper);
                 let new_info = &mut self.infos[new_var as usize];
                 new_info.join_helper_index = Some(self.relation_helper_nr);
                 new_info.init_rank = Some(self.init_rank);
@@ -258,6 +340,7 @@ impl<'a> JoinOrderComputer<'a> {
                 let mut rel_constraints = newly_available_constraints(
                     &self.available,
                     &mut self.relations_left,
+                    &mut self.paths_left,
                     self.infos,
                     &mut self.relation_helper_nr,
                 );
@@ -289,6 +372,7 @@ impl<'a> JoinOrderComputer<'a> {
 fn newly_available_constraints(
     available: &[isize],
     relations_left: &mut Vec<Relation>,
+    paths_left: &mut Vec<Path>,
     infos: &mut [VarInfo],
     relation_helper_nr: &mut usize,
 ) -> Vec<RelationConstraint> {
@@ -320,9 +404,40 @@ fn newly_available_constraints(
             new_var: new,
             nr: *relation_helper_nr,
             cid_index,
+            path: None,
+        });
+        *relation_helper_nr += 1;
+    }
+    while let Some(index) = paths_left
+        .iter()
+        .position(|(_, a, b, _, _)| available.contains(a) && available.contains(b))
+    {
+        let (ty, a, b, min, max) = paths_left.swap_remove(index);
+        let (old, new, flip_target) =
+            if infos[a as usize].init_rank.unwrap() < infos[b as usize].init_rank.unwrap() {
+                (a, b, false)
+            } else {
+                (b, a, true)
+            };
+        result.push(RelationConstraint {
+            helper_nr: *relation_helper_nr,
+            checked_invar: Some(new), // TODO only set this for actual invars
         });
-        let new_info = &mut infos[new as usize];
-        new_info.join_helper_index = Some(*relation_helper_nr);
+        infos[old as usize]
+            .relation_helpers
+            .push(RelationHelperInfo {
+                column_index: 0,
+                old_var: old,
+                new_var: new,
+                nr: *relation_helper_nr,
+                cid_index: 0,
+                path: Some(PathInfo {
+                    ty,
+                    flip_target,
+                    min,
+                    max,
+                }),
+            });
         *relation_helper_nr += 1;
     }
     return result;
@@ -359,9 +474,8 @@ fn newly_available_unrelations(
             old_var: old,
             new_var: new,
             nr: number,
+            optional: false,
         });
-        let new_info = &mut infos[new as usize];
-        new_info.join_helper_index = Some(number);
     }
     return result;
 }
@@ -389,10 +503,12 @@ mod test {
             &relations,
             &uncomponents,
             &[],
+            &[],
         );
 
-        let join_order = JoinOrderComputer::new(&relations, &mut infos, &prefills, &[], &[])
-            .compute_join_order();
+        let join_order =
+            JoinOrderComputer::new(&relations, &mut infos, &prefills, &[], &[], &[], &[], false)
+                .compute_join_order();
         insta::assert_debug_snapshot!(join_order, @r#"
         [
             InitVar(
@@ -442,9 +558,17 @@ mod test {
 
         let mut infos = compute_var_infos(&vars, &components, &relations, &[]);
 
-        let join_order =
-            JoinOrderComputer::new(&relations, &mut infos, &prefills, &unequals, &unrelations)
-                .compute_join_order();
+        let join_order = JoinOrderComputer::new(
+            &relations,
+            &mut infos,
+            &prefills,
+            &unequals,
+            &unrelations,
+            &[],
+            &[],
+            false,
+        )
+        .compute_join_order();
         insta::assert_debug_snapshot!(join_order, @r#"
         [
             InitInvars(
@@ -2,6 +2,7 @@ use std::fmt::Debug;
 
 pub mod archetype_start;
 pub mod invar_start;
+pub mod optional_join;
 pub mod relation_helper;
 pub mod relation_join;
 pub mod types;
@@ -1,4 +1,5 @@
 use crate::Checks;
+use crate::generator::nodes::types::Predicate;
 
 use super::GeneratorNode;
 use super::relation_helper::{
@@ -17,12 +18,22 @@ pub struct ArchetypeStart {
     pub relation_helpers: Vec<RelationHelperInfo>,
     pub unrelation_helpers: Vec<UnrelationHelperInfo>,
     pub checks: Checks,
+    /// predicates on components of the var
+    pub predicates: Vec<Predicate>,
 }
 
 impl GeneratorNode for ArchetypeStart {
     fn generate(&self, step: usize, prepend: &mut String, append: &mut String) -> usize {
         let first = self.var;
         let Range { start, end } = &self.components;
+        // a cross join starts over for every match of the steps before
+        let exhausted = if step == 0 {
+            "return None;"
+        } else {
+            "*next_index = usize::MAX;
+        current_step -= 1;
+        continue;"
+        };
         write!(
             append,
             "
@@ -33,14 +44,14 @@ impl GeneratorNode for ArchetypeStart {
     let archetype_ids = &archetype_id_sets[CURRENT_VAR];
     *next_index = next_index.wrapping_add(1);
     if *next_index >= archetype_ids.len() {{
-        return None;
+        {exhausted}
     }}
     let next_id = archetype_ids[*next_index];
 
     // gets rolled over to 0 by wrapping_add
     a_rows[CURRENT_VAR] = ::froql::archetype::ArchetypeRow(u32::MAX);
     let a_ref = &mut a_refs[CURRENT_VAR];
-    *a_ref = &bk.archetypes[next_id.as_index()];
+    *a_ref = &__froql_bk.archetypes[next_id.as_index()];
     a_ref.find_multiple_columns(
         &components_{first},
         &mut col_indexes[CURRENT_VAR_COMPONENTS],
@@ -87,7 +98,7 @@ impl GeneratorNode for ArchetypeStart {
         )
         .unwrap();
 
-        if self.checks.is_empty() {
+        if self.checks.is_empty() && self.predicates.is_empty() {
             relation_helpers_set_rows(append, &self.relation_helpers, &self.unrelation_helpers);
 
             write!(
@@ -97,15 +108,18 @@ impl GeneratorNode for ArchetypeStart {
             )
             .unwrap();
         } else {
-            append.push_str(
-                "
+            if !self.checks.is_empty() {
+                append.push_str(
+                    "
         let id = a_refs[CURRENT_VAR].entities[a_rows[CURRENT_VAR].as_index()];",
-            );
+                );
+            }
             insert_checks(
                 append,
                 &self.checks.unequals,
                 &self.checks.rel_constraints,
                 &self.checks.unrel_constraints,
+                &self.predicates,
             );
             append.push_str(
                 "
@@ -150,6 +164,7 @@ mod test {
                 rel_constraints: vec![],
                 unrel_constraints: vec![],
             },
+            predicates: vec![],
         };
 
         let mut prepend = String::new();
@@ -172,7 +187,7 @@ mod test {
             // gets rolled over to 0 by wrapping_add
             a_rows[CURRENT_VAR] = ::froql::archetype::ArchetypeRow(u32::MAX);
             let a_ref = &mut a_refs[CURRENT_VAR];
-            *a_ref = &bk.archetypes[next_id.as_index()];
+            *a_ref = &__froql_bk.archetypes[next_id.as_index()];
             a_ref.find_multiple_columns(
                 &components_0,
                 &mut col_indexes[CURRENT_VAR_COMPONENTS],
@@ -211,6 +226,7 @@ mod test {
                 rel_constraints: vec![],
                 unrel_constraints: vec![],
             },
+            predicates: vec![],
         };
 
         let mut prepend = String::new();
@@ -233,7 +249,7 @@ mod test {
             // gets rolled over to 0 by wrapping_add
             a_rows[CURRENT_VAR] = ::froql::archetype::ArchetypeRow(u32::MAX);
             let a_ref = &mut a_refs[CURRENT_VAR];
-            *a_ref = &bk.archetypes[next_id.as_index()];
+            *a_ref = &__froql_bk.archetypes[next_id.as_index()];
             a_ref.find_multiple_columns(
                 &components_0,
                 &mut col_indexes[CURRENT_VAR_COMPONENTS],
@@ -264,4 +280,80 @@ mod test {
         }
         "#);
     }
+
+    #[test]
+    fn check_predicate() {
+        let generator = ArchetypeStart {
+            var: 0,
+            components: 0..2,
+            opt_components: vec![],
+            relation_helpers: vec![],
+            unrelation_helpers: vec![],
+            checks: Checks {
+                unequals: vec![],
+                rel_constraints: vec![],
+                unrel_constraints: vec![],
+            },
+            predicates: vec![Predicate {
+                nr: 0,
+                ty: "Health".into(),
+                var: 0,
+                column_index: 1,
+            }],
+        };
+
+        let mut prepend = String::new();
+        let mut append = String::new();
+        let r = generator.generate(0, &mut prepend, &mut append);
+        assert_eq!(2, r);
+        assert_eq!(prepend, "");
+        insta::assert_snapshot!(append, @r#"
+        0 => {
+            const CURRENT_VAR: usize = 0;
+            const CURRENT_VAR_COMPONENTS: ::std::ops::Range<usize> = 0..2;
+            let next_index = &mut a_next_indexes[CURRENT_VAR];
+            let archetype_ids = &archetype_id_sets[CURRENT_VAR];
+            *next_index = next_index.wrapping_add(1);
+            if *next_index >= archetype_ids.len() {
+                return None;
+            }
+            let next_id = archetype_ids[*next_index];
+
+            // gets rolled over to 0 by wrapping_add
+            a_rows[CURRENT_VAR] = ::froql::archetype::ArchetypeRow(u32::MAX);
+            let a_ref = &mut a_refs[CURRENT_VAR];
+            *a_ref = &__froql_bk.archetypes[next_id.as_index()];
+            a_ref.find_multiple_columns(
+                &components_0,
+                &mut col_indexes[CURRENT_VAR_COMPONENTS],
+            );
+            a_max_rows[CURRENT_VAR] = a_ref.entities.len() as u32;
+            current_step += 1;
+        }
+
+        // next row in archetype
+        1 => {
+            const CURRENT_VAR: usize = 0;
+            let row_counter = &mut a_rows[CURRENT_VAR].0;
+            let max_row = a_max_rows[CURRENT_VAR];
+            // rolls over to 0 for u32::MAX, which is our start value
+            *row_counter = row_counter.wrapping_add(1);
+
+            if *row_counter >= max_row {
+                current_step -= 1;
+            } else {
+                    if
+                        !__froql_pred_0(&unsafe {
+                            ::froql::query_helper::coerce_cast::<Health>(
+                                __froql_world,
+                                a_refs[0].columns[col_indexes[1]].get(a_rows[0].0)
+                            )
+                        }.borrow())
+                {} else {
+                    current_step += 1;
+                }
+            }
+        }
+        "#);
+    }
 }
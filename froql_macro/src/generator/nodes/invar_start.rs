@@ -5,7 +5,7 @@ use super::{
         relation_helpers_set_rows,
     },
     relation_join::{insert_checks, insert_optional_comps},
-    types::{RelationConstraint, UnrelationConstraint},
+    types::{Predicate, RelationConstraint, UnrelationConstraint},
 };
 use std::{fmt::Write, ops::Range};
 
@@ -17,6 +17,11 @@ pub struct InvarInfo {
     pub opt_components: Vec<(String, usize)>,
     pub relation_helpers: Vec<RelationHelperInfo>,
     pub unrelation_helpers: Vec<UnrelationHelperInfo>,
+    /// the archetype has to be in the archetype set, because of OR terms
+    pub check_archetype: bool,
+    /// `@Type` entities may not exist, then there are no rows
+    pub check_alive: bool,
+    pub predicates: Vec<Predicate>,
 }
 
 #[derive(Debug)]
@@ -53,6 +58,7 @@ impl GeneratorNode for InvarStart {
                 &self.unequalities,
                 &self.rel_constraints,
                 &self.unrel_constraints,
+                &[],
             );
             append.push_str(
                 "
@@ -85,13 +91,38 @@ impl InvarStart {
         for invar in &self.invars {
             let var_index = invar.var_index;
             let Range { start, end } = &invar.component_range;
+            append.push_str("\n{");
+            if invar.check_alive {
+                write!(
+                    append,
+                    "
+    if !__froql_bk.entities.is_alive(__froql_invar_{var_index}) {{
+        return None;
+    }}"
+                )
+                .unwrap();
+            }
+            write!(
+                append,
+                "
+    let (aid, arow) = __froql_bk.entities.get_archetype(__froql_invar_{var_index});"
+            )
+            .unwrap();
+            if invar.check_archetype {
+                write!(
+                    append,
+                    "
+    if !archetype_id_sets[{var_index}].contains(&aid) {{
+        return None;
+    }}"
+                )
+                .unwrap();
+            }
             write!(
                 append,
                 "
-{{
-    let (aid, arow) = bk.entities.get_archetype(invar_{var_index});
     let a_ref = &mut a_refs[{var_index}];
-    *a_ref = &bk.archetypes[aid.as_index()];
+    *a_ref = &__froql_bk.archetypes[aid.as_index()];
     if !a_ref.find_multiple_columns_fallible(&components_{var_index}, &mut col_indexes[{start}..{end}]) {{
         return None;
     }}
@@ -115,6 +146,16 @@ impl InvarStart {
 "
             )
             .unwrap();
+            if !invar.predicates.is_empty() {
+                // the invar is fixed, so there is nothing else to try
+                insert_checks(append, &[], &[], &[], &invar.predicates);
+                append.push_str(
+                    "
+{
+    return None;
+}",
+                );
+            }
         }
     }
 }
@@ -135,6 +176,9 @@ mod test {
                 opt_components: vec![],
                 relation_helpers: vec![],
                 unrelation_helpers: vec![],
+                check_archetype: false,
+                check_alive: false,
+                predicates: vec![],
             }],
         };
 
@@ -147,9 +191,9 @@ mod test {
         0 => {
 
         {
-            let (aid, arow) = bk.entities.get_archetype(invar_0);
+            let (aid, arow) = __froql_bk.entities.get_archetype(__froql_invar_0);
             let a_ref = &mut a_refs[0];
-            *a_ref = &bk.archetypes[aid.as_index()];
+            *a_ref = &__froql_bk.archetypes[aid.as_index()];
             if !a_ref.find_multiple_columns_fallible(&components_0, &mut col_indexes[0..2]) {
                 return None;
             }
@@ -0,0 +1,151 @@
+use super::GeneratorNode;
+use super::relation_join::insert_optional_comps;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Binds the variable of an optional relation term.
+///
+/// Visits every related entity like `RelationJoin`,
+/// but if there are none it goes on once with the variable unbound.
+#[derive(Debug)]
+pub struct OptionalJoin {
+    pub new: isize,
+    pub new_components: Range<usize>,
+    pub opt_components: Vec<(String, usize)>,
+    /// nr of the OptRelationHelper that contains the Relation(old, new)
+    pub helper_nr: usize,
+}
+
+impl GeneratorNode for OptionalJoin {
+    fn generate(&self, step: usize, prepend: &mut String, append: &mut String) -> usize {
+        let new = self.new;
+        let Range { start, end } = &self.new_components;
+        let helper_nr = self.helper_nr;
+        write!(
+            prepend,
+            "
+// is the optional var bound in the current row
+let mut opt_bound_{new} = false;
+// was the optional var bound or skipped for the current row of the old var
+let mut opt_visited_{new} = false;"
+        )
+        .unwrap();
+        write!(
+            append,
+            "
+// follow optional relation
+{step} => {{
+    const REL_VAR: usize = {new};
+    const REL_VAR_COMPONENTS: ::std::ops::Range<usize> = {start}..{end};
+    if let Some(id) = opt_rel_helper_{helper_nr}.next_related() {{
+        let (aid, arow) = __froql_bk.entities.get_archetype_unchecked(id);
+        if archetype_id_sets[REL_VAR].contains(&aid) {{
+            let a_ref = &mut a_refs[REL_VAR];
+            *a_ref = &__froql_bk.archetypes[aid.as_index()];
+            a_ref.find_multiple_columns(
+                &components_{new},
+                &mut col_indexes[REL_VAR_COMPONENTS],
+            );
+            a_rows[REL_VAR] = arow;"
+        )
+        .unwrap();
+        insert_optional_comps(prepend, append, &self.opt_components);
+        write!(
+            append,
+            "
+            opt_bound_{new} = true;
+            opt_visited_{new} = true;
+            current_step += 1;
+        }}
+    }} else if !opt_visited_{new} {{
+        // nothing related, go on once without the var"
+        )
+        .unwrap();
+        for (_, id) in &self.opt_components {
+            write!(
+                append,
+                "
+        opt_col_{id} = None;"
+            )
+            .unwrap();
+        }
+        write!(
+            append,
+            "
+        opt_bound_{new} = false;
+        opt_visited_{new} = true;
+        current_step += 1;
+    }} else {{
+        opt_visited_{new} = false;
+        opt_rel_helper_{helper_nr}.rewind();
+        current_step -= 1;
+    }}
+}}
+"
+        )
+        .unwrap();
+        return step + 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn optional_join() {
+        let generator = OptionalJoin {
+            new: 1,
+            new_components: 2..3,
+            opt_components: vec![("Name".into(), 0)],
+            helper_nr: 0,
+        };
+
+        let mut prepend = String::new();
+        let mut append = String::new();
+        let r = generator.generate(2, &mut prepend, &mut append);
+        assert_eq!(3, r);
+        insta::assert_snapshot!(prepend, @r#"
+        // is the optional var bound in the current row
+        let mut opt_bound_1 = false;
+        // was the optional var bound or skipped for the current row of the old var
+        let mut opt_visited_1 = false;
+        let opt_cid_0 = __froql_world.get_component_id::<Name>();
+        let mut opt_col_0 = None;
+        "#);
+        insta::assert_snapshot!(append, @r#"
+        // follow optional relation
+        2 => {
+            const REL_VAR: usize = 1;
+            const REL_VAR_COMPONENTS: ::std::ops::Range<usize> = 2..3;
+            if let Some(id) = opt_rel_helper_0.next_related() {
+                let (aid, arow) = __froql_bk.entities.get_archetype_unchecked(id);
+                if archetype_id_sets[REL_VAR].contains(&aid) {
+                    let a_ref = &mut a_refs[REL_VAR];
+                    *a_ref = &__froql_bk.archetypes[aid.as_index()];
+                    a_ref.find_multiple_columns(
+                        &components_1,
+                        &mut col_indexes[REL_VAR_COMPONENTS],
+                    );
+                    a_rows[REL_VAR] = arow;
+                    opt_col_0 = a_ref.find_column_opt(opt_cid_0);
+
+                    opt_bound_1 = true;
+                    opt_visited_1 = true;
+                    current_step += 1;
+                }
+            } else if !opt_visited_1 {
+                // nothing related, go on once without the var
+                opt_col_0 = None;
+                opt_bound_1 = false;
+                opt_visited_1 = true;
+                current_step += 1;
+            } else {
+                opt_visited_1 = false;
+                opt_rel_helper_0.rewind();
+                current_step -= 1;
+            }
+        }
+        "#);
+    }
+}
@@ -3,12 +3,28 @@ use std::fmt::Write;
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct RelationHelperInfo {
     /// Index where the relationship cid is in the cid array for `old_var`
+    /// not used by path helpers
     pub cid_index: usize,
+    /// not used by path helpers
     pub column_index: usize,
     pub old_var: isize,
     pub new_var: isize,
     /// nr of this RelationHelper, used when generating variable name
     pub nr: usize,
+    /// set if the helper follows a path term like `Rel+(a, b)`
+    pub path: Option<PathInfo>,
+}
+
+/// The relation component of a path term is not in the cid array,
+/// because `Rel*(a, b)` also matches entities without it.
+/// So the helper looks up its cid and column by itself.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PathInfo {
+    pub ty: String,
+    pub flip_target: bool,
+    pub min: u32,
+    /// None if unbounded
+    pub max: Option<u32>,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -20,6 +36,18 @@ pub struct UnrelationHelperInfo {
     pub new_var: isize,
     /// nr of this RelationHelper, used when generating variable name
     pub nr: usize,
+    /// helper of an optional relation term, followed by `OptionalJoin` instead of being checked
+    pub optional: bool,
+}
+
+impl UnrelationHelperInfo {
+    fn name(&self) -> String {
+        if self.optional {
+            format!("opt_rel_helper_{}", self.nr)
+        } else {
+            format!("unrel_helper_{}", self.nr)
+        }
+    }
 }
 
 pub fn relation_helpers_init_and_set_col(
@@ -33,6 +61,32 @@ pub fn relation_helpers_init_and_set_col(
         let nr = helper.nr;
         let column_index = helper.column_index;
         let cid_index = helper.cid_index;
+        if let Some(path) = &helper.path {
+            let PathInfo { ty, min, .. } = path;
+            let flip = if path.flip_target {
+                ".flip_target()"
+            } else {
+                ""
+            };
+            let max = path.max.unwrap_or(u32::MAX);
+            write!(
+                prepend,
+                "
+let mut rel_helper_{nr} = ::froql::query_helper::RelationHelper::with_hops
+    (__froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()){flip},
+    ::std::option::Option::Some(({min}, {max})));
+"
+            )
+            .unwrap();
+            write!(
+                append,
+                "
+    rel_helper_{nr}.set_archetype(a_ref);
+"
+            )
+            .unwrap();
+            continue;
+        }
         write!(
             prepend,
             "
@@ -51,14 +105,19 @@ let mut rel_helper_{nr} = ::froql::query_helper::RelationHelper::new
     }
 
     for unhelper in unhelpers {
-        let nr = unhelper.nr;
+        let name = unhelper.name();
         let ty = &unhelper.ty;
+        let helper = if unhelper.optional {
+            "OptRelationHelper"
+        } else {
+            "UnrelationHelper"
+        };
         if unhelper.flip_target {
             write!(
                 prepend,
                 "
-let mut unrel_helper_{nr} = ::froql::query_helper::UnrelationHelper::new
-    (bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>())
+let mut {name} = ::froql::query_helper::{helper}::new
+    (__froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>())
             .flip_target());
 "
             )
@@ -67,8 +126,8 @@ let mut unrel_helper_{nr} = ::froql::query_helper::UnrelationHelper::new
             write!(
                 prepend,
                 "
-let mut unrel_helper_{nr} = ::froql::query_helper::UnrelationHelper::new
-    (bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()));
+let mut {name} = ::froql::query_helper::{helper}::new
+    (__froql_bk.get_component_id_unchecked(::std::any::TypeId::of::<::froql::relation::Relation<{ty}>>()));
 "
             )
             .unwrap();
@@ -76,7 +135,7 @@ let mut unrel_helper_{nr} = ::froql::query_helper::UnrelationHelper::new
         write!(
             append,
             "
-    unrel_helper_{nr}.set_col(a_ref);
+    {name}.set_col(a_ref);
 "
         )
         .unwrap();
@@ -94,18 +153,18 @@ pub fn relation_helpers_set_rows(
         write!(
             append,
             "
-        rel_helper_{nr}.set_row(bk, a_rows[{var}].0);
+        rel_helper_{nr}.set_row(__froql_bk, a_rows[{var}].0);
 "
         )
         .unwrap();
     }
     for unhelper in unhelpers {
-        let nr = unhelper.nr;
+        let name = unhelper.name();
         let var = unhelper.old_var;
         write!(
             append,
             "
-        unrel_helper_{nr}.set_row(bk, a_rows[{var}].0);
+        {name}.set_row(__froql_bk, a_rows[{var}].0);
 "
         )
         .unwrap();
@@ -3,7 +3,7 @@ use super::relation_helper::{
     RelationHelperInfo, UnrelationHelperInfo, relation_helpers_init_and_set_col,
     relation_helpers_set_rows,
 };
-use super::types::{RelationConstraint, UnrelationConstraint};
+use super::types::{Predicate, RelationConstraint, UnrelationConstraint};
 use std::fmt::Write;
 use std::ops::Range;
 
@@ -17,6 +17,8 @@ pub struct RelationJoin {
     pub rel_constraints: Vec<RelationConstraint>,
     /// UnrelationHelpers that constrain the new var
     pub unrel_constraints: Vec<UnrelationConstraint>,
+    /// predicates on components of the new var
+    pub predicates: Vec<Predicate>,
     pub opt_components: Vec<(String, usize)>,
     /// RelationHelpers that depend on the new var
     pub new_relation_helpers: Vec<RelationHelperInfo>,
@@ -40,12 +42,12 @@ impl GeneratorNode for RelationJoin {
     const REL_VAR_COMPONENTS: ::std::ops::Range<usize> = {start}..{end};
     if let Some(id) = rel_helper_{helper_nr}.next_related() {{
         // get aid/row for entity in relation
-        let (aid, arow) = bk.entities.get_archetype_unchecked(id);
+        let (aid, arow) = __froql_bk.entities.get_archetype_unchecked(id);
 
         // if in target archetype set => go to next step
         if archetype_id_sets[REL_VAR].contains(&aid) {{
             let a_ref = &mut a_refs[REL_VAR];
-            *a_ref = &bk.archetypes[aid.as_index()];
+            *a_ref = &__froql_bk.archetypes[aid.as_index()];
             a_ref.find_multiple_columns(
                 &components_{new},
                 &mut col_indexes[REL_VAR_COMPONENTS],
@@ -59,6 +61,7 @@ impl GeneratorNode for RelationJoin {
         if self.unequal_constraints.is_empty()
             && self.rel_constraints.is_empty()
             && self.unrel_constraints.is_empty()
+            && self.predicates.is_empty()
         {
             insert_optional_comps(prepend, append, &self.opt_components);
             relation_helpers_init_and_set_col(
@@ -84,6 +87,7 @@ impl GeneratorNode for RelationJoin {
                 &self.unequal_constraints,
                 &self.rel_constraints,
                 &self.unrel_constraints,
+                &self.predicates,
             );
             append.push_str(
                 "
@@ -110,11 +114,14 @@ impl GeneratorNode for RelationJoin {
             );
         }
 
+        // the old var may have been bound several steps earlier,
+        // so the helper has to start over when we come back to this step
         write!(
             append,
             "
         }}
     }} else {{
+        rel_helper_{helper_nr}.rewind();
         current_step -= 1;
     }}
 }}
@@ -130,6 +137,7 @@ pub fn insert_checks(
     unequalities: &[(isize, isize)],
     rel_constraints: &[RelationConstraint],
     unrel_constraints: &[UnrelationConstraint],
+    predicates: &[Predicate],
 ) {
     append.push_str(
         r#"
@@ -159,7 +167,7 @@ pub fn insert_checks(
         let helper_nr = &rc.helper_nr;
         let id = &rc
             .checked_invar
-            .map(|it| format!("invar_{it}.id"))
+            .map(|it| format!("__froql_invar_{it}.id"))
             .unwrap_or_else(|| "id".to_string());
         if not_first {
             write!(
@@ -182,7 +190,7 @@ pub fn insert_checks(
         let helper_nr = &rc.helper_nr;
         let id = &rc
             .checked_invar
-            .map(|it| format!("invar_{it}.id"))
+            .map(|it| format!("__froql_invar_{it}.id"))
             .unwrap_or_else(|| "id".to_string());
         if not_first {
             write!(
@@ -201,6 +209,34 @@ pub fn insert_checks(
         .unwrap();
         not_first = true;
     }
+    for predicate in predicates {
+        let Predicate {
+            nr,
+            ty,
+            var,
+            column_index,
+        } = predicate;
+        if not_first {
+            write!(
+                append,
+                "
+            ||"
+            )
+            .unwrap();
+        }
+        write!(
+            append,
+            "
+                !__froql_pred_{nr}(&unsafe {{
+                    ::froql::query_helper::coerce_cast::<{ty}>(
+                        __froql_world,
+                        a_refs[{var}].columns[col_indexes[{column_index}]].get(a_rows[{var}].0)
+                    )
+                }}.borrow())"
+        )
+        .unwrap();
+        not_first = true;
+    }
 }
 
 pub fn insert_optional_comps(
@@ -212,7 +248,7 @@ pub fn insert_optional_comps(
         write!(
             prepend,
             r#"
-let opt_cid_{id} = world.get_component_id::<{ty}>();
+let opt_cid_{id} = __froql_world.get_component_id::<{ty}>();
 let mut opt_col_{id} = None;"#
         )
         .unwrap();
@@ -237,6 +273,7 @@ mod test {
             unequal_constraints: vec![(0, 2), (2, 1)],
             rel_constraints: vec![],
             unrel_constraints: vec![],
+            predicates: vec![],
             opt_components: vec![],
             new_relation_helpers: vec![],
             new_unrelation_helpers: vec![],
@@ -262,6 +299,7 @@ mod test {
                 checked_invar: None,
             }],
             unrel_constraints: vec![],
+            predicates: vec![],
             opt_components: vec![],
             new_relation_helpers: vec![],
             new_unrelation_helpers: vec![],
@@ -284,6 +322,7 @@ mod test {
             unequal_constraints: vec![],
             rel_constraints: vec![],
             unrel_constraints: vec![],
+            predicates: vec![],
             opt_components: vec![("OptA".into(), 0), ("OptB".into(), 1)],
             new_relation_helpers: vec![],
             new_unrelation_helpers: vec![],
@@ -295,9 +334,9 @@ mod test {
         let r = generator.generate(3, &mut prepend, &mut append);
         assert_eq!(4, r);
         insta::assert_snapshot!(prepend, @r#"
-        let opt_cid_0 = world.get_component_id::<OptA>();
+        let opt_cid_0 = __froql_world.get_component_id::<OptA>();
         let mut opt_col_0 = None;
-        let opt_cid_1 = world.get_component_id::<OptB>();
+        let opt_cid_1 = __froql_world.get_component_id::<OptB>();
         let mut opt_col_1 = None;
         "#);
         insta::assert_snapshot!(append);
@@ -13,3 +13,14 @@ pub struct UnrelationConstraint {
     /// when joining we always check the just joined id
     pub checked_invar: Option<isize>,
 }
+
+/// `where(Comp(var) => closure)`, checked as soon as the var is bound
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Predicate {
+    /// nr of the predicate, used when generating variable name
+    pub nr: usize,
+    pub ty: String,
+    pub var: isize,
+    /// index in `col_indexes` of the component
+    pub column_index: usize,
+}
@@ -11,6 +11,21 @@ pub struct YieldResult<'a> {
 
 impl GeneratorNode for YieldResult<'_> {
     fn generate(&self, step: usize, _prepend: &mut String, append: &mut String) -> usize {
+        if self.accessors.is_empty() {
+            // nothing to access, used by `count!` and `exists!`
+            write!(
+                append,
+                "
+// yield row
+{step} => {{
+    current_step -= 1;
+    return Some(());
+}}
+"
+            )
+            .unwrap();
+            return usize::MAX;
+        }
         write!(
             append,
             "
@@ -29,7 +44,7 @@ impl GeneratorNode for YieldResult<'_> {
                         append,
                         "
             ::froql::query_helper::coerce_cast::<{ty}>(
-                world,
+                __froql_world,
                 a_refs[{var}].columns[col_indexes[{col}]].get(a_rows[{var}].0)
             ).borrow(),"
                     )
@@ -41,7 +56,7 @@ impl GeneratorNode for YieldResult<'_> {
                         append,
                         "
             ::froql::query_helper::coerce_cast::<{ty}>(
-                world,
+                __froql_world,
                 a_refs[{var}].columns[col_indexes[{col}]].get(a_rows[{var}].0)
             ).borrow_mut(),"
                     )
@@ -51,18 +66,29 @@ impl GeneratorNode for YieldResult<'_> {
                     write!(
                         append,
                         "
-            ::froql::entity_view_deferred::EntityViewDeferred::from_id_unchecked(world,
+            ::froql::entity_view_deferred::EntityViewDeferred::from_id_unchecked(__froql_world,
                                 a_refs[{var}].entities[a_rows[{var}].0 as usize]),"
                     )
                     .unwrap();
                 }
+                Accessor::OptOutVar(var) => {
+                    write!(
+                        append,
+                        "
+            opt_bound_{var}.then(|| {{
+                ::froql::entity_view_deferred::EntityViewDeferred::from_id_unchecked(__froql_world,
+                                a_refs[{var}].entities[a_rows[{var}].0 as usize])
+            }}),"
+                    )
+                    .unwrap();
+                }
                 Accessor::OptComponent(ty, var, opt_id) => {
                     write!(
                         append,
                         "
             (opt_col_{opt_id}.map(|col| {{
                 ::froql::query_helper::coerce_cast::<{ty}>(
-                    world,
+                    __froql_world,
                     col.get(a_rows[{var}].0)
                 ).borrow()
             }})),"
@@ -75,7 +101,7 @@ impl GeneratorNode for YieldResult<'_> {
                         "
             (opt_col_{opt_id}.map(|col| {{
                 ::froql::query_helper::coerce_cast::<{ty}>(
-                    world,
+                    __froql_world,
                     col.get(a_rows[{var}].0)
                 ).borrow_mut()
             }})),"
@@ -86,7 +112,7 @@ impl GeneratorNode for YieldResult<'_> {
                     write!(
                         append,
                         "
-            world.singleton::<{ty}>(),"
+            __froql_world.singleton::<{ty}>(),"
                     )
                     .unwrap();
                 }
@@ -94,10 +120,41 @@ impl GeneratorNode for YieldResult<'_> {
                     write!(
                         append,
                         "
-            world.singleton_mut::<{ty}>(),"
+            __froql_world.singleton_mut::<{ty}>(),"
+                    )
+                    .unwrap();
+                }
+                Accessor::Branch(index, var) => {
+                    write!(
+                        append,
+                        "
+            or_{index}.iter().position(|cid| a_refs[{var}].components.contains(cid)).unwrap(),"
                     )
                     .unwrap();
                 }
+                Accessor::Hops(a, b) => {
+                    let helper = (self.infos.iter())
+                        .flat_map(|info| &info.relation_helpers)
+                        .filter(|helper| helper.path.is_some())
+                        .find(|helper| {
+                            (helper.old_var, helper.new_var) == (*a, *b)
+                                || (helper.old_var, helper.new_var) == (*b, *a)
+                        })
+                        .expect("Internal: hops(a, b) needs a path term");
+                    let nr = helper.nr;
+                    let new = helper.new_var;
+                    write!(
+                        append,
+                        "
+            rel_helper_{nr}.hops_to(a_refs[{new}].entities[a_rows[{new}].0 as usize]),"
+                    )
+                    .unwrap();
+                }
+                Accessor::Aggregate(..) => {
+                    unreachable!(
+                        "Internal: aggregates are resolved before __froql_rows are yielded"
+                    )
+                }
             }
         }
         write!(
@@ -130,19 +187,19 @@ mod test {
         let mut append = String::new();
         generator.generate(3, &mut prepend, &mut append);
         insta::assert_snapshot!(prepend, @"");
-        insta::assert_snapshot!(append, @r"
+        insta::assert_snapshot!(append, @r#"
         // yield row
         3 => {
             current_step -= 1;
             return Some(unsafe {
                 (
                     ::froql::query_helper::coerce_cast::<Health>(
-                        world,
+                        __froql_world,
                         a_refs[0].columns[col_indexes[0]].get(a_rows[0].0)
                     ).borrow(),
                 )
             });
         }
-        ");
+        "#);
     }
 }
@@ -5,22 +5,57 @@ extern crate proc_macro;
 
 mod generator;
 mod macro_error;
-mod parser;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::generator::*;
+use froql_grammar::AggregateKind;
+use froql_grammar::OrVars;
+use froql_grammar::RelationVarKind as RVK;
+use froql_grammar::VarKind as VK;
+use froql_grammar::{ParseError, Term, Token, parse_term, split_terms, transform_anyvars};
 use macro_error::MacroError;
-use parser::RelationVarKind as RVK;
-use parser::VarKind as VK;
-use parser::{Term, parse_term};
-use proc_macro::{TokenStream, TokenTree};
+use proc_macro::{Delimiter, Span, TokenStream, TokenTree};
 
 pub(crate) const ANYVAR: isize = isize::MAX;
 
 #[proc_macro]
 pub fn query(input: TokenStream) -> TokenStream {
-    return match inner(input) {
+    return match inner(input, false, Output::Rows) {
+        Ok(tt) => tt,
+        Err(err) => err.to_compile_error(),
+    };
+}
+
+/// Like `query!`, but resolved component ids and matching archetypes are cached
+/// per call site and only updated when new archetypes were created.
+#[proc_macro]
+pub fn cached_query(input: TokenStream) -> TokenStream {
+    return match inner(input, true, Output::Rows) {
+        Ok(tt) => tt,
+        Err(err) => err.to_compile_error(),
+    };
+}
+
+/// Counts the matches of a query without borrowing any component.
+///
+/// Takes the same arguments as `query!` and evaluates to a `usize`.
+/// Single variable queries only add up the sizes of the matching archetypes.
+#[proc_macro]
+pub fn count(input: TokenStream) -> TokenStream {
+    return match inner(input, false, Output::Count) {
+        Ok(tt) => tt,
+        Err(err) => err.to_compile_error(),
+    };
+}
+
+/// Checks if a query has at least one match without borrowing any component.
+///
+/// Takes the same arguments as `query!` and evaluates to a `bool`.
+/// Stops at the first match.
+#[proc_macro]
+pub fn exists(input: TokenStream) -> TokenStream {
+    return match inner(input, false, Output::Exists) {
         Ok(tt) => tt,
         Err(err) => err.to_compile_error(),
     };
@@ -32,10 +67,16 @@ pub(crate) type Relation = (String, isize, isize);
 pub(crate) type Component = (String, isize);
 /// RelationType, from_var, to_var, index
 pub(crate) type Unrelation = (String, isize, isize, usize);
+/// BranchTypes, var, other var for relations, index
+pub(crate) type Or = (Vec<String>, isize, Option<isize>, usize);
+/// RelationType, from_var, to_var, optional var
+pub(crate) type OptRelation = (String, isize, isize, isize);
+/// RelationType, from_var, to_var, min hops, max hops (None if unbounded)
+pub(crate) type Path = (String, isize, isize, u32, Option<u32>);
 
 /// we need to preserve the order of the query in the result
 /// this is why we put result entities and components in the same vec via enum
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Accessor {
     /// ComponentType, var
     Component(String, isize),
@@ -43,6 +84,8 @@ pub(crate) enum Accessor {
     ComponentMut(String, isize),
     /// var index in result
     OutVar(isize),
+    /// var bound by an optional relation, `None` if it is not bound
+    OptOutVar(isize),
     /// ComponentType, var, opt_col_index
     OptMutComponent(String, isize, usize),
     /// ComponentType, var, opt_col_index
@@ -51,6 +94,15 @@ pub(crate) enum Accessor {
     Singleton(String),
     /// ComponentType
     SingletonMut(String),
+    /// kind, ComponentType, var
+    /// rows are grouped by the outvars of the query
+    Aggregate(AggregateKind, Option<String>, isize),
+    /// index of the OR term, var whose archetype contains the branch
+    /// yields the index of the first matching branch
+    Branch(usize, isize),
+    /// var, var of a path term
+    /// yields the number of hops between the two as `usize`
+    Hops(isize, isize),
 }
 
 struct VariableStore {
@@ -67,33 +119,53 @@ impl VariableStore {
     }
 
     fn var_number(&mut self, var_name: impl Into<String>) -> isize {
-        let var = *self.variables.entry(var_name.into()).or_insert_with(|| {
+        *self.variables.entry(var_name.into()).or_insert_with(|| {
             self.var_count += 1;
             self.var_count
-        });
-        var
+        })
     }
 }
 
-fn inner(input: TokenStream) -> Result<TokenStream, MacroError> {
+fn inner(input: TokenStream, cached: bool, output: Output) -> Result<TokenStream, MacroError> {
     //dbg!(&input);
 
     let mut iter = input.into_iter();
 
-    let world = if let Some(TokenTree::Ident(world)) = iter.next() {
-        world.to_string()
-    } else {
-        panic!("First argument should be a reference to the world.");
-    };
-
-    if let Some(TokenTree::Punct(comma)) = iter.next() {
-        assert_eq!(',', comma.as_char(), "Expected , after world");
-    } else {
-        panic!("Expected , after world");
-    };
-
-    // stores incomplete terms
-    let mut buffer: Vec<TokenTree> = Vec::with_capacity(10);
+    // the world can be any expression up to the first comma
+    let mut world = Vec::new();
+    let mut comma = None;
+    for tt in iter.by_ref() {
+        match tt {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                comma = Some(punct.span());
+                break;
+            }
+            tt => world.push(tt),
+        }
+    }
+    match (world.first(), world.last(), comma) {
+        (None, _, None) => {
+            return Err(MacroError::call_site(
+                "Expected a reference to the world followed by terms, like query!(world, Name(a)).",
+            ));
+        }
+        (None, _, Some(comma)) => {
+            return Err(MacroError::new(
+                comma,
+                comma,
+                "Expected a reference to the world before the first `,`.",
+            ));
+        }
+        (Some(first), Some(last), None) => {
+            return Err(MacroError::new(
+                first.span(),
+                last.span(),
+                "Expected `,` and query terms after the world.",
+            ));
+        }
+        _ => (),
+    }
+    let world = TokenStream::from_iter(world).to_string();
 
     let mut variables = VariableStore::new();
 
@@ -106,237 +178,553 @@ fn inner(input: TokenStream) -> Result<TokenStream, MacroError> {
     let mut unrelations: Vec<Unrelation> = Vec::new();
     let mut opt_components = Vec::new();
     let mut prefills = HashMap::new();
+    let mut order_by = Vec::new();
+    let mut ors: Vec<Or> = Vec::new();
+    let mut cross_join = false;
+    let mut opt_relations: Vec<OptRelation> = Vec::new();
+    let mut predicates = Vec::new();
+    let mut paths: Vec<Path> = Vec::new();
+    // checked after all paths are known
+    let mut hops = Vec::new();
 
-    // loop over all input tokens
-    // when encountering a seperator, parse a term and sort it into the collections above
-    loop {
-        let next = iter.next();
+    // the closures of where terms are passed through as they are
+    let input: Vec<TokenTree> = iter.collect();
+    let mut closures = where_closures(&input).into_iter();
 
-        let is_separator = match next {
-            None => true,
-            Some(TokenTree::Punct(ref comma)) if comma.as_char() == ',' => true,
-            _ => false,
-        };
-        if is_separator && !buffer.is_empty() {
-            match parse_term(&buffer).map(transform_anyvars)? {
-                Term::ComponentVar(ty, ref varkind @ VK::Var(ref var_name))
-                | Term::ComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
-                    let var = variables.var_number(var_name);
-                    match varkind {
-                        VK::Var(_) => (),
-                        VK::InVar(_) => {
-                            prefills.insert(var, var_name.clone());
-                        }
-                    }
-                    components.push((ty.clone(), var));
-                    accessors.push(Accessor::Component(ty, var));
-                }
-                Term::MutComponentVar(ty, ref varkind @ VK::Var(ref var_name))
-                | Term::MutComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
-                    let var = variables.var_number(var_name);
-                    match varkind {
-                        VK::Var(_) => (),
-                        VK::InVar(_) => {
-                            prefills.insert(var, var_name.clone());
-                        }
-                    }
-                    components.push((ty.clone(), var));
-                    accessors.push(Accessor::ComponentMut(ty, var));
+    // parse every term and sort it into the collections above
+    let tokens: Vec<Token<Span>> = input.into_iter().map(convert_token).collect();
+    let terms = split_terms(&tokens)
+        .map(|term| {
+            let parsed = transform_anyvars(parse_term(term)?)
+                .map_err(|message| ParseError::slice(term, message))?;
+            Ok((term, parsed))
+        })
+        .collect::<Result<Vec<_>, ParseError<Span>>>()?;
+    // a variable that is used for the first time in an optional relation is optional
+    // it can only be used in terms that can handle a missing entity
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut optional_vars: HashSet<String> = HashSet::new();
+    // first term of every variable, for error messages
+    let mut first_use: HashMap<String, &[Token<Span>]> = HashMap::new();
+    for (term, parsed) in &terms {
+        let vars = term_vars(parsed);
+        for var in &vars {
+            first_use.entry(var.to_string()).or_insert(term);
+        }
+        match parsed {
+            Term::OptionalRelation(_, a, b) => {
+                if matches!(a, RVK::AnyVar) || matches!(b, RVK::AnyVar) {
+                    return Err(ParseError::slice(
+                        term,
+                        "Optional relations can't have _ on either side.".to_string(),
+                    )
+                    .into());
                 }
-                Term::NoOutComponentVar(ty, ref varkind @ VK::Var(ref var_name))
-                | Term::NoOutComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
-                    // Optimization: don't need to access this, just check its there
-                    let var = variables.var_number(var_name);
-                    match varkind {
-                        VK::Var(_) => (),
-                        VK::InVar(_) => {
-                            prefills.insert(var, var_name.clone());
-                        }
+                let is_new =
+                    |kind: &RVK| matches!(kind, RVK::Var(name) if !seen.contains(name.as_str()));
+                let (new, old) = match (is_new(a), is_new(b)) {
+                    (false, true) => (b, a),
+                    (true, false) => (a, b),
+                    _ => {
+                        return Err(ParseError::slice(
+                            term,
+                            "One variable of an optional relation has to be used for the first time and the other one has to be used before.".to_string(),
+                        )
+                        .into());
                     }
-                    components.push((ty, var));
+                };
+                let (RVK::Var(new) | RVK::InVar(new)) = new else {
+                    unreachable!("Internal: anyvars are never new")
+                };
+                if let RVK::Var(old) | RVK::InVar(old) = old
+                    && optional_vars.contains(old.as_str())
+                {
+                    return Err(optional_var_error(term, old));
                 }
-                Term::OutVar(var) => {
-                    let var = variables.var_number(var);
-                    accessors.push(Accessor::OutVar(var));
+                optional_vars.insert(new.clone());
+            }
+            Term::OptionalComponent(..) | Term::OptionalMutComponent(..) | Term::OutVar(_) => (),
+            _ => {
+                if let Some(var) = vars.iter().find(|var| optional_vars.contains(**var)) {
+                    return Err(optional_var_error(term, var));
                 }
-                Term::ConstraintUnequal(
-                    ref ta @ VK::Var(ref var_a),
-                    ref tb @ VK::Var(ref var_b),
-                )
-                | Term::ConstraintUnequal(
-                    ref ta @ VK::InVar(ref var_a),
-                    ref tb @ VK::Var(ref var_b),
-                )
-                | Term::ConstraintUnequal(
-                    ref ta @ VK::Var(ref var_a),
-                    ref tb @ VK::InVar(ref var_b),
-                )
-                | Term::ConstraintUnequal(
-                    ref ta @ VK::InVar(ref var_a),
-                    ref tb @ VK::InVar(ref var_b),
-                ) => {
-                    // maybe we should error if a constraint uses a variable defined nowhere else?
-                    let a = variables.var_number(var_a);
-                    let b = variables.var_number(var_b);
-                    match (ta, tb) {
-                        (VK::InVar(_), VK::Var(_)) => {
-                            prefills.insert(a, var_a.clone());
-                        }
-                        (VK::Var(_), VK::InVar(_)) => {
-                            prefills.insert(b, var_b.clone());
-                        }
-                        (VK::InVar(_), VK::InVar(_)) => {
-                            prefills.insert(a, var_a.clone());
-                            prefills.insert(b, var_b.clone());
-                        }
-                        (VK::Var(_), VK::Var(_)) => (),
+            }
+        }
+        seen.extend(vars);
+    }
+
+    // rows are merged into groups, so components of single rows can't be in the result
+    let aggregating = terms
+        .iter()
+        .any(|(_, parsed)| matches!(parsed, Term::Aggregate(..)));
+    for (term, parsed) in terms {
+        if aggregating
+            && matches!(
+                parsed,
+                Term::ComponentVar(..)
+                    | Term::MutComponentVar(..)
+                    | Term::OptionalComponent(..)
+                    | Term::OptionalMutComponent(..)
+                    | Term::Singleton(..)
+                    | Term::SingletonMut(..)
+                    | Term::Or(_, _, true)
+                    | Term::Hops(..)
+            )
+        {
+            return Err(ParseError::slice(
+                term,
+                "Only outvars and aggregates can be in the result of an aggregating query. Use _ Component(var) to match without output.".to_string(),
+            )
+            .into());
+        }
+        match parsed {
+            Term::ComponentVar(ty, ref varkind @ VK::Var(ref var_name))
+            | Term::ComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
+                let var = variables.var_number(var_name);
+                match varkind {
+                    VK::Var(_) => (),
+                    VK::InVar(_) => {
+                        prefills.insert(var, var_name.clone());
                     }
-                    unequals.push((a, b));
-                }
-                Term::Uncomponent(ty, var) => {
-                    let var = variables.var_number(var);
-                    uncomponents.push((ty, var));
-                }
-                Term::Relation(ty, RVK::Var(var_a), RVK::Var(var_b)) => {
-                    let a = variables.var_number(var_a);
-                    let b = variables.var_number(var_b);
-                    relations.push((ty, a, b));
-                }
-                Term::Relation(ty, RVK::InVar(var_a), RVK::Var(var_b)) => {
-                    let a = variables.var_number(&var_a);
-                    let b = variables.var_number(var_b);
-                    relations.push((ty, a, b));
-                    prefills.insert(a, var_a);
-                }
-                Term::Relation(ty, RVK::InVar(var_a), RVK::InVar(var_b)) => {
-                    let a = variables.var_number(&var_a);
-                    let b = variables.var_number(&var_b);
-                    relations.push((ty, a, b));
-                    prefills.insert(a, var_a);
-                    prefills.insert(b, var_b);
                 }
-                Term::Relation(ty, RVK::Var(var_a), RVK::InVar(var_b)) => {
-                    let a = variables.var_number(var_a);
-                    let b = variables.var_number(&var_b);
-                    relations.push((ty, a, b));
-                    prefills.insert(b, var_b);
-                }
-                Term::Relation(ty, RVK::Var(var_a), RVK::AnyVar) => {
-                    let a = variables.var_number(var_a);
-                    let b = ANYVAR;
-                    relations.push((ty, a, b));
-                }
-                Term::Relation(ty, RVK::InVar(var_a), RVK::AnyVar) => {
-                    let a = variables.var_number(&var_a);
-                    let b = ANYVAR;
-                    prefills.insert(a, var_a);
-                    relations.push((ty, a, b));
+                components.push((ty.clone(), var));
+                accessors.push(Accessor::Component(ty, var));
+            }
+            Term::MutComponentVar(ty, ref varkind @ VK::Var(ref var_name))
+            | Term::MutComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
+                let var = variables.var_number(var_name);
+                match varkind {
+                    VK::Var(_) => (),
+                    VK::InVar(_) => {
+                        prefills.insert(var, var_name.clone());
+                    }
                 }
-                Term::Relation(ty, RVK::AnyVar, RVK::Var(var_b)) => {
-                    let a = ANYVAR;
-                    let b = variables.var_number(var_b);
-                    relations.push((ty, a, b));
+                components.push((ty.clone(), var));
+                accessors.push(Accessor::ComponentMut(ty, var));
+            }
+            Term::NoOutComponentVar(ty, ref varkind @ VK::Var(ref var_name))
+            | Term::NoOutComponentVar(ty, ref varkind @ VK::InVar(ref var_name)) => {
+                // Optimization: don't need to access this, just check its there
+                let var = variables.var_number(var_name);
+                match varkind {
+                    VK::Var(_) => (),
+                    VK::InVar(_) => {
+                        prefills.insert(var, var_name.clone());
+                    }
                 }
-                Term::Relation(ty, RVK::AnyVar, RVK::InVar(var_b)) => {
-                    let a = ANYVAR;
-                    let b = variables.var_number(&var_b);
-                    prefills.insert(b, var_b);
-                    relations.push((ty, a, b));
+                components.push((ty, var));
+            }
+            Term::OutVar(var) => {
+                let optional = optional_vars.contains(var.as_str());
+                let var = variables.var_number(var);
+                if optional {
+                    accessors.push(Accessor::OptOutVar(var));
+                } else {
+                    accessors.push(Accessor::OutVar(var));
                 }
-                Term::Relation(_ty, RVK::AnyVar, RVK::AnyVar) => {
-                    panic!("Relation(_,_) does not make sense.");
+            }
+            Term::ConstraintUnequal(ref ta @ VK::Var(ref var_a), ref tb @ VK::Var(ref var_b))
+            | Term::ConstraintUnequal(ref ta @ VK::InVar(ref var_a), ref tb @ VK::Var(ref var_b))
+            | Term::ConstraintUnequal(ref ta @ VK::Var(ref var_a), ref tb @ VK::InVar(ref var_b))
+            | Term::ConstraintUnequal(
+                ref ta @ VK::InVar(ref var_a),
+                ref tb @ VK::InVar(ref var_b),
+            ) => {
+                // maybe we should error if a constraint uses a variable defined nowhere else?
+                let a = variables.var_number(var_a);
+                let b = variables.var_number(var_b);
+                match (ta, tb) {
+                    (VK::InVar(_), VK::Var(_)) => {
+                        prefills.insert(a, var_a.clone());
+                    }
+                    (VK::Var(_), VK::InVar(_)) => {
+                        prefills.insert(b, var_b.clone());
+                    }
+                    (VK::InVar(_), VK::InVar(_)) => {
+                        prefills.insert(a, var_a.clone());
+                        prefills.insert(b, var_b.clone());
+                    }
+                    (VK::Var(_), VK::Var(_)) => (),
                 }
-                Term::Unrelation(
-                    ty,
-                    ref term_a @ RVK::Var(ref var_a),
-                    ref term_b @ RVK::Var(ref var_b),
-                )
-                | Term::Unrelation(
-                    ty,
-                    ref term_a @ RVK::InVar(ref var_a),
-                    ref term_b @ RVK::Var(ref var_b),
+                unequals.push((a, b));
+            }
+            Term::Uncomponent(ty, var) => {
+                let var = variables.var_number(var);
+                uncomponents.push((ty, var));
+            }
+            Term::Relation(ty, RVK::Var(var_a), RVK::Var(var_b)) => {
+                let a = variables.var_number(var_a);
+                let b = variables.var_number(var_b);
+                relations.push((ty, a, b));
+            }
+            Term::Relation(ty, RVK::InVar(var_a), RVK::Var(var_b)) => {
+                let a = variables.var_number(&var_a);
+                let b = variables.var_number(var_b);
+                relations.push((ty, a, b));
+                prefills.insert(a, var_a);
+            }
+            Term::Relation(ty, RVK::InVar(var_a), RVK::InVar(var_b)) => {
+                let a = variables.var_number(&var_a);
+                let b = variables.var_number(&var_b);
+                relations.push((ty, a, b));
+                prefills.insert(a, var_a);
+                prefills.insert(b, var_b);
+            }
+            Term::Relation(ty, RVK::Var(var_a), RVK::InVar(var_b)) => {
+                let a = variables.var_number(var_a);
+                let b = variables.var_number(&var_b);
+                relations.push((ty, a, b));
+                prefills.insert(b, var_b);
+            }
+            Term::Relation(ty, RVK::Var(var_a), RVK::AnyVar) => {
+                let a = variables.var_number(var_a);
+                let b = ANYVAR;
+                relations.push((ty, a, b));
+            }
+            Term::Relation(ty, RVK::InVar(var_a), RVK::AnyVar) => {
+                let a = variables.var_number(&var_a);
+                let b = ANYVAR;
+                prefills.insert(a, var_a);
+                relations.push((ty, a, b));
+            }
+            Term::Relation(ty, RVK::AnyVar, RVK::Var(var_b)) => {
+                let a = ANYVAR;
+                let b = variables.var_number(var_b);
+                relations.push((ty, a, b));
+            }
+            Term::Relation(ty, RVK::AnyVar, RVK::InVar(var_b)) => {
+                let a = ANYVAR;
+                let b = variables.var_number(&var_b);
+                prefills.insert(b, var_b);
+                relations.push((ty, a, b));
+            }
+            Term::Relation(ty, RVK::AnyVar, RVK::AnyVar) => {
+                return Err(ParseError::slice(
+                    term,
+                    format!("{ty}(_, _) does not constrain any variable, at least one side has to be a variable."),
                 )
-                | Term::Unrelation(
-                    ty,
-                    ref term_a @ RVK::Var(ref var_a),
-                    ref term_b @ RVK::InVar(ref var_b),
-                ) => {
-                    let a = variables.var_number(var_a);
-                    let b = variables.var_number(var_b);
-                    match (term_a, term_b) {
-                        (RVK::InVar(_), RVK::Var(_)) => {
-                            prefills.insert(a, var_a.clone());
-                        }
-                        (RVK::Var(_), RVK::InVar(_)) => {
-                            prefills.insert(b, var_b.clone());
-                        }
-                        _ => (),
+                .into());
+            }
+            Term::Unrelation(
+                ty,
+                ref term_a @ RVK::Var(ref var_a),
+                ref term_b @ RVK::Var(ref var_b),
+            )
+            | Term::Unrelation(
+                ty,
+                ref term_a @ RVK::InVar(ref var_a),
+                ref term_b @ RVK::Var(ref var_b),
+            )
+            | Term::Unrelation(
+                ty,
+                ref term_a @ RVK::Var(ref var_a),
+                ref term_b @ RVK::InVar(ref var_b),
+            ) => {
+                let a = variables.var_number(var_a);
+                let b = variables.var_number(var_b);
+                match (term_a, term_b) {
+                    (RVK::InVar(_), RVK::Var(_)) => {
+                        prefills.insert(a, var_a.clone());
                     }
-                    unrelations.push((ty, a, b, unrelations.len()));
-                }
-                Term::Unrelation(ty, RVK::Var(var_a), RVK::AnyVar) => {
-                    let a = variables.var_number(var_a);
-                    let b = ANYVAR;
-                    unrelations.push((ty, a, b, unrelations.len()));
-                }
-                Term::Unrelation(ty, RVK::InVar(var_a), RVK::AnyVar) => {
-                    let a = variables.var_number(&var_a);
-                    let b = ANYVAR;
-                    prefills.insert(a, var_a);
-                    unrelations.push((ty, a, b, unrelations.len()));
-                }
-                Term::Unrelation(ty, RVK::AnyVar, RVK::Var(var_b)) => {
-                    let a = ANYVAR;
-                    let b = variables.var_number(var_b);
-                    unrelations.push((ty, a, b, unrelations.len()));
+                    (RVK::Var(_), RVK::InVar(_)) => {
+                        prefills.insert(b, var_b.clone());
+                    }
+                    _ => (),
                 }
-                Term::Unrelation(ty, RVK::AnyVar, RVK::InVar(var_b)) => {
-                    let a = ANYVAR;
-                    let b = variables.var_number(&var_b);
-                    prefills.insert(b, var_b);
-                    unrelations.push((ty, a, b, unrelations.len()));
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::Var(var_a), RVK::AnyVar) => {
+                let a = variables.var_number(var_a);
+                let b = ANYVAR;
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::InVar(var_a), RVK::AnyVar) => {
+                let a = variables.var_number(&var_a);
+                let b = ANYVAR;
+                prefills.insert(a, var_a);
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::AnyVar, RVK::Var(var_b)) => {
+                let a = ANYVAR;
+                let b = variables.var_number(var_b);
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::AnyVar, RVK::InVar(var_b)) => {
+                let a = ANYVAR;
+                let b = variables.var_number(&var_b);
+                prefills.insert(b, var_b);
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::InVar(var_a), RVK::InVar(var_b)) => {
+                let a = variables.var_number(&var_a);
+                let b = variables.var_number(&var_b);
+                prefills.insert(a, var_a);
+                prefills.insert(b, var_b);
+                unrelations.push((ty, a, b, unrelations.len()));
+            }
+            Term::Unrelation(ty, RVK::AnyVar, RVK::AnyVar) => {
+                return Err(ParseError::slice(
+                    term,
+                    format!("!{ty}(_, _) does not constrain any variable, at least one side has to be a variable."),
+                )
+                .into());
+            }
+            Term::OptionalComponent(ty, var) => {
+                let index = opt_components.len();
+                let var = variables.var_number(var);
+                opt_components.push((ty.clone(), var, index));
+                accessors.push(Accessor::OptComponent(ty, var, index));
+            }
+            Term::OptionalMutComponent(ty, var) => {
+                let index = opt_components.len();
+                let var = variables.var_number(var);
+                opt_components.push((ty.clone(), var, index));
+                accessors.push(Accessor::OptMutComponent(ty, var, index));
+            }
+            Term::Singleton(ty) => {
+                accessors.push(Accessor::Singleton(ty));
+            }
+            Term::SingletonMut(ty) => {
+                accessors.push(Accessor::SingletonMut(ty));
+            }
+            Term::Aggregate(kind, ty, var) => {
+                if output != Output::Rows {
+                    return Err(ParseError::slice(
+                        term,
+                        "Aggregates can't be used in count! or exists!.".to_string(),
+                    )
+                    .into());
                 }
-                Term::Unrelation(ty, RVK::InVar(var_a), RVK::InVar(var_b)) => {
-                    let a = variables.var_number(&var_a);
-                    let b = variables.var_number(&var_b);
-                    prefills.insert(a, var_a);
-                    prefills.insert(b, var_b);
-                    unrelations.push((ty, a, b, unrelations.len()));
+                let var = variables.var_number(var);
+                if let Some(ty) = &ty {
+                    components.push((ty.clone(), var));
                 }
-                Term::Unrelation(ty, RVK::AnyVar, RVK::AnyVar) => {
-                    panic!("!{ty}(_,_) does not make sense.")
+                accessors.push(Accessor::Aggregate(kind, ty, var));
+            }
+            Term::Or(types, vars, output) => {
+                let index = ors.len();
+                let (var, other) = match vars {
+                    OrVars::Component(VK::Var(name)) => (variables.var_number(name), None),
+                    OrVars::Component(VK::InVar(name)) => {
+                        let var = variables.var_number(&name);
+                        prefills.insert(var, name);
+                        (var, None)
+                    }
+                    OrVars::Relation(a, b) => {
+                        let mut var = |kind: RVK| match kind {
+                            RVK::Var(name) => variables.var_number(name),
+                            RVK::InVar(name) => {
+                                let var = variables.var_number(&name);
+                                prefills.insert(var, name);
+                                var
+                            }
+                            RVK::AnyVar => ANYVAR,
+                        };
+                        let (a, b) = (var(a), var(b));
+                        if a == ANYVAR && b == ANYVAR {
+                            return Err(ParseError::slice(
+                                term,
+                                "(A | B)(_, _) does not make sense.".to_string(),
+                            )
+                            .into());
+                        }
+                        (a, Some(b))
+                    }
+                };
+                ors.push((types, var, other, index));
+                if output {
+                    // with an anyvar only the other side has the relation component
+                    let var = if var == ANYVAR { other.unwrap() } else { var };
+                    accessors.push(Accessor::Branch(index, var));
                 }
-                Term::OptionalComponent(ty, var) => {
-                    let index = opt_components.len();
-                    let var = variables.var_number(var);
-                    opt_components.push((ty.clone(), var, index));
-                    accessors.push(Accessor::OptComponent(ty, var, index));
+            }
+            Term::OrderBy(ty, var, order) => {
+                if aggregating {
+                    return Err(ParseError::slice(
+                        term,
+                        "order_by can't be combined with aggregates.".to_string(),
+                    )
+                    .into());
                 }
-                Term::OptionalMutComponent(ty, var) => {
-                    let index = opt_components.len();
-                    let var = variables.var_number(var);
-                    opt_components.push((ty.clone(), var, index));
-                    accessors.push(Accessor::OptMutComponent(ty, var, index));
+                let var = variables.var_number(var);
+                components.push((ty.clone(), var));
+                order_by.push((ty, var, order));
+            }
+            Term::CrossJoin => {
+                cross_join = true;
+            }
+            Term::Where(ty, varkind) => {
+                let var = match varkind {
+                    VK::Var(name) => variables.var_number(name),
+                    VK::InVar(name) => {
+                        let var = variables.var_number(&name);
+                        prefills.insert(var, name);
+                        var
+                    }
+                };
+                let closure = closures
+                    .next()
+                    .expect("Internal: closure needs to exist for where term");
+                components.push((ty.clone(), var));
+                predicates.push((ty, var, closure));
+            }
+            Term::OptionalRelation(ty, a, b) => {
+                let mut var = |kind: RVK| match kind {
+                    RVK::Var(name) => variables.var_number(name),
+                    RVK::InVar(name) => {
+                        let var = variables.var_number(&name);
+                        prefills.insert(var, name);
+                        var
+                    }
+                    RVK::AnyVar => unreachable!("Internal: optional relations have no anyvar"),
+                };
+                let a_optional =
+                    matches!(&a, RVK::Var(name) if optional_vars.contains(name.as_str()));
+                let (a, b) = (var(a), var(b));
+                // only the optional var needs to have the relation
+                if a_optional {
+                    relations.push((ty.clone(), a, ANYVAR));
+                    opt_relations.push((ty, a, b, a));
+                } else {
+                    relations.push((ty.clone(), ANYVAR, b));
+                    opt_relations.push((ty, a, b, b));
                 }
-                Term::Singleton(ty) => {
-                    accessors.push(Accessor::Singleton(ty));
+            }
+            Term::Path(ty, a, b, min, max) => {
+                let mut var = |kind: RVK| match kind {
+                    RVK::Var(name) => Some(variables.var_number(name)),
+                    RVK::InVar(name) => {
+                        let var = variables.var_number(&name);
+                        prefills.insert(var, name);
+                        Some(var)
+                    }
+                    RVK::AnyVar => None,
+                };
+                let (Some(a), Some(b)) = (var(a), var(b)) else {
+                    return Err(ParseError::slice(
+                        term,
+                        format!("Path terms need a variable on both sides, use {ty}(a, _) to match any related entity."),
+                    )
+                    .into());
+                };
+                if a == b {
+                    return Err(ParseError::slice(
+                        term,
+                        "A path term needs two different variables.".to_string(),
+                    )
+                    .into());
                 }
-                Term::SingletonMut(ty) => {
-                    accessors.push(Accessor::SingletonMut(ty));
+                // without zero hops both sides need the relation component,
+                // so it can still narrow down the archetypes
+                if min > 0 {
+                    relations.push((ty.clone(), a, ANYVAR));
+                    relations.push((ty.clone(), ANYVAR, b));
                 }
-            };
-            buffer.clear();
-        } else {
-            match next {
-                None => break,
-                Some(other) => buffer.push(other),
+                paths.push((ty, a, b, min, max));
             }
+            Term::Hops(a, b) => {
+                let a = variables.var_number(a);
+                let b = variables.var_number(b);
+                hops.push((term, a, b));
+                accessors.push(Accessor::Hops(a, b));
+            }
+        };
+    }
+
+    // the matched branch of an OR relation is not kept while sorting
+    let join_branch =
+        |it: &Accessor| matches!(it, Accessor::Branch(index, _) if is_join_or(&ors[*index]));
+    if !order_by.is_empty() && accessors.iter().any(join_branch) {
+        return Err(ParseError::slice(
+            &tokens,
+            "order_by can't be combined with the matched branch of (A | B)(a, b).".to_string(),
+        )
+        .into());
+    }
+
+    // rows with optional relations don't always have an entity for every variable
+    if !opt_relations.is_empty()
+        && (aggregating || !order_by.is_empty() || ors.iter().any(is_join_or))
+    {
+        return Err(ParseError::slice(
+            &tokens,
+            "Optional relations can't be combined with aggregates, order_by or (A | B)(a, b)."
+                .to_string(),
+        )
+        .into());
+    }
+
+    // the hop count is only known while the path is traversed, not when rows are collected
+    if !hops.is_empty() && (!order_by.is_empty() || ors.iter().any(is_join_or)) {
+        return Err(ParseError::slice(
+            &tokens,
+            "hops(a, b) can't be combined with order_by or (A | B)(a, b).".to_string(),
+        )
+        .into());
+    }
+    for (term, a, b) in hops {
+        let connects =
+            |(_, from, to, _, _): &Path| (*from, *to) == (a, b) || (*from, *to) == (b, a);
+        if !paths.iter().any(connects) {
+            return Err(ParseError::slice(
+                term,
+                "hops(a, b) needs a path term between a and b, like Rel+(a, b).".to_string(),
+            )
+            .into());
         }
     }
 
+    if components.is_empty() && relations.is_empty() && ors.is_empty() {
+        let message = "A query needs at least one Component or Relation term, like Name(a).";
+        return Err(ParseError::slice(&tokens, message.to_string()).into());
+    }
+
+    let var_names: HashMap<isize, String> = variables
+        .variables
+        .iter()
+        .map(|(name, var)| (*var, name.clone()))
+        .collect();
     let mut vars: Vec<_> = variables.variables.into_values().collect();
     vars.sort();
 
+    if !cross_join
+        && let Some(var) =
+            disconnected_var(&vars, &prefills, &relations, &ors, &opt_relations, &paths)
+    {
+        let name = &var_names[&var];
+        return Err(ParseError::slice(
+            first_use[name],
+            format!("Variable `{name}` is not connected to the rest of the query by a relation. Cross joins are not supported, use nested queries instead or add `cross` to the query."),
+        )
+        .into());
+    }
+
+    if cross_join
+        && let Some(var) = unconstrained_cross_var(
+            &vars,
+            &prefills,
+            &components,
+            &relations,
+            &ors,
+            &opt_relations,
+            &paths,
+        )
+    {
+        let name = &var_names[&var];
+        return Err(ParseError::slice(
+            first_use[name],
+            format!("Variable `{name}` is cross joined, but has no Component or Relation term to scan for, like Name({name})."),
+        )
+        .into());
+    }
+
+    // nothing is handed out, so nothing needs to be borrowed
+    if output != Output::Rows {
+        accessors.clear();
+        opt_components.clear();
+        order_by.clear();
+    }
+
     let generator = Generator {
         vars,
         prefills,
@@ -347,43 +735,190 @@ fn inner(input: TokenStream) -> Result<TokenStream, MacroError> {
         unequals,
         accessors,
         unrelations,
+        cached,
+        output,
+        order_by,
+        ors,
+        cross_join,
+        opt_relations,
+        predicates,
+        paths,
+        captured: false,
     };
 
     let result = generator.generate(&world);
 
     //eprintln!("{}", &result);
-    Ok(result.parse().unwrap())
+    Ok(result
+        .parse()
+        .expect("Internal: generated code has to be valid Rust tokens"))
 }
 
-/// the parser treats the identifier _ of anyvars as normal variable names
-/// this function changes those to the proper anyvar enum variant
-fn transform_anyvars(input: Term) -> Term {
-    match input {
-        Term::Relation(ty, var_a, var_b) => {
-            Term::Relation(ty, transform_var(var_a), transform_var(var_b))
+/// names of the variables a term refers to, without anyvars
+fn term_vars(term: &Term) -> Vec<&str> {
+    fn var(kind: &VK) -> &str {
+        match kind {
+            VK::Var(name) | VK::InVar(name) => name,
         }
-        Term::Unrelation(ty, var_a, var_b) => {
-            Term::Unrelation(ty, transform_var(var_a), transform_var(var_b))
-        }
-        no_transform => no_transform,
     }
+    fn relation_vars<'a>(a: &'a RVK, b: &'a RVK) -> Vec<&'a str> {
+        [a, b]
+            .into_iter()
+            .filter_map(|kind| match kind {
+                RVK::Var(name) | RVK::InVar(name) => Some(name.as_str()),
+                RVK::AnyVar => None,
+            })
+            .collect()
+    }
+    match term {
+        Term::ComponentVar(_, kind)
+        | Term::MutComponentVar(_, kind)
+        | Term::NoOutComponentVar(_, kind)
+        | Term::Where(_, kind)
+        | Term::Or(_, OrVars::Component(kind), _) => vec![var(kind)],
+        Term::Relation(_, a, b)
+        | Term::Unrelation(_, a, b)
+        | Term::OptionalRelation(_, a, b)
+        | Term::Path(_, a, b, _, _)
+        | Term::Or(_, OrVars::Relation(a, b), _) => relation_vars(a, b),
+        Term::Hops(a, b) => vec![a.as_str(), b.as_str()],
+        Term::ConstraintUnequal(a, b) => vec![var(a), var(b)],
+        Term::OutVar(name)
+        | Term::Uncomponent(_, name)
+        | Term::OptionalComponent(_, name)
+        | Term::OptionalMutComponent(_, name)
+        | Term::Aggregate(_, _, name)
+        | Term::OrderBy(_, name, _) => vec![name.as_str()],
+        Term::Singleton(_) | Term::SingletonMut(_) | Term::CrossJoin => Vec::new(),
+    }
+}
+
+fn optional_var_error(term: &[Token<Span>], var: &str) -> MacroError {
+    ParseError::slice(
+        term,
+        format!("Variable '{var}' is bound by an optional relation, it can only be used in Component({var})? and &{var}."),
+    )
+    .into()
 }
 
-fn transform_var(input: RVK) -> RVK {
-    match input {
-        RVK::Var(ref var) => {
-            if var == "_" {
-                RVK::AnyVar
-            } else {
-                input
+/// a variable that would need a second archetype scan,
+/// because it can't be reached from the invars or the first variable over relations
+fn disconnected_var(
+    vars: &[isize],
+    prefills: &HashMap<isize, String>,
+    relations: &[Relation],
+    ors: &[Or],
+    opt_relations: &[OptRelation],
+    paths: &[Path],
+) -> Option<isize> {
+    let edges: Vec<(isize, isize)> = relations
+        .iter()
+        .map(|(_, a, b)| (*a, *b))
+        .chain(ors.iter().filter_map(|(_, a, b, _)| b.map(|b| (*a, b))))
+        .chain(opt_relations.iter().map(|(_, a, b, _)| (*a, *b)))
+        .chain(paths.iter().map(|(_, a, b, _, _)| (*a, *b)))
+        .filter(|(a, b)| *a != ANYVAR && *b != ANYVAR)
+        .collect();
+    let mut reached: Vec<isize> = prefills.keys().copied().collect();
+    let mut started = false;
+    loop {
+        while let Some((a, b)) = edges
+            .iter()
+            .find(|(a, b)| reached.contains(a) != reached.contains(b))
+        {
+            reached.push(if reached.contains(a) { *b } else { *a });
+        }
+        match vars.iter().find(|var| !reached.contains(var)) {
+            Some(var) if started => return Some(*var),
+            Some(var) => {
+                started = true;
+                reached.push(*var);
             }
+            None => return None,
+        }
+    }
+}
+
+/// With `cross` every group of connected variables gets its own archetype scan.
+/// Returns a variable of a group without an invar that has no Component or Relation term,
+/// so there is nothing to scan for.
+fn unconstrained_cross_var(
+    vars: &[isize],
+    prefills: &HashMap<isize, String>,
+    components: &[Component],
+    relations: &[Relation],
+    ors: &[Or],
+    opt_relations: &[OptRelation],
+    paths: &[Path],
+) -> Option<isize> {
+    let edges: Vec<(isize, isize)> = relations
+        .iter()
+        .map(|(_, a, b)| (*a, *b))
+        .chain(ors.iter().filter_map(|(_, a, b, _)| b.map(|b| (*a, b))))
+        .chain(opt_relations.iter().map(|(_, a, b, _)| (*a, *b)))
+        .chain(paths.iter().map(|(_, a, b, _, _)| (*a, *b)))
+        .filter(|(a, b)| *a != ANYVAR && *b != ANYVAR)
+        .collect();
+    let constrained = |var: isize| {
+        prefills.contains_key(&var)
+            || components.iter().any(|(_, v)| *v == var)
+            || relations.iter().any(|(_, a, b)| *a == var || *b == var)
+            || ors.iter().any(|(_, a, b, _)| *a == var || *b == Some(var))
+            || paths.iter().any(|(_, a, b, _, _)| *a == var || *b == var)
+    };
+    let mut reached: Vec<isize> = Vec::new();
+    while let Some(start) = vars.iter().find(|var| !reached.contains(var)) {
+        let mut group = vec![*start];
+        while let Some((a, b)) = edges
+            .iter()
+            .find(|(a, b)| group.contains(a) != group.contains(b))
+        {
+            group.push(if group.contains(a) { *b } else { *a });
+        }
+        if !group.iter().any(|var| constrained(*var)) {
+            return Some(*start);
         }
-        RVK::InVar(ref var) => {
-            if var == "_" {
-                panic!("An Invar may not also be an AnyVar!\n*_ is not allowed!");
+        reached.extend(group);
+    }
+    None
+}
+
+/// source of the closures after `=>` in `where(..)` terms, in query order
+fn where_closures(input: &[TokenTree]) -> Vec<String> {
+    let mut result = Vec::new();
+    for pair in input.windows(2) {
+        if let [TokenTree::Ident(ident), TokenTree::Group(group)] = pair
+            && ident.to_string() == "where"
+        {
+            let inner: Vec<TokenTree> = group.stream().into_iter().collect();
+            let arrow = inner.windows(2).position(|it| {
+                matches!(it, [TokenTree::Punct(a), TokenTree::Punct(b)]
+                    if a.as_char() == '=' && b.as_char() == '>')
+            });
+            if let Some(arrow) = arrow {
+                let closure = TokenStream::from_iter(inner[arrow + 2..].iter().cloned());
+                result.push(closure.to_string());
             }
-            input
         }
-        RVK::AnyVar => input,
+    }
+    return result;
+}
+
+/// converts proc_macro tokens into the tokens of the shared grammar
+fn convert_token(tt: TokenTree) -> Token<Span> {
+    match tt {
+        TokenTree::Ident(ident) => Token::Ident(ident.to_string(), ident.span()),
+        TokenTree::Punct(punct) => Token::Punct(punct.as_char(), punct.span()),
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => Token::Group(
+            group.stream().into_iter().map(convert_token).collect(),
+            group.span(),
+        ),
+        // a `$ty` passed in by macro_rules
+        TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+            Token::Ident(group.stream().to_string(), group.span())
+        }
+        // only parentheses are part of the grammar, other groups can appear in types like `[u8; 4]`
+        TokenTree::Group(group) => Token::Other(group.to_string(), group.span()),
+        TokenTree::Literal(lit) => Token::Other(lit.to_string(), lit.span()),
     }
 }
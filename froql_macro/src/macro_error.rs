@@ -1,3 +1,4 @@
+use froql_grammar::ParseError;
 use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 
 // mostly taken from syn
@@ -8,23 +9,20 @@ pub struct MacroError {
 }
 
 impl MacroError {
-    pub fn slice(arr: &[TokenTree], message: String) -> Self {
-        let end = arr.last().expect(&message).span();
-        let start = arr[0].span();
+    pub fn new(start: Span, end: Span, message: impl Into<String>) -> Self {
         Self {
             start,
             end,
-            message,
+            message: message.into(),
         }
     }
 
-    pub fn start_end(start: &TokenTree, end: &TokenTree, message: String) -> Self {
-        let start = start.span();
-        let end = end.span();
+    /// error without a more specific location, points at the whole macro invocation
+    pub fn call_site(message: impl Into<String>) -> Self {
         Self {
-            start,
-            end,
-            message,
+            start: Span::call_site(),
+            end: Span::call_site(),
+            message: message.into(),
         }
     }
 
@@ -51,3 +49,13 @@ impl MacroError {
         ])
     }
 }
+
+impl From<ParseError<Span>> for MacroError {
+    fn from(err: ParseError<Span>) -> Self {
+        Self {
+            start: err.start,
+            end: err.end,
+            message: err.message,
+        }
+    }
+}